@@ -0,0 +1,182 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    license_allowlist::IgnorePackage, license_changes::normalized_license,
+    license_info::LicenseInfo, CheckOutput, UnsafeReason,
+};
+
+/// Default location of the baseline file
+pub const DEFAULT_BASELINE_PATH: &str = "conda-deny-baseline.json";
+
+/// Known findings that do not fail the check
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Baseline {
+    pub entries: Vec<BaselineEntry>,
+    /// The licenses of all checked packages, so that safe packages whose license changed
+    /// are detected as well
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub licenses: Vec<BaselineEntry>,
+}
+
+/// A known finding: a package in a version range with a specific license
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BaselineEntry {
+    /// Package name and version range, written as an exact version
+    #[serde(flatten)]
+    pub package: IgnorePackage,
+    pub license: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<UnsafeReason>,
+}
+
+impl BaselineEntry {
+    fn from_license_info(license_info: &LicenseInfo) -> Self {
+        BaselineEntry {
+            package: IgnorePackage::new(
+                license_info.package_name.clone(),
+                license_info
+                    .version
+                    .as_ref()
+                    .map(|version| format!("=={version}")),
+            ),
+            license: license_info.license.license_string(),
+            reason: license_info.reason,
+        }
+    }
+
+    /// Returns true if the finding is covered by this entry. A changed license is a new finding,
+    /// but equivalent ways of writing the same license are not.
+    fn matches(&self, license_info: &LicenseInfo) -> Result<bool> {
+        Ok(normalized_license(self.license.as_deref())
+            == normalized_license(license_info.license.license_string().as_deref())
+            && self
+                .package
+                .matches(&license_info.package_name, license_info.version.as_deref())?)
+    }
+}
+
+impl Baseline {
+    /// Records the unsafe dependencies of a check and the licenses of all checked packages.
+    pub fn from_check_output(check_output: &CheckOutput) -> Self {
+        let entries = sorted_entries(
+            check_output
+                .unsafe_dependencies
+                .iter()
+                .map(BaselineEntry::from_license_info),
+        );
+        let licenses =
+            sorted_entries(
+                check_output
+                    .checked_dependencies()
+                    .map(|license_info| BaselineEntry {
+                        reason: None,
+                        ..BaselineEntry::from_license_info(license_info)
+                    }),
+            );
+        Baseline { entries, licenses }
+    }
+
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read baseline file: {path:?}"))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse baseline file: {path:?}"))
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let content = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, content + "\n")
+            .with_context(|| format!("Failed to write baseline file: {path:?}"))
+    }
+
+    /// Moves the unsafe dependencies that are covered by the baseline to
+    /// `baselined_dependencies` and records the entries that no longer match any finding.
+    pub fn apply(&self, check_output: &mut CheckOutput) -> Result<()> {
+        let mut used = vec![false; self.entries.len()];
+        let mut remaining = Vec::new();
+        for license_info in std::mem::take(&mut check_output.unsafe_dependencies) {
+            let mut baselined = false;
+            for (entry, used) in self.entries.iter().zip(used.iter_mut()) {
+                if entry.matches(&license_info)? {
+                    *used = true;
+                    baselined = true;
+                }
+            }
+            if baselined {
+                check_output.baselined_dependencies.push(license_info);
+            } else {
+                remaining.push(license_info);
+            }
+        }
+        check_output.unsafe_dependencies = remaining;
+        check_output.fixed_baseline_entries = self
+            .entries
+            .iter()
+            .zip(used)
+            .filter(|(_, used)| !used)
+            .map(|(entry, _)| entry.clone())
+            .collect();
+        Ok(())
+    }
+}
+
+/// Sorts the entries and removes duplicates, as the same package is reported once per
+/// platform and policy.
+fn sorted_entries(entries: impl Iterator<Item = BaselineEntry>) -> Vec<BaselineEntry> {
+    let mut entries: Vec<BaselineEntry> = entries.collect();
+    entries.sort_by(|a, b| {
+        (a.package.to_string(), &a.license).cmp(&(b.package.to_string(), &b.license))
+    });
+    entries.dedup();
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn license_info(name: &str, version: &str, license: &str) -> LicenseInfo {
+        LicenseInfo {
+            reason: Some(UnsafeReason::NonSpdx),
+            ..LicenseInfo::for_test(name, version, license)
+        }
+    }
+
+    #[test]
+    fn test_apply_baseline() {
+        let baseline = Baseline::from_check_output(&CheckOutput {
+            unsafe_dependencies: vec![
+                license_info("foo", "1.0", "Custom"),
+                license_info("bar", "2.0", "Custom"),
+                license_info("qux", "1.0", "MIT OR GPL-3.0-only"),
+            ],
+            ..Default::default()
+        });
+        assert_eq!(baseline.entries.len(), 3);
+        assert_eq!(baseline.entries[0].package.to_string(), "bar ==2.0");
+
+        let mut check_output = CheckOutput {
+            unsafe_dependencies: vec![
+                // Known finding
+                license_info("foo", "1.0", "Custom"),
+                // The license changed, so this is a new finding
+                license_info("foo", "1.0", "Other"),
+                license_info("baz", "1.0", "Custom"),
+                // The same license, written differently
+                license_info("qux", "1.0", "GPL-3.0-only OR MIT"),
+            ],
+            ..Default::default()
+        };
+        baseline.apply(&mut check_output).unwrap();
+
+        assert_eq!(check_output.baselined_dependencies.len(), 2);
+        assert_eq!(check_output.unsafe_dependencies.len(), 2);
+        assert_eq!(
+            check_output.fixed_baseline_entries,
+            vec![baseline.entries[0].clone()]
+        );
+    }
+}
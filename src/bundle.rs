@@ -1,6 +1,6 @@
 use anyhow::{anyhow, Context, Result};
 use log::{debug, trace, warn};
-use rattler_conda_types::PrefixRecord;
+use rattler_conda_types::{PackageRecord, PrefixRecord};
 use rattler_lock::CondaPackageData;
 use rattler_package_streaming::{
     read::stream_tar_bz2,
@@ -9,6 +9,7 @@ use rattler_package_streaming::{
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 use reqwest::Url;
 use std::{
+    collections::BTreeSet,
     io::{Cursor, Read, Seek, Write},
     path::{Path, PathBuf},
     sync::Arc,
@@ -18,15 +19,110 @@ use indicatif::{ProgressBar, ProgressStyle};
 
 use rattler_networking::AuthenticationMiddleware;
 
-use crate::{pixi_lock::get_conda_packages_for_pixi_lock, CondaDenyBundleConfig, LockfileOrPrefix};
+use crate::{
+    pixi_lock::{get_locked_conda_packages, read_pixi_lock},
+    CondaDenyBundleConfig, LockfileOrPrefix,
+};
+
+/// Directory the license files are bundled into by default
+pub const DEFAULT_BUNDLE_DIRECTORY: &str = "bundle";
+
+/// Returns the directory with the license texts collected by `bundle`.
+///
+/// An explicitly given directory must exist, the default directory is only used if it exists.
+pub(crate) fn existing_bundle_directory(directory: Option<&Path>) -> Result<Option<PathBuf>> {
+    match directory {
+        Some(directory) => {
+            anyhow::ensure!(
+                directory.is_dir(),
+                "The bundle directory {directory:?} does not exist, run `conda-deny bundle` first"
+            );
+            Ok(Some(directory.to_path_buf()))
+        }
+        None => Ok(
+            Some(PathBuf::from(DEFAULT_BUNDLE_DIRECTORY)).filter(|directory| directory.is_dir())
+        ),
+    }
+}
+
+fn files_in(directory: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(directory) else {
+        return Vec::new();
+    };
+    let mut files = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            files.extend(files_in(&path));
+        } else {
+            files.push(path);
+        }
+    }
+    files.sort();
+    files
+}
+
+/// Returns the license files that `bundle` collected for a package, with their names relative
+/// to the directory of the package.
+pub(crate) fn bundled_license_files(
+    bundle_directory: &Path,
+    package_name: &str,
+    version: &str,
+    build: &str,
+) -> Vec<(String, PathBuf)> {
+    let package_directory = bundle_directory.join(format!("{package_name}-{version}-{build}"));
+    files_in(&package_directory)
+        .into_iter()
+        .map(|file| {
+            let name = file
+                .strip_prefix(&package_directory)
+                .unwrap_or(&file)
+                .display()
+                .to_string()
+                .replace('\\', "/");
+            (name, file)
+        })
+        .collect()
+}
 
 type LicenseContents = (String, Vec<u8>);
 
+/// Package metadata that is extracted together with the license files
+const ABOUT_FILE: &str = "info/about.json";
+
+/// What the notice file shows about a package
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct PackageDescription {
+    name: String,
+    version: Option<String>,
+    license: Option<String>,
+    /// From `info/about.json` of the package
+    homepage: Option<String>,
+}
+
+impl PackageDescription {
+    fn from_package_record(record: &PackageRecord) -> Self {
+        PackageDescription {
+            name: record.name.as_source().to_string(),
+            version: Some(record.version.to_string()),
+            license: record.license.clone(),
+            homepage: None,
+        }
+    }
+}
+
+/// The license files of a package and its homepage
+struct PackageContents {
+    license_files: Vec<LicenseContents>,
+    homepage: Option<String>,
+}
+
 #[derive(Debug, Clone)]
 struct LicenseFile {
     package_name: String,
     filename: String,
     license_text: Vec<u8>,
+    package: PackageDescription,
 }
 
 pub fn bundle<W: Write>(config: CondaDenyBundleConfig, mut out: W) -> Result<()> {
@@ -37,13 +133,19 @@ pub fn bundle<W: Write>(config: CondaDenyBundleConfig, mut out: W) -> Result<()>
             let lockfiles = lockfile_spec.lockfiles.clone();
             let mut conda_packages = vec![];
             for lockfile in lockfiles.clone() {
-                conda_packages.extend(get_conda_packages_for_pixi_lock(
-                    &lockfile,
+                let locked_packages = get_locked_conda_packages(
+                    &read_pixi_lock(&lockfile)?,
+                    &lockfile.display().to_string(),
                     &lockfile_spec.environments,
                     &lockfile_spec.platforms,
                     lockfile_spec.ignore_pypi,
-                    &[],
-                )?);
+                    &config.ignore_packages,
+                )?;
+                conda_packages.extend(
+                    locked_packages
+                        .into_iter()
+                        .flat_map(|(_, packages)| packages),
+                );
             }
 
             bundle_license_files(
@@ -56,7 +158,15 @@ pub fn bundle<W: Write>(config: CondaDenyBundleConfig, mut out: W) -> Result<()>
                         })
                         .cloned()
                 },
-                |pkg| pkg.name().as_source().to_string(),
+                |pkg| match pkg.record() {
+                    Some(record) => PackageDescription::from_package_record(record),
+                    None => PackageDescription {
+                        name: pkg.name().as_source().to_string(),
+                        version: None,
+                        license: None,
+                        homepage: None,
+                    },
+                },
                 |pkg| {
                     if let Some(record) = pkg.record() {
                         format!(
@@ -82,7 +192,9 @@ pub fn bundle<W: Write>(config: CondaDenyBundleConfig, mut out: W) -> Result<()>
             bundle_license_files(
                 prefix_records,
                 |rec: &PrefixRecord| Ok(rec.repodata_record.url.clone()),
-                |rec: &PrefixRecord| rec.file_name(),
+                |rec: &PrefixRecord| {
+                    PackageDescription::from_package_record(&rec.repodata_record.package_record)
+                },
                 |rec| {
                     Path::new(&rec.file_name())
                         .file_stem()
@@ -94,20 +206,28 @@ pub fn bundle<W: Write>(config: CondaDenyBundleConfig, mut out: W) -> Result<()>
         }
     };
 
-    let path = config.directory.unwrap_or(PathBuf::from("bundle"));
+    let path = config
+        .directory
+        .unwrap_or(PathBuf::from(DEFAULT_BUNDLE_DIRECTORY));
     let path = Path::new(&path);
-    create_license_file_directory(path, license_files)
+    create_license_file_directory(path, &license_files)
         .with_context(|| format!("Failed to create license file directory: {path:?}"))?;
     writeln!(out, "License files written to: {path:?}")
         .with_context(|| format!("Failed to write license file: {path:?}"))?;
 
+    if let Some(notice_file) = &config.notice_file {
+        std::fs::write(notice_file, format_notice(&license_files, notice_file))
+            .with_context(|| format!("Failed to write notice file: {notice_file:?}"))?;
+        writeln!(out, "Notice file written to: {notice_file:?}")?;
+    }
+
     Ok(())
 }
 
 fn bundle_license_files<I>(
     items: I,
     get_url: impl Fn(&I::Item) -> Result<Url> + Sync,
-    get_name: impl Fn(&I::Item) -> String + Sync,
+    get_package: impl Fn(&I::Item) -> PackageDescription + Sync,
     get_filename: impl Fn(&I::Item) -> String + Sync,
 ) -> Result<Vec<LicenseFile>>
 where
@@ -121,29 +241,33 @@ where
         .par_iter()
         .map(|item| -> Result<Vec<LicenseFile>> {
             bar.inc(1);
-            bar.set_message(format!("📦 Bundling licenses for: {}", get_name(item)));
+            let mut package = get_package(item);
+            bar.set_message(format!("📦 Bundling licenses for: {}", package.name));
 
             let rt = tokio::runtime::Runtime::new()?;
 
             let url = get_url(item)?;
-            let files = rt
-                .block_on(get_license_files_from_url(url))
-                .with_context(|| format!("Failed to process conda package: {}", get_name(item)))?;
+            let contents = rt
+                .block_on(get_package_contents_from_url(url))
+                .with_context(|| format!("Failed to process conda package: {}", package.name))?;
 
             debug!(
                 "Received {} license files for {}",
-                files.len(),
-                get_name(item)
+                contents.license_files.len(),
+                package.name
             );
-            trace!("License files: {files:?}");
+            trace!("License files: {:?}", contents.license_files);
 
+            package.homepage = contents.homepage;
             let package_name = get_filename(item);
-            Ok(files
+            Ok(contents
+                .license_files
                 .into_iter()
                 .map(|(filename, license_text)| LicenseFile {
                     package_name: package_name.clone(),
                     filename,
                     license_text,
+                    package: package.clone(),
                 })
                 .collect())
         })
@@ -156,7 +280,7 @@ where
     Ok(license_files)
 }
 
-fn create_license_file_directory(path: &Path, license_files: Vec<LicenseFile>) -> Result<()> {
+fn create_license_file_directory(path: &Path, license_files: &[LicenseFile]) -> Result<()> {
     if path.exists() {
         warn!("Output directory already exists. Removing and creating a new one: {path:?}");
         std::fs::remove_dir_all(path)
@@ -164,7 +288,7 @@ fn create_license_file_directory(path: &Path, license_files: Vec<LicenseFile>) -
     }
     std::fs::create_dir_all(path)
         .with_context(|| format!("Failed to create output directory: {path:?}"))?;
-    for license_file in &license_files {
+    for license_file in license_files {
         let package_name = license_file.package_name.to_string();
         let package_path = path.join(package_name);
         std::fs::create_dir_all(&package_path)
@@ -194,6 +318,107 @@ fn create_license_file_directory(path: &Path, license_files: Vec<LicenseFile>) -
     Ok(())
 }
 
+/// License files with the same text, and the packages they apply to
+struct NoticeEntry<'a> {
+    text: String,
+    packages: BTreeSet<&'a PackageDescription>,
+}
+
+/// Groups the license files by text, so that identical texts are only listed once.
+fn notice_entries(license_files: &[LicenseFile]) -> Vec<NoticeEntry<'_>> {
+    let mut entries: Vec<NoticeEntry> = Vec::new();
+    for license_file in license_files {
+        let text = String::from_utf8_lossy(&license_file.license_text)
+            .trim()
+            .replace("\r\n", "\n");
+        match entries.iter_mut().find(|entry| entry.text == text) {
+            Some(entry) => {
+                entry.packages.insert(&license_file.package);
+            }
+            None => entries.push(NoticeEntry {
+                text,
+                packages: BTreeSet::from([&license_file.package]),
+            }),
+        }
+    }
+    entries.sort_by(|a, b| a.packages.first().cmp(&b.packages.first()));
+    entries
+}
+
+fn package_header(package: &PackageDescription) -> Vec<(&'static str, String)> {
+    let mut header = vec![(
+        "Package",
+        match &package.version {
+            Some(version) => format!("{} {version}", package.name),
+            None => package.name.clone(),
+        },
+    )];
+    header.push((
+        "License",
+        package
+            .license
+            .clone()
+            .unwrap_or_else(|| "no license".to_string()),
+    ));
+    if let Some(homepage) = &package.homepage {
+        header.push(("Homepage", homepage.clone()));
+    }
+    header
+}
+
+/// Formats the license texts as a single notice file, in Markdown if the file ends with `.md`.
+fn format_notice(license_files: &[LicenseFile], path: &Path) -> String {
+    let markdown = path.extension().is_some_and(|extension| extension == "md");
+    let entries = notice_entries(license_files);
+    let mut output = String::new();
+
+    if markdown {
+        output.push_str(
+            "# Third-party notices\n\nThis software includes the following third-party packages.\n",
+        );
+        for entry in &entries {
+            let names: Vec<&str> = entry
+                .packages
+                .iter()
+                .map(|package| package.name.as_str())
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .collect();
+            output.push_str(&format!("\n## {}\n\n", names.join(", ")));
+            for package in &entry.packages {
+                let header: Vec<String> = package_header(package)
+                    .into_iter()
+                    .map(|(key, value)| format!("{key}: {value}"))
+                    .collect();
+                output.push_str(&format!("- {}\n", header.join(", ")));
+            }
+            // The fence must be longer than any backtick sequence in the text
+            let longest_backticks = entry
+                .text
+                .split(|c| c != '`')
+                .map(str::len)
+                .max()
+                .unwrap_or(0);
+            let fence = "`".repeat(longest_backticks.max(2) + 1);
+            output.push_str(&format!("\n{fence}text\n{}\n{fence}\n", entry.text));
+        }
+    } else {
+        output.push_str(
+            "THIRD-PARTY NOTICES\n\nThis software includes the following third-party packages.\n",
+        );
+        for entry in &entries {
+            output.push_str(&format!("\n{}\n\n", "=".repeat(80)));
+            for package in &entry.packages {
+                for (key, value) in package_header(package) {
+                    output.push_str(&format!("{key}: {value}\n"));
+                }
+            }
+            output.push_str(&format!("\n{}\n\n{}\n", "-".repeat(80), entry.text));
+        }
+    }
+    output
+}
+
 async fn download_conda_package_as_cursor(url: Url) -> Result<std::io::Cursor<Vec<u8>>> {
     let auth_middleware = AuthenticationMiddleware::from_env_and_defaults()
         .with_context(|| "Failed to set up authentication middleware.")?;
@@ -213,22 +438,47 @@ async fn download_conda_package_as_cursor(url: Url) -> Result<std::io::Cursor<Ve
     Ok(std::io::Cursor::new(bytes.to_vec()))
 }
 
-async fn get_license_files_from_url(url: Url) -> Result<Vec<LicenseContents>> {
+async fn get_package_contents_from_url(url: Url) -> Result<PackageContents> {
     let cursor = download_conda_package_as_cursor(url.clone()).await?;
-    if url.path().ends_with(".conda") {
+    let files = if url.path().ends_with(".conda") {
         license_files_from_dot_conda(cursor)
     } else {
         license_files_from_tarbz2(cursor)
+    }?;
+
+    let mut contents = PackageContents {
+        license_files: Vec::new(),
+        homepage: None,
+    };
+    for (filename, content) in files {
+        if filename == ABOUT_FILE {
+            contents.homepage = homepage_from_about(&content);
+        } else {
+            contents.license_files.push((filename, content));
+        }
     }
+    Ok(contents)
+}
+
+/// Returns the homepage from the contents of `info/about.json`.
+fn homepage_from_about(about: &[u8]) -> Option<String> {
+    let about: serde_json::Value = serde_json::from_slice(about).ok()?;
+    about["home"]
+        .as_str()
+        .filter(|home| !home.is_empty())
+        .map(str::to_string)
 }
 
+/// Extracts the license files and `info/about.json` from a package archive.
 fn extract_license_files<R: Read>(archive: &mut tar::Archive<R>) -> Result<Vec<LicenseContents>> {
     let mut files_and_license_text = Vec::new();
 
     for entry in archive.entries()? {
         let mut file = entry?;
         let path = file.path()?.to_path_buf();
-        if file.header().entry_type().is_file() && path.starts_with("info/licenses") {
+        if file.header().entry_type().is_file()
+            && (path.starts_with("info/licenses") || path == Path::new(ABOUT_FILE))
+        {
             let mut content: Vec<u8> = Vec::new();
             file.read_to_end(&mut content)?;
 
@@ -288,3 +538,68 @@ fn setup_bundle_bar(steps: u64) -> ProgressBar {
 
     bar
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn license_file(name: &str, license: &str, text: &str) -> LicenseFile {
+        LicenseFile {
+            package_name: format!("{name}-1.0-h0_0"),
+            filename: "info/licenses/LICENSE".to_string(),
+            license_text: text.as_bytes().to_vec(),
+            package: PackageDescription {
+                name: name.to_string(),
+                version: Some("1.0".to_string()),
+                license: Some(license.to_string()),
+                homepage: Some(format!("https://example.com/{name}")),
+            },
+        }
+    }
+
+    #[test]
+    fn test_format_notice_merges_identical_texts() {
+        let license_files = [
+            license_file("zlib", "Zlib", "zlib license\n"),
+            license_file("libblas", "BSD-3-Clause", "OpenBLAS license"),
+            license_file("libcblas", "BSD-3-Clause", "OpenBLAS license\r\n"),
+        ];
+
+        assert_eq!(
+            format_notice(&license_files, Path::new("NOTICE")),
+            format!(
+                "THIRD-PARTY NOTICES\n\nThis software includes the following third-party packages.\n\n{separator}\n\n\
+                 Package: libblas 1.0\nLicense: BSD-3-Clause\nHomepage: https://example.com/libblas\n\
+                 Package: libcblas 1.0\nLicense: BSD-3-Clause\nHomepage: https://example.com/libcblas\n\
+                 \n{line}\n\nOpenBLAS license\n\n{separator}\n\n\
+                 Package: zlib 1.0\nLicense: Zlib\nHomepage: https://example.com/zlib\n\
+                 \n{line}\n\nzlib license\n",
+                separator = "=".repeat(80),
+                line = "-".repeat(80)
+            )
+        );
+    }
+
+    #[test]
+    fn test_format_notice_markdown() {
+        let license_files = [license_file("ncurses", "X11", "Use ```code``` freely")];
+
+        assert_eq!(
+            format_notice(&license_files, Path::new("THIRD_PARTY_NOTICES.md")),
+            "# Third-party notices\n\nThis software includes the following third-party packages.\n\n\
+             ## ncurses\n\n\
+             - Package: ncurses 1.0, License: X11, Homepage: https://example.com/ncurses\n\n\
+             ````text\nUse ```code``` freely\n````\n"
+        );
+    }
+
+    #[test]
+    fn test_homepage_from_about() {
+        assert_eq!(
+            homepage_from_about(br#"{"home": "https://zlib.net/", "summary": "zlib"}"#),
+            Some("https://zlib.net/".to_string())
+        );
+        assert_eq!(homepage_from_about(br#"{"home": ""}"#), None);
+        assert_eq!(homepage_from_about(b"not json"), None);
+    }
+}
@@ -1,79 +1,266 @@
 use crate::{
-    collect_license_infos,
-    license_info::{LicenseInfo, LicenseState},
-    CheckOutput, CondaDenyCheckConfig, OutputFormat,
+    baseline::Baseline,
+    collect_dependency_graphs, collect_license_infos, collect_runtime_closures,
+    json_output::{checked_packages, write_json_document},
+    junit::junit_report,
+    license_changes::{
+        inconsistent_licenses, license_changes_from_baseline, license_changes_from_history,
+        LicenseChange, LicenseInconsistency,
+    },
+    license_info::{count_packages, DependencyType, IgnoredPackages, LicenseInfo},
+    markdown::format_check_output_markdown,
+    query::{
+        aggregate_check_output, filter_check_output, group_license_infos, sort_check_output,
+        GroupBy,
+    },
+    sarif::sarif_log,
+    template::{check_context, render_template},
+    CheckOutput, CondaDenyCheckConfig, OutputFormat, ReasonAction, UnsafeReason,
 };
 use anyhow::{Context, Result};
 use colored::Colorize;
 use log::debug;
 use serde::Serialize;
 use serde_json::json;
-use std::io::Write;
+use std::{collections::BTreeSet, io::Write};
 
 fn check_license_infos(config: &CondaDenyCheckConfig) -> Result<CheckOutput> {
-    let license_infos =
-        collect_license_infos(config.lockfile_or_prefix.clone(), &config.ignore_packages)
-            .with_context(|| "Fetching license information failed.")?;
+    let mut license_infos = collect_license_infos(
+        config.lockfile_or_prefix.clone(),
+        &config.ignore_packages,
+        IgnoredPackages::Keep,
+    )
+    .with_context(|| "Fetching license information failed.")?;
 
-    if config.osi {
-        debug!("Checking licenses for OSI compliance");
-        Ok(license_infos.osi_check())
-    } else {
-        debug!("Checking licenses against specified allowlist");
-        license_infos.check(config)
+    if config.pulled_in_by {
+        let graphs = collect_dependency_graphs(&config.lockfile_or_prefix)
+            .with_context(|| "Building the dependency graph failed.")?;
+        license_infos.annotate_pulled_in_by(&graphs);
+    }
+
+    if let Some(runtime_closure) = &config.runtime_closure {
+        let closures = collect_runtime_closures(&config.lockfile_or_prefix, &runtime_closure.of)
+            .with_context(|| {
+                format!(
+                    "Computing the runtime closure of {} failed.",
+                    runtime_closure.of
+                )
+            })?;
+        license_infos.annotate_runtime_closure(&closures);
     }
+
+    debug!(
+        "Checking licenses against {} named policies",
+        config.policies.len()
+    );
+    let mut check_output = license_infos.check(config)?;
+    if config.inconsistent_licenses != ReasonAction::Ignore {
+        let mut not_ignored = Vec::new();
+        for license_info in &license_infos.license_infos {
+            if license_info
+                .matching_ignore_rules(&config.ignore_packages)?
+                .is_empty()
+            {
+                not_ignored.push(license_info);
+            }
+        }
+        check_output.inconsistent_licenses = inconsistent_licenses(&not_ignored);
+    }
+    Ok(check_output)
+}
+
+fn check_output_json(check_output: &CheckOutput) -> Result<serde_json::Value> {
+    Ok(json!({
+        "packages": checked_packages(check_output)?,
+        "fixed_baseline": check_output.fixed_baseline_entries,
+        "license_changes": check_output.license_changes,
+        "inconsistent_licenses": check_output.inconsistent_licenses,
+    }))
+}
+
+/// The checked dependencies without those matched by ignore-packages, whose licenses are not tracked.
+fn not_ignored_by_rule(check_output: &CheckOutput) -> Vec<&LicenseInfo> {
+    check_output
+        .checked_dependencies()
+        .filter(|license_info| license_info.ignored_by.is_empty())
+        .collect()
+}
+
+/// Checks the licenses and applies the baseline and license history of the configuration.
+pub(crate) fn check_output(config: &CondaDenyCheckConfig) -> Result<CheckOutput> {
+    let mut check_output = check_license_infos(config)?;
+
+    // When writing a baseline, the current findings are recorded instead
+    if config.write_baseline.is_none()
+        && let Some(path) = &config.baseline
+    {
+        let baseline = Baseline::from_path(path)?;
+        baseline.apply(&mut check_output)?;
+        if config.license_changes != ReasonAction::Ignore {
+            let checked = not_ignored_by_rule(&check_output);
+            check_output.license_changes = license_changes_from_baseline(&baseline, &checked)?;
+        }
+    }
+
+    if config.license_changes != ReasonAction::Ignore
+        && let Some(path) = &config.license_history
+    {
+        let checked = not_ignored_by_rule(&check_output);
+        let changes = license_changes_from_history(path, &checked)?;
+        check_output.license_changes.extend(changes);
+    }
+    Ok(check_output)
 }
 
 pub fn check<W: Write>(check_config: CondaDenyCheckConfig, mut out: W) -> Result<()> {
-    let (safe_dependencies, unsafe_dependencies) = check_license_infos(&check_config)?;
+    let mut check_output = check_output(&check_config)?;
+
+    let written_baseline = match &check_config.write_baseline {
+        Some(path) => {
+            let baseline = Baseline::from_check_output(&check_output);
+            baseline.write(path)?;
+            Some((path, baseline.entries.len()))
+        }
+        None => None,
+    };
+
+    // The exit status considers all findings, the output only the filtered ones
+    let exit_status = match written_baseline {
+        // The recorded findings are accepted from now on
+        Some(_) => Ok(()),
+        None => check_exit_status(&check_output, &check_config),
+    };
+    if let Some(filter) = &check_config.filter {
+        filter_check_output(&mut check_output, filter);
+    }
+    sort_check_output(&mut check_output, &check_config.sort_by);
+    if check_config.aggregate {
+        aggregate_check_output(&mut check_output);
+    }
 
     match check_config.output_format {
         OutputFormat::Default => {
             writeln!(
                 out,
                 "{}",
-                format_check_output(safe_dependencies, unsafe_dependencies.clone(),)
+                format_check_output(
+                    check_output.clone(),
+                    check_config.explain,
+                    &check_config.group_by
+                )
+            )?;
+            if let Some((path, entries)) = written_baseline {
+                writeln!(
+                    out,
+                    "📝 Wrote {entries} unsafe dependencies to the baseline {}",
+                    path.display()
+                )?;
+            }
+        }
+        OutputFormat::Json | OutputFormat::JsonPretty => {
+            write_json_document(
+                &mut out,
+                "check",
+                &check_config.lockfile_or_prefix,
+                check_output_json(&check_output)?,
+                check_config.output_format,
+            )?;
+        }
+        OutputFormat::Markdown => {
+            write!(
+                out,
+                "## conda-deny check\n\n{}",
+                format_check_output_markdown(&check_output)
+            )?;
+        }
+        OutputFormat::Sarif => {
+            writeln!(
+                out,
+                "{}",
+                serde_json::to_string_pretty(&sarif_log(&check_output, &check_config)?)?
             )?;
         }
-        OutputFormat::Json => {
-            let json_output = json!({
-                "safe": safe_dependencies,
-                "unsafe": unsafe_dependencies,
-            });
-            writeln!(out, "{json_output}")?;
+        OutputFormat::Junit => {
+            write!(out, "{}", junit_report(&check_output, &check_config))?;
         }
-        OutputFormat::JsonPretty => {
-            let json_output = json!({
-                "safe": safe_dependencies,
-                "unsafe": unsafe_dependencies,
-            });
-            writeln!(out, "{}", serde_json::to_string_pretty(&json_output)?)?;
+        OutputFormat::Template => {
+            let context = check_context(&check_output, &check_config, exit_status.as_ref().err())?;
+            write!(
+                out,
+                "{}",
+                render_template(check_config.template.as_deref(), context)?
+            )?;
         }
         OutputFormat::Csv => {
             #[derive(Debug, Clone, Serialize)]
             struct LicenseInfoWithSafety {
                 package_name: String,
                 version: Option<String>,
-                license: LicenseState,
+                license: Option<String>,
                 platform: Option<String>,
                 build: Option<String>,
+                policy: Option<String>,
+                reason: Option<UnsafeReason>,
+                dependency_type: Option<DependencyType>,
                 safe: bool,
+                warn: bool,
+                ignored: bool,
+                outside_closure: bool,
+                baselined: bool,
             }
 
             let mut writer = csv::WriterBuilder::new().from_writer(vec![]);
 
-            for (license_info, is_safe) in unsafe_dependencies
-                .iter()
-                .map(|x: &LicenseInfo| (x, false))
-                .chain(safe_dependencies.iter().map(|x: &LicenseInfo| (x, true)))
+            for (license_info, is_safe, is_warn, is_ignored, is_outside_closure, is_baselined) in
+                check_output
+                    .unsafe_dependencies
+                    .iter()
+                    .map(|x: &LicenseInfo| (x, false, false, false, false, false))
+                    .chain(
+                        check_output
+                            .warn_dependencies
+                            .iter()
+                            .map(|x: &LicenseInfo| (x, false, true, false, false, false)),
+                    )
+                    .chain(
+                        check_output
+                            .ignored_dependencies
+                            .iter()
+                            .map(|x: &LicenseInfo| (x, false, false, true, false, false)),
+                    )
+                    .chain(
+                        check_output
+                            .safe_dependencies
+                            .iter()
+                            .map(|x: &LicenseInfo| (x, true, false, false, false, false)),
+                    )
+                    .chain(
+                        check_output
+                            .outside_closure_dependencies
+                            .iter()
+                            .map(|x: &LicenseInfo| (x, false, false, false, true, false)),
+                    )
+                    .chain(
+                        check_output
+                            .baselined_dependencies
+                            .iter()
+                            .map(|x: &LicenseInfo| (x, false, false, false, false, true)),
+                    )
             {
                 let extended_info = LicenseInfoWithSafety {
                     package_name: license_info.package_name.clone(),
                     version: license_info.version.clone(),
-                    license: license_info.license.clone(),
-                    platform: license_info.platform.clone(),
-                    build: license_info.build.clone(),
+                    license: license_info.license.license_string(),
+                    platform: license_info.platforms_string(),
+                    build: license_info.builds_string(),
+                    policy: license_info.policy.clone(),
+                    reason: license_info.reason,
+                    dependency_type: license_info.dependency_type,
                     safe: is_safe,
+                    warn: is_warn,
+                    ignored: is_ignored,
+                    outside_closure: is_outside_closure,
+                    baselined: is_baselined,
                 };
                 writer.serialize(&extended_info).with_context(|| {
                     format!(
@@ -86,18 +273,236 @@ pub fn check<W: Write>(check_config: CondaDenyCheckConfig, mut out: W) -> Result
         }
     }
 
-    if !unsafe_dependencies.is_empty() {
-        Err(anyhow::anyhow!("Unsafe licenses found"))
-    } else {
+    exit_status
+}
+
+/// Returns an error if the check output contains findings that are configured to fail the check.
+pub fn check_exit_status(check_output: &CheckOutput, config: &CondaDenyCheckConfig) -> Result<()> {
+    let mut failures = Vec::new();
+
+    // `--fail-on` is part of the reason actions, so every unsafe dependency fails the check
+    let reasons: BTreeSet<UnsafeReason> = check_output
+        .unsafe_dependencies
+        .iter()
+        .filter_map(|info| info.reason)
+        .collect();
+    failures.extend(reasons.into_iter().map(|reason| match reason {
+        UnsafeReason::Disallowed => "disallowed licenses",
+        UnsafeReason::Denied => "denied licenses",
+        UnsafeReason::NonSpdx => "non-SPDX licenses",
+        UnsafeReason::Missing => "packages without license",
+    }));
+
+    // License changes and inconsistencies are findings of their own, with a configurable action
+    let findings = [
+        (
+            config.license_changes,
+            !check_output.license_changes.is_empty(),
+            "license changes",
+        ),
+        (
+            config.inconsistent_licenses,
+            !check_output.inconsistent_licenses.is_empty(),
+            "inconsistent licenses",
+        ),
+    ];
+    let has_warning_findings = findings
+        .iter()
+        .any(|(action, found, _)| *action == ReasonAction::Warn && *found);
+
+    if config.fail_on_warn && (!check_output.warn_dependencies.is_empty() || has_warning_findings) {
+        failures.push("licenses with warnings");
+    }
+
+    for (action, found, failure) in findings {
+        if action == ReasonAction::Fail && found {
+            failures.push(failure);
+        }
+    }
+
+    if failures.is_empty() {
         Ok(())
+    } else {
+        Err(anyhow::anyhow!("Found {}", failures.join(", ")))
+    }
+}
+
+/// Pushes the packages with a heading per group of `--group-by`, if set.
+fn push_grouped(
+    output: &mut String,
+    license_infos: &[LicenseInfo],
+    explain: bool,
+    group_by: &[GroupBy],
+) {
+    if group_by.is_empty() {
+        push_grouped_by_policy(output, license_infos, explain);
+        return;
+    }
+    for (i, (group, license_infos)) in group_license_infos(license_infos, group_by)
+        .into_iter()
+        .enumerate()
+    {
+        if i > 0 {
+            output.push('\n');
+        }
+        output.push_str(&format!("{}:\n", group.bold()));
+        push_grouped_by_policy(output, license_infos, explain);
+    }
+}
+
+fn push_grouped_by_policy<'a>(
+    output: &mut String,
+    license_infos: impl IntoIterator<Item = &'a LicenseInfo>,
+    explain: bool,
+) {
+    let mut current_policy = None;
+    for license_info in license_infos {
+        if let Some(policy) = &license_info.policy
+            && current_policy != Some(policy)
+        {
+            if current_policy.is_some() {
+                output.push('\n');
+            }
+            output.push_str(&format!("{} {}:\n", "Policy".bold(), policy.bold()));
+            current_policy = Some(policy);
+        }
+        output.push_str(&license_info.pretty_print());
+        if !license_info.pulled_in_by.is_empty() {
+            output.push_str(&format!(
+                "    {}\n",
+                format!(
+                    "pulled in by: {}",
+                    license_info
+                        .pulled_in_by
+                        .iter()
+                        .cloned()
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+                .bright_black()
+            ));
+        }
+        if explain && let Some(explanation) = &license_info.explanation {
+            output.push_str(&explanation.pretty_print());
+        }
+    }
+}
+
+fn format_license_change(change: &LicenseChange) -> String {
+    let version = match (&change.old_version, &change.new_version) {
+        (Some(old), Some(new)) if old != new => format!("{old} → {new}"),
+        (_, Some(new)) => new.clone(),
+        (Some(old), None) => old.clone(),
+        (None, None) => String::new(),
+    };
+    format!(
+        "{} {} ({}): {} → {}\n",
+        change.package_name.blue(),
+        version.cyan(),
+        change
+            .platform
+            .as_deref()
+            .unwrap_or("unknown")
+            .bright_black(),
+        change
+            .old_license
+            .as_deref()
+            .unwrap_or("no license")
+            .yellow(),
+        change
+            .new_license
+            .as_deref()
+            .unwrap_or("no license")
+            .yellow()
+    )
+}
+
+fn format_license_inconsistency(inconsistency: &LicenseInconsistency) -> String {
+    let mut output = format!(
+        "{} {}:\n",
+        inconsistency.package_name.blue(),
+        inconsistency.version.cyan()
+    );
+    for variant in &inconsistency.licenses {
+        let builds = variant
+            .builds
+            .iter()
+            .map(|build| {
+                format!(
+                    "{} {}",
+                    build.platform.as_deref().unwrap_or("unknown"),
+                    build.build.as_deref().unwrap_or("unknown")
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        output.push_str(&format!(
+            "    {}: {}\n",
+            variant.license.as_deref().unwrap_or("no license").yellow(),
+            builds.bright_black()
+        ));
     }
+    output
 }
 
+/// Formats the check output for the terminal, with `explain` also listing safe dependencies
+/// and the evaluation of each license requirement, and the packages grouped by `group_by`.
 pub fn format_check_output(
-    safe_dependencies: Vec<LicenseInfo>,
-    unsafe_dependencies: Vec<LicenseInfo>,
+    check_output: CheckOutput,
+    explain: bool,
+    group_by: &[GroupBy],
 ) -> String {
     let mut output = String::new();
+    let CheckOutput {
+        mut safe_dependencies,
+        mut warn_dependencies,
+        mut unsafe_dependencies,
+        ignored_dependencies,
+        outside_closure_dependencies,
+        baselined_dependencies,
+        fixed_baseline_entries,
+        license_changes,
+        inconsistent_licenses,
+    } = check_output;
+    safe_dependencies.sort_by(|a, b| a.policy.cmp(&b.policy));
+    warn_dependencies.sort_by(|a, b| a.policy.cmp(&b.policy));
+    unsafe_dependencies.sort_by(|a, b| a.policy.cmp(&b.policy));
+
+    if explain && !safe_dependencies.is_empty() {
+        output.push_str(
+            format!(
+                "\n✅ {}:\n\n",
+                "The following dependencies are safe".green()
+            )
+            .as_str(),
+        );
+        push_grouped(&mut output, &safe_dependencies, explain, group_by);
+    }
+
+    if !outside_closure_dependencies.is_empty() {
+        output.push_str(
+            format!(
+                "\n⏭️ {}:\n\n",
+                "The following dependencies are outside the runtime closure and were not checked"
+                    .bright_black()
+            )
+            .as_str(),
+        );
+        for license_info in &outside_closure_dependencies {
+            output.push_str(&license_info.pretty_print());
+        }
+    }
+
+    if !warn_dependencies.is_empty() {
+        output.push_str(
+            format!(
+                "\n⚠️ {}:\n\n",
+                "The following dependencies require attention".yellow()
+            )
+            .as_str(),
+        );
+        push_grouped(&mut output, &warn_dependencies, explain, group_by);
+    }
 
     if !unsafe_dependencies.is_empty() {
         output.push_str(
@@ -107,8 +512,61 @@ pub fn format_check_output(
             )
             .as_str(),
         );
-        for license_info in &unsafe_dependencies {
-            output.push_str(&license_info.pretty_print())
+        push_grouped(&mut output, &unsafe_dependencies, explain, group_by);
+    }
+
+    if explain && !baselined_dependencies.is_empty() {
+        output.push_str(
+            format!(
+                "\n📋 {}:\n\n",
+                "The following unsafe dependencies are known from the baseline".bright_black()
+            )
+            .as_str(),
+        );
+        push_grouped(&mut output, &baselined_dependencies, explain, group_by);
+    }
+
+    if !fixed_baseline_entries.is_empty() {
+        output.push_str(
+            format!(
+                "\n🎉 {}:\n\n",
+                "The following baseline entries have been fixed and can be removed".green()
+            )
+            .as_str(),
+        );
+        for entry in &fixed_baseline_entries {
+            output.push_str(&format!(
+                "{}: {}\n",
+                entry.package.to_string().blue(),
+                entry.license.as_deref().unwrap_or("no license").yellow()
+            ));
+        }
+    }
+
+    if !license_changes.is_empty() {
+        output.push_str(
+            format!(
+                "\n🔄 {}:\n\n",
+                "The following packages changed their license".yellow()
+            )
+            .as_str(),
+        );
+        for change in &license_changes {
+            output.push_str(&format_license_change(change));
+        }
+    }
+
+    if !inconsistent_licenses.is_empty() {
+        output.push_str(
+            format!(
+                "\n🧩 {}:\n\n",
+                "The following packages have different licenses on different platforms or builds"
+                    .yellow()
+            )
+            .as_str(),
+        );
+        for inconsistency in &inconsistent_licenses {
+            output.push_str(&format_license_inconsistency(inconsistency));
         }
     }
 
@@ -124,11 +582,58 @@ pub fn format_check_output(
         ));
     }
 
-    output.push_str(&format!(
-        "\nThere were {} safe licenses and {} unsafe licenses.",
-        safe_dependencies.len().to_string().green(),
-        unsafe_dependencies.len().to_string().red()
+    let mut counts = vec![format!(
+        "{} safe licenses",
+        count_packages(&safe_dependencies).to_string().green()
+    )];
+    if !warn_dependencies.is_empty() {
+        counts.push(format!(
+            "{} licenses with warnings",
+            count_packages(&warn_dependencies).to_string().yellow()
+        ));
+    }
+    counts.push(format!(
+        "{} unsafe licenses",
+        count_packages(&unsafe_dependencies).to_string().red()
     ));
+    if !ignored_dependencies.is_empty() {
+        counts.push(format!(
+            "{} ignored licenses",
+            count_packages(&ignored_dependencies)
+                .to_string()
+                .bright_black()
+        ));
+    }
+    if !outside_closure_dependencies.is_empty() {
+        counts.push(format!(
+            "{} packages outside the runtime closure",
+            count_packages(&outside_closure_dependencies)
+                .to_string()
+                .bright_black()
+        ));
+    }
+    if !baselined_dependencies.is_empty() {
+        counts.push(format!(
+            "{} unsafe licenses known from the baseline",
+            count_packages(&baselined_dependencies)
+                .to_string()
+                .bright_black()
+        ));
+    }
+    if !license_changes.is_empty() {
+        counts.push(format!(
+            "{} license changes",
+            license_changes.len().to_string().yellow()
+        ));
+    }
+    if !inconsistent_licenses.is_empty() {
+        counts.push(format!(
+            "{} packages with inconsistent licenses",
+            inconsistent_licenses.len().to_string().yellow()
+        ));
+    }
+    let last = counts.pop().expect("There are always at least two counts");
+    output.push_str(&format!("\nThere were {} and {last}.", counts.join(", ")));
 
     output.push('\n');
 
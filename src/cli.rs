@@ -6,7 +6,11 @@ use clap::{Parser, ValueHint};
 use clap_complete::Shell;
 use rattler_conda_types::Platform;
 
-use crate::OutputFormat;
+use crate::{
+    baseline::DEFAULT_BASELINE_PATH,
+    query::{Field, Filter, GroupBy},
+    FailOn, OutputFormat, SbomFormat,
+};
 
 #[derive(Parser, Debug)]
 #[command(
@@ -55,6 +59,34 @@ pub enum CondaDenyCliConfig {
         #[arg(long)]
         osi: Option<bool>,
 
+        /// Check all packages against this policy instead of the configured policy mapping
+        #[arg(long)]
+        policy: Option<String>,
+
+        /// Findings that make the check fail [default: disallowed,denied,non-spdx,missing]
+        #[arg(long, value_delimiter = ',')]
+        fail_on: Option<Vec<FailOn>>,
+
+        /// Show which direct dependencies pull in each package
+        #[arg(long)]
+        pulled_in_by: bool,
+
+        /// Only check the runtime closure of the direct dependencies of this pixi feature (or environment)
+        #[arg(long, value_name = "FEATURE")]
+        runtime_closure: Option<String>,
+
+        /// Only fail on unsafe dependencies that are not in the baseline file
+        #[arg(long, num_args = 0..=1, default_missing_value = DEFAULT_BASELINE_PATH, value_hint = ValueHint::FilePath)]
+        baseline: Option<PathBuf>,
+
+        /// Record the current unsafe dependencies in a baseline file
+        #[arg(long, num_args = 0..=1, default_missing_value = DEFAULT_BASELINE_PATH, conflicts_with = "baseline", value_hint = ValueHint::FilePath)]
+        write_baseline: Option<PathBuf>,
+
+        /// Report packages whose license changed compared to the output of `list --output json`
+        #[arg(long, value_hint = ValueHint::FilePath)]
+        license_history: Option<PathBuf>,
+
         /// Ignore when encountering pypi packages instead of failing.
         #[arg(long)]
         ignore_pypi: Option<bool>,
@@ -62,6 +94,26 @@ pub enum CondaDenyCliConfig {
         /// Output format
         #[arg(short, long)]
         output: Option<OutputFormat>,
+
+        /// Tera template to render the output with, implies `--output template`
+        #[arg(long, value_hint = ValueHint::FilePath)]
+        template: Option<PathBuf>,
+
+        /// Only report packages matching this expression, e.g. `license~GPL and verdict=unsafe`
+        #[arg(long)]
+        filter: Option<Vec<Filter>>,
+
+        /// Sort the packages by these fields
+        #[arg(long, value_delimiter = ',')]
+        sort_by: Option<Vec<Field>>,
+
+        /// Merge entries with the same name, version and license across platforms [default: true for the default and markdown output]
+        #[arg(long)]
+        aggregate: Option<bool>,
+
+        /// Group the packages by the lockfiles, environments or platforms they were found in
+        #[arg(long, value_delimiter = ',')]
+        group_by: Option<Vec<GroupBy>>,
     },
     /// List all packages and their licenses in your conda or pixi environment
     List {
@@ -88,6 +140,78 @@ pub enum CondaDenyCliConfig {
         /// Output format
         #[arg(short, long)]
         output: Option<OutputFormat>,
+
+        /// Tera template to render the output with, implies `--output template`
+        #[arg(long, value_hint = ValueHint::FilePath)]
+        template: Option<PathBuf>,
+
+        /// Only list packages matching this expression, e.g. `platform=linux-64 and name=lib*`
+        #[arg(long)]
+        filter: Option<Vec<Filter>>,
+
+        /// Sort the packages by these fields
+        #[arg(long, value_delimiter = ',')]
+        sort_by: Option<Vec<Field>>,
+
+        /// Only show these fields
+        #[arg(long, value_delimiter = ',')]
+        columns: Option<Vec<Field>>,
+
+        /// Merge entries with the same name, version and license across platforms [default: true for the default and markdown output]
+        #[arg(long)]
+        aggregate: Option<bool>,
+
+        /// Group the packages by the lockfiles, environments or platforms they were found in
+        #[arg(long, value_delimiter = ',')]
+        group_by: Option<Vec<GroupBy>>,
+    },
+
+    /// Summarize which licenses are used by how many packages
+    Licenses {
+        /// Path to the pixi lockfile(s), can be glob patterns
+        #[arg(short, long, value_hint = ValueHint::AnyPath)]
+        lockfile: Option<Vec<String>>,
+
+        /// Path to the conda prefix(es)
+        #[arg(
+            long,
+            global = true,
+            conflicts_with_all = ["platform", "environment", "lockfile"],
+            value_hint = ValueHint::DirPath
+        )]
+        prefix: Option<Vec<PathBuf>>,
+
+        /// Platform(s) to summarize
+        #[arg(short, long)]
+        platform: Option<Vec<Platform>>,
+
+        /// Pixi environment(s) to summarize
+        #[arg(short, long)]
+        environment: Option<Vec<String>>,
+
+        /// Check against OSI licenses instead of custom license allowlists.
+        #[arg(long)]
+        osi: Option<bool>,
+
+        /// Check all packages against this policy instead of the configured policy mapping
+        #[arg(long)]
+        policy: Option<String>,
+
+        /// Group by the individual SPDX license ids instead of the whole license expressions
+        #[arg(long)]
+        by_id: bool,
+
+        /// Ignore when encountering pypi packages instead of failing.
+        #[arg(long)]
+        ignore_pypi: Option<bool>,
+
+        /// Output format
+        #[arg(short, long)]
+        output: Option<OutputFormat>,
+
+        /// Tera template to render the output with, implies `--output template`
+        #[arg(long, value_hint = ValueHint::FilePath)]
+        template: Option<PathBuf>,
     },
 
     /// Bundle all dependency licenses in a directory
@@ -115,6 +239,205 @@ pub enum CondaDenyCliConfig {
         /// Directory to bundle licenses into
         #[arg(short, long, value_hint = ValueHint::DirPath)]
         directory: Option<PathBuf>,
+
+        /// Also write all license texts to a single notice file, in Markdown if it ends with .md
+        #[arg(long, value_hint = ValueHint::FilePath)]
+        notice_file: Option<PathBuf>,
+    },
+
+    /// Explain the license verdict for a single package
+    Explain {
+        /// Package to explain, optionally with a version: <package>[==version]
+        package: String,
+
+        /// Path to the pixi lockfile(s), can be glob patterns
+        #[arg(short, long, value_hint = ValueHint::AnyPath)]
+        lockfile: Option<Vec<String>>,
+
+        /// Path to the conda prefix(es)
+        #[arg(
+            long,
+            global = true,
+            conflicts_with_all = ["platform", "environment", "lockfile"],
+            value_hint = ValueHint::DirPath
+        )]
+        prefix: Option<Vec<PathBuf>>,
+
+        /// Platform(s) to search
+        #[arg(short, long)]
+        platform: Option<Vec<Platform>>,
+
+        /// Pixi environment(s) to search
+        #[arg(short, long)]
+        environment: Option<Vec<String>>,
+
+        /// Check against OSI licenses instead of custom license allowlists.
+        #[arg(long)]
+        osi: Option<bool>,
+
+        /// Explain the verdict of this policy instead of the configured policy mapping
+        #[arg(long)]
+        policy: Option<String>,
+
+        /// Ignore when encountering pypi packages instead of failing.
+        #[arg(long)]
+        ignore_pypi: Option<bool>,
+
+        /// Output format
+        #[arg(short, long)]
+        output: Option<OutputFormat>,
+    },
+
+    /// Show the dependency chains that pull in a package
+    Why {
+        /// Package to look for
+        package: String,
+
+        /// Path to the pixi lockfile(s), can be glob patterns
+        #[arg(short, long, value_hint = ValueHint::AnyPath)]
+        lockfile: Option<Vec<String>>,
+
+        /// Path to the conda prefix(es)
+        #[arg(
+            long,
+            global = true,
+            conflicts_with_all = ["platform", "environment", "lockfile"],
+            value_hint = ValueHint::DirPath
+        )]
+        prefix: Option<Vec<PathBuf>>,
+
+        /// Platform(s) to search
+        #[arg(short, long)]
+        platform: Option<Vec<Platform>>,
+
+        /// Pixi environment(s) to search
+        #[arg(short, long)]
+        environment: Option<Vec<String>>,
+
+        /// Output format
+        #[arg(short, long)]
+        output: Option<OutputFormat>,
+    },
+
+    /// Compare the licenses of two lockfiles and check the packages that changed
+    Diff {
+        /// The old lockfile, a path or git:<rev>:<path>
+        old: String,
+
+        /// The new lockfile, a path or git:<rev>:<path>
+        new: String,
+
+        /// Platform(s) to compare
+        #[arg(short, long)]
+        platform: Option<Vec<Platform>>,
+
+        /// Pixi environment(s) to compare
+        #[arg(short, long)]
+        environment: Option<Vec<String>>,
+
+        /// Check against OSI licenses instead of custom license allowlists.
+        #[arg(long)]
+        osi: Option<bool>,
+
+        /// Check all changed packages against this policy instead of the configured policy mapping
+        #[arg(long)]
+        policy: Option<String>,
+
+        /// Findings that make the diff fail [default: disallowed,denied,non-spdx,missing]
+        #[arg(long, value_delimiter = ',')]
+        fail_on: Option<Vec<FailOn>>,
+
+        /// Ignore when encountering pypi packages instead of failing.
+        #[arg(long)]
+        ignore_pypi: Option<bool>,
+
+        /// Output format
+        #[arg(short, long)]
+        output: Option<OutputFormat>,
+    },
+
+    /// Write a self-contained HTML compliance report
+    Report {
+        /// Path to the pixi lockfile(s), can be glob patterns
+        #[arg(short, long, value_hint = ValueHint::AnyPath)]
+        lockfile: Option<Vec<String>>,
+
+        /// Path to the conda prefix(es)
+        #[arg(
+            long,
+            global = true,
+            conflicts_with_all = ["platform", "environment", "lockfile"],
+            value_hint = ValueHint::DirPath
+        )]
+        prefix: Option<Vec<PathBuf>>,
+
+        /// Platform(s) to report on
+        #[arg(short, long)]
+        platform: Option<Vec<Platform>>,
+
+        /// Pixi environment(s) to report on
+        #[arg(short, long)]
+        environment: Option<Vec<String>>,
+
+        /// Check against OSI licenses instead of custom license allowlists.
+        #[arg(long)]
+        osi: Option<bool>,
+
+        /// Check all packages against this policy instead of the configured policy mapping
+        #[arg(long)]
+        policy: Option<String>,
+
+        /// Ignore when encountering pypi packages instead of failing.
+        #[arg(long)]
+        ignore_pypi: Option<bool>,
+
+        /// Path of the HTML report
+        #[arg(long, value_hint = ValueHint::FilePath)]
+        html: PathBuf,
+
+        /// Directory with the license texts collected by `bundle` [default: bundle, if it exists]
+        #[arg(long, value_hint = ValueHint::DirPath)]
+        bundle_directory: Option<PathBuf>,
+    },
+
+    /// Generate a software bill of materials (SBOM) of your conda or pixi environment
+    Sbom {
+        /// Path to the pixi lockfile(s), can be glob patterns
+        #[arg(short, long, value_hint = ValueHint::AnyPath)]
+        lockfile: Option<Vec<String>>,
+
+        /// Path to the conda prefix(es)
+        #[arg(
+            long,
+            global = true,
+            conflicts_with_all = ["platform", "environment", "lockfile"],
+            value_hint = ValueHint::DirPath
+        )]
+        prefix: Option<Vec<PathBuf>>,
+
+        /// Platform(s) to include
+        #[arg(short, long)]
+        platform: Option<Vec<Platform>>,
+
+        /// Pixi environment(s) to include
+        #[arg(short, long)]
+        environment: Option<Vec<String>>,
+
+        /// Ignore when encountering pypi packages instead of failing.
+        #[arg(long)]
+        ignore_pypi: Option<bool>,
+
+        /// Format of the SBOM
+        #[arg(long, default_value = "cyclonedx-json")]
+        format: SbomFormat,
+
+        /// File to write the SBOM to [default: stdout]
+        #[arg(short, long, value_hint = ValueHint::FilePath)]
+        output: Option<PathBuf>,
+
+        /// Directory with the license texts collected by `bundle`, embedded in SPDX documents [default: bundle, if it exists]
+        #[arg(long, value_hint = ValueHint::DirPath)]
+        bundle_directory: Option<PathBuf>,
     },
 
     /// Generate shell completions
@@ -130,7 +453,13 @@ impl CondaDenyCliConfig {
         match self {
             CondaDenyCliConfig::Check { lockfile, .. } => lockfile.clone(),
             CondaDenyCliConfig::List { lockfile, .. } => lockfile.clone(),
+            CondaDenyCliConfig::Licenses { lockfile, .. } => lockfile.clone(),
+            CondaDenyCliConfig::Explain { lockfile, .. } => lockfile.clone(),
+            CondaDenyCliConfig::Why { lockfile, .. } => lockfile.clone(),
             CondaDenyCliConfig::Bundle { lockfile, .. } => lockfile.clone(),
+            CondaDenyCliConfig::Report { lockfile, .. } => lockfile.clone(),
+            CondaDenyCliConfig::Sbom { lockfile, .. } => lockfile.clone(),
+            CondaDenyCliConfig::Diff { .. } => None,
             CondaDenyCliConfig::Completion { .. } => None,
         }
     }
@@ -139,7 +468,13 @@ impl CondaDenyCliConfig {
         match self {
             CondaDenyCliConfig::Check { prefix, .. } => prefix.clone(),
             CondaDenyCliConfig::List { prefix, .. } => prefix.clone(),
+            CondaDenyCliConfig::Licenses { prefix, .. } => prefix.clone(),
+            CondaDenyCliConfig::Explain { prefix, .. } => prefix.clone(),
+            CondaDenyCliConfig::Why { prefix, .. } => prefix.clone(),
             CondaDenyCliConfig::Bundle { prefix, .. } => prefix.clone(),
+            CondaDenyCliConfig::Report { prefix, .. } => prefix.clone(),
+            CondaDenyCliConfig::Sbom { prefix, .. } => prefix.clone(),
+            CondaDenyCliConfig::Diff { .. } => None,
             CondaDenyCliConfig::Completion { .. } => None,
         }
     }
@@ -148,7 +483,13 @@ impl CondaDenyCliConfig {
         match self {
             CondaDenyCliConfig::Check { platform, .. } => platform.clone(),
             CondaDenyCliConfig::List { platform, .. } => platform.clone(),
+            CondaDenyCliConfig::Licenses { platform, .. } => platform.clone(),
+            CondaDenyCliConfig::Explain { platform, .. } => platform.clone(),
+            CondaDenyCliConfig::Why { platform, .. } => platform.clone(),
             CondaDenyCliConfig::Bundle { platform, .. } => platform.clone(),
+            CondaDenyCliConfig::Report { platform, .. } => platform.clone(),
+            CondaDenyCliConfig::Sbom { platform, .. } => platform.clone(),
+            CondaDenyCliConfig::Diff { platform, .. } => platform.clone(),
             CondaDenyCliConfig::Completion { .. } => None,
         }
     }
@@ -157,7 +498,13 @@ impl CondaDenyCliConfig {
         match self {
             CondaDenyCliConfig::Check { environment, .. } => environment.clone(),
             CondaDenyCliConfig::List { environment, .. } => environment.clone(),
+            CondaDenyCliConfig::Licenses { environment, .. } => environment.clone(),
+            CondaDenyCliConfig::Explain { environment, .. } => environment.clone(),
+            CondaDenyCliConfig::Why { environment, .. } => environment.clone(),
             CondaDenyCliConfig::Bundle { environment, .. } => environment.clone(),
+            CondaDenyCliConfig::Report { environment, .. } => environment.clone(),
+            CondaDenyCliConfig::Sbom { environment, .. } => environment.clone(),
+            CondaDenyCliConfig::Diff { environment, .. } => environment.clone(),
             CondaDenyCliConfig::Completion { .. } => None,
         }
     }
@@ -166,7 +513,13 @@ impl CondaDenyCliConfig {
         match self {
             CondaDenyCliConfig::Check { ignore_pypi, .. } => *ignore_pypi,
             CondaDenyCliConfig::List { ignore_pypi, .. } => *ignore_pypi,
+            CondaDenyCliConfig::Licenses { ignore_pypi, .. } => *ignore_pypi,
+            CondaDenyCliConfig::Explain { ignore_pypi, .. } => *ignore_pypi,
+            CondaDenyCliConfig::Why { .. } => None,
             CondaDenyCliConfig::Bundle { ignore_pypi, .. } => *ignore_pypi,
+            CondaDenyCliConfig::Report { ignore_pypi, .. } => *ignore_pypi,
+            CondaDenyCliConfig::Sbom { ignore_pypi, .. } => *ignore_pypi,
+            CondaDenyCliConfig::Diff { ignore_pypi, .. } => *ignore_pypi,
             CondaDenyCliConfig::Completion { .. } => None,
         }
     }
@@ -175,7 +528,141 @@ impl CondaDenyCliConfig {
         match self {
             CondaDenyCliConfig::Check { output, .. } => *output,
             CondaDenyCliConfig::List { output, .. } => *output,
+            CondaDenyCliConfig::Licenses { output, .. } => *output,
+            CondaDenyCliConfig::Explain { output, .. } => *output,
+            CondaDenyCliConfig::Why { output, .. } => *output,
+            CondaDenyCliConfig::Diff { output, .. } => *output,
+            CondaDenyCliConfig::Bundle { .. } => None,
+            CondaDenyCliConfig::Report { .. } => None,
+            // The output of sbom is a file path, the format is set with --format
+            CondaDenyCliConfig::Sbom { .. } => None,
+            CondaDenyCliConfig::Completion { .. } => None,
+        }
+    }
+
+    /// Name of the subcommand, as used in error messages
+    pub fn name(&self) -> &'static str {
+        match self {
+            CondaDenyCliConfig::Check { .. } => "check",
+            CondaDenyCliConfig::List { .. } => "list",
+            CondaDenyCliConfig::Licenses { .. } => "licenses",
+            CondaDenyCliConfig::Explain { .. } => "explain",
+            CondaDenyCliConfig::Why { .. } => "why",
+            CondaDenyCliConfig::Bundle { .. } => "bundle",
+            CondaDenyCliConfig::Report { .. } => "report",
+            CondaDenyCliConfig::Sbom { .. } => "sbom",
+            CondaDenyCliConfig::Diff { .. } => "diff",
+            CondaDenyCliConfig::Completion { .. } => "completion",
+        }
+    }
+
+    /// Output formats that the subcommand can write with `--output`
+    pub fn supported_output_formats(&self) -> &'static [OutputFormat] {
+        use OutputFormat::*;
+        match self {
+            CondaDenyCliConfig::Check { .. } => &[
+                Default, Json, JsonPretty, Csv, Markdown, Sarif, Junit, Template,
+            ],
+            CondaDenyCliConfig::List { .. } | CondaDenyCliConfig::Licenses { .. } => {
+                &[Default, Json, JsonPretty, Csv, Markdown, Template]
+            }
+            CondaDenyCliConfig::Diff { .. } => &[Default, Json, JsonPretty, Csv, Markdown],
+            CondaDenyCliConfig::Explain { .. } | CondaDenyCliConfig::Why { .. } => {
+                &[Default, Json, JsonPretty, Csv]
+            }
+            CondaDenyCliConfig::Bundle { .. }
+            | CondaDenyCliConfig::Report { .. }
+            | CondaDenyCliConfig::Sbom { .. }
+            | CondaDenyCliConfig::Completion { .. } => &[Default],
+        }
+    }
+
+    pub fn template(&self) -> Option<PathBuf> {
+        match self {
+            CondaDenyCliConfig::Check { template, .. } => template.clone(),
+            CondaDenyCliConfig::List { template, .. } => template.clone(),
+            CondaDenyCliConfig::Licenses { template, .. } => template.clone(),
+            CondaDenyCliConfig::Explain { .. } => None,
+            CondaDenyCliConfig::Why { .. } => None,
+            CondaDenyCliConfig::Diff { .. } => None,
+            CondaDenyCliConfig::Bundle { .. } => None,
+            CondaDenyCliConfig::Report { .. } => None,
+            CondaDenyCliConfig::Sbom { .. } => None,
+            CondaDenyCliConfig::Completion { .. } => None,
+        }
+    }
+
+    pub fn filter(&self) -> Option<Vec<Filter>> {
+        match self {
+            CondaDenyCliConfig::Check { filter, .. } => filter.clone(),
+            CondaDenyCliConfig::List { filter, .. } => filter.clone(),
+            CondaDenyCliConfig::Licenses { .. } => None,
+            CondaDenyCliConfig::Explain { .. } => None,
+            CondaDenyCliConfig::Why { .. } => None,
+            CondaDenyCliConfig::Diff { .. } => None,
+            CondaDenyCliConfig::Bundle { .. } => None,
+            CondaDenyCliConfig::Report { .. } => None,
+            CondaDenyCliConfig::Sbom { .. } => None,
+            CondaDenyCliConfig::Completion { .. } => None,
+        }
+    }
+
+    pub fn sort_by(&self) -> Option<Vec<Field>> {
+        match self {
+            CondaDenyCliConfig::Check { sort_by, .. } => sort_by.clone(),
+            CondaDenyCliConfig::List { sort_by, .. } => sort_by.clone(),
+            CondaDenyCliConfig::Licenses { .. } => None,
+            CondaDenyCliConfig::Explain { .. } => None,
+            CondaDenyCliConfig::Why { .. } => None,
+            CondaDenyCliConfig::Diff { .. } => None,
+            CondaDenyCliConfig::Bundle { .. } => None,
+            CondaDenyCliConfig::Report { .. } => None,
+            CondaDenyCliConfig::Sbom { .. } => None,
+            CondaDenyCliConfig::Completion { .. } => None,
+        }
+    }
+
+    pub fn aggregate(&self) -> Option<bool> {
+        match self {
+            CondaDenyCliConfig::Check { aggregate, .. } => *aggregate,
+            CondaDenyCliConfig::List { aggregate, .. } => *aggregate,
+            CondaDenyCliConfig::Licenses { .. } => None,
+            CondaDenyCliConfig::Explain { .. } => None,
+            CondaDenyCliConfig::Why { .. } => None,
+            CondaDenyCliConfig::Diff { .. } => None,
+            CondaDenyCliConfig::Bundle { .. } => None,
+            CondaDenyCliConfig::Report { .. } => None,
+            CondaDenyCliConfig::Sbom { .. } => None,
+            CondaDenyCliConfig::Completion { .. } => None,
+        }
+    }
+
+    pub fn group_by(&self) -> Option<Vec<GroupBy>> {
+        match self {
+            CondaDenyCliConfig::Check { group_by, .. } => group_by.clone(),
+            CondaDenyCliConfig::List { group_by, .. } => group_by.clone(),
+            CondaDenyCliConfig::Licenses { .. } => None,
+            CondaDenyCliConfig::Explain { .. } => None,
+            CondaDenyCliConfig::Why { .. } => None,
+            CondaDenyCliConfig::Diff { .. } => None,
+            CondaDenyCliConfig::Bundle { .. } => None,
+            CondaDenyCliConfig::Report { .. } => None,
+            CondaDenyCliConfig::Sbom { .. } => None,
+            CondaDenyCliConfig::Completion { .. } => None,
+        }
+    }
+
+    pub fn columns(&self) -> Option<Vec<Field>> {
+        match self {
+            CondaDenyCliConfig::Check { .. } => None,
+            CondaDenyCliConfig::List { columns, .. } => columns.clone(),
+            CondaDenyCliConfig::Licenses { .. } => None,
+            CondaDenyCliConfig::Explain { .. } => None,
+            CondaDenyCliConfig::Why { .. } => None,
+            CondaDenyCliConfig::Diff { .. } => None,
             CondaDenyCliConfig::Bundle { .. } => None,
+            CondaDenyCliConfig::Report { .. } => None,
+            CondaDenyCliConfig::Sbom { .. } => None,
             CondaDenyCliConfig::Completion { .. } => None,
         }
     }
@@ -214,6 +701,243 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_cli_with_fail_on() {
+        let cli = Cli::try_parse_from(vec!["conda-deny", "check", "--fail-on", "disallowed,warn"])
+            .unwrap();
+        match cli.command {
+            CondaDenyCliConfig::Check { fail_on, .. } => {
+                assert_eq!(fail_on, Some(vec![FailOn::Disallowed, FailOn::Warn]));
+            }
+            _ => panic!("Expected check subcommand with --fail-on"),
+        }
+    }
+
+    #[test]
+    fn test_cli_with_fail_on_aliases() {
+        let cli = Cli::try_parse_from(vec![
+            "conda-deny",
+            "check",
+            "--fail-on",
+            "unsafe,invalid,no-license",
+        ])
+        .unwrap();
+        match cli.command {
+            CondaDenyCliConfig::Check { fail_on, .. } => {
+                assert_eq!(
+                    fail_on,
+                    Some(vec![FailOn::Unsafe, FailOn::NonSpdx, FailOn::Missing])
+                );
+            }
+            _ => panic!("Expected check subcommand with --fail-on"),
+        }
+    }
+
+    #[test]
+    fn test_cli_with_runtime_closure() {
+        let cli =
+            Cli::try_parse_from(vec!["conda-deny", "check", "--runtime-closure", "prod"]).unwrap();
+        match cli.command {
+            CondaDenyCliConfig::Check {
+                runtime_closure, ..
+            } => {
+                assert_eq!(runtime_closure, Some("prod".to_string()));
+            }
+            _ => panic!("Expected check subcommand with --runtime-closure"),
+        }
+    }
+
+    #[test]
+    fn test_cli_with_baseline() {
+        let cli = Cli::try_parse_from(vec!["conda-deny", "check", "--write-baseline"]).unwrap();
+        match cli.command {
+            CondaDenyCliConfig::Check {
+                baseline,
+                write_baseline,
+                ..
+            } => {
+                assert_eq!(baseline, None);
+                assert_eq!(write_baseline, Some(PathBuf::from(DEFAULT_BASELINE_PATH)));
+            }
+            _ => panic!("Expected check subcommand with --write-baseline"),
+        }
+
+        let cli = Cli::try_parse_from(vec!["conda-deny", "check", "--baseline", "baseline.json"])
+            .unwrap();
+        match cli.command {
+            CondaDenyCliConfig::Check { baseline, .. } => {
+                assert_eq!(baseline, Some(PathBuf::from("baseline.json")));
+            }
+            _ => panic!("Expected check subcommand with --baseline"),
+        }
+    }
+
+    #[test]
+    fn test_cli_with_explain_arguments() {
+        let cli = Cli::try_parse_from(vec!["conda-deny", "explain", "openssl==3.3.0"]).unwrap();
+        match cli.command {
+            CondaDenyCliConfig::Explain { package, .. } => {
+                assert_eq!(package, "openssl==3.3.0");
+            }
+            _ => panic!("Expected explain subcommand"),
+        }
+    }
+
+    #[test]
+    fn test_cli_with_why_arguments() {
+        let cli =
+            Cli::try_parse_from(vec!["conda-deny", "why", "libgcc", "-e", "default"]).unwrap();
+        match cli.command {
+            CondaDenyCliConfig::Why {
+                package,
+                environment,
+                ..
+            } => {
+                assert_eq!(package, "libgcc");
+                assert_eq!(environment, Some(vec!["default".to_string()]));
+            }
+            _ => panic!("Expected why subcommand"),
+        }
+    }
+
+    #[test]
+    fn test_cli_with_diff_arguments() {
+        let cli = Cli::try_parse_from(vec![
+            "conda-deny",
+            "diff",
+            "git:HEAD~1:pixi.lock",
+            "pixi.lock",
+            "--output",
+            "markdown",
+        ])
+        .unwrap();
+        match cli.command {
+            CondaDenyCliConfig::Diff {
+                old, new, output, ..
+            } => {
+                assert_eq!(old, "git:HEAD~1:pixi.lock");
+                assert_eq!(new, "pixi.lock");
+                assert!(matches!(output, Some(OutputFormat::Markdown)));
+            }
+            _ => panic!("Expected diff subcommand"),
+        }
+    }
+
+    #[test]
+    fn test_cli_with_report_arguments() {
+        let cli = Cli::try_parse_from(vec![
+            "conda-deny",
+            "report",
+            "--html",
+            "report.html",
+            "--bundle-directory",
+            "licenses",
+        ])
+        .unwrap();
+        match cli.command {
+            CondaDenyCliConfig::Report {
+                html,
+                bundle_directory,
+                ..
+            } => {
+                assert_eq!(html, PathBuf::from("report.html"));
+                assert_eq!(bundle_directory, Some(PathBuf::from("licenses")));
+            }
+            _ => panic!("Expected report subcommand"),
+        }
+    }
+
+    #[test]
+    fn test_cli_with_sbom_arguments() {
+        let cli = Cli::try_parse_from(vec!["conda-deny", "sbom", "--output", "sbom.json"]).unwrap();
+        match cli.command {
+            CondaDenyCliConfig::Sbom { format, output, .. } => {
+                assert_eq!(format, SbomFormat::CyclonedxJson);
+                assert_eq!(output, Some(PathBuf::from("sbom.json")));
+            }
+            _ => panic!("Expected sbom subcommand"),
+        }
+
+        let cli = Cli::try_parse_from(vec![
+            "conda-deny",
+            "sbom",
+            "--format",
+            "spdx-tv",
+            "--bundle-directory",
+            "licenses",
+        ])
+        .unwrap();
+        match cli.command {
+            CondaDenyCliConfig::Sbom {
+                format,
+                bundle_directory,
+                ..
+            } => {
+                assert_eq!(format, SbomFormat::SpdxTv);
+                assert_eq!(bundle_directory, Some(PathBuf::from("licenses")));
+            }
+            _ => panic!("Expected sbom subcommand"),
+        }
+    }
+
+    #[test]
+    fn test_cli_with_licenses_arguments() {
+        let cli = Cli::try_parse_from(vec!["conda-deny", "licenses", "--by-id", "--osi", "true"])
+            .unwrap();
+        match cli.command {
+            CondaDenyCliConfig::Licenses { by_id, osi, .. } => {
+                assert!(by_id);
+                assert_eq!(osi, Some(true));
+            }
+            _ => panic!("Expected licenses subcommand"),
+        }
+    }
+
+    #[test]
+    fn test_cli_with_template_arguments() {
+        let cli = Cli::try_parse_from(vec![
+            "conda-deny",
+            "list",
+            "--output",
+            "template",
+            "--template",
+            "licenses.tera",
+        ])
+        .unwrap();
+        assert!(matches!(cli.command.output(), Some(OutputFormat::Template)));
+        assert_eq!(cli.command.template(), Some(PathBuf::from("licenses.tera")));
+    }
+
+    #[test]
+    fn test_cli_with_query_arguments() {
+        let cli = Cli::try_parse_from(vec![
+            "conda-deny",
+            "list",
+            "--filter",
+            "license~GPL and platform=linux-64",
+            "--sort-by",
+            "license,name",
+            "--columns",
+            "name,dependency-type",
+        ])
+        .unwrap();
+        assert_eq!(
+            cli.command.filter(),
+            Some(vec!["license~GPL and platform=linux-64".parse().unwrap()])
+        );
+        assert_eq!(
+            cli.command.sort_by(),
+            Some(vec![Field::License, Field::Name])
+        );
+        assert_eq!(
+            cli.command.columns(),
+            Some(vec![Field::Name, Field::DependencyType])
+        );
+
+        assert!(Cli::try_parse_from(vec!["conda-deny", "check", "--filter", "license~"]).is_ok());
+        assert!(Cli::try_parse_from(vec!["conda-deny", "check", "--filter", "(name=a"]).is_err());
+    }
+
     #[test]
     fn test_cli_with_completion_arguments() {
         let cli = Cli::try_parse_from(vec!["conda-deny", "completion", "--shell", "bash"]).unwrap();
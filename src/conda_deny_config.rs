@@ -3,12 +3,14 @@ use glob::glob_with;
 use log::debug;
 use rattler_conda_types::Platform;
 use serde::Deserialize;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 use std::path::PathBuf;
 use std::vec;
 use std::{fs::File, io::Read};
 
-use crate::license_allowlist::IgnorePackage;
+use crate::{
+    license_allowlist::IgnorePackage, FailOn, ReasonAction, ReasonActions, RuntimeClosureConfig,
+};
 
 #[derive(Debug, Deserialize)]
 pub struct CondaDenyTomlConfig {
@@ -49,6 +51,60 @@ pub enum LockfileSpec {
     Multiple(Vec<String>),
 }
 
+#[derive(Debug, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum PrefixSpec {
+    Single(String),
+    Multiple(Vec<String>),
+}
+
+/// A named license policy, configured in `[tool.conda-deny.policies.<name>]`.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct PolicyConfig {
+    #[serde(alias = "license-whitelist")]
+    license_allowlist: Option<LicenseAllowlist>,
+    #[serde(rename = "environment")]
+    environment_spec: Option<EnviromentSpec>,
+    #[serde(rename = "prefix")]
+    prefix_spec: Option<PrefixSpec>,
+    osi: Option<bool>,
+    pub safe_licenses: Option<Vec<String>>,
+    pub warn_licenses: Option<Vec<String>>,
+    pub deny_licenses: Option<Vec<String>>,
+    pub ignore_packages: Option<Vec<IgnorePackage>>,
+}
+
+impl PolicyConfig {
+    pub fn get_license_allowlists(&self) -> Result<Vec<String>> {
+        resolve_license_allowlists(&self.license_allowlist)
+    }
+
+    pub fn get_environment_spec(&self) -> Vec<String> {
+        match &self.environment_spec {
+            Some(EnviromentSpec::Single(name)) => vec![name.clone()],
+            Some(EnviromentSpec::Multiple(names)) => names.clone(),
+            None => vec![],
+        }
+    }
+
+    pub fn get_prefix_spec(&self) -> Result<Vec<PathBuf>> {
+        let prefixes = match &self.prefix_spec {
+            Some(PrefixSpec::Single(prefix)) => vec![prefix.clone()],
+            Some(PrefixSpec::Multiple(prefixes)) => prefixes.clone(),
+            None => vec![],
+        };
+        Ok(expand_env_vars(&prefixes)?
+            .into_iter()
+            .map(PathBuf::from)
+            .collect())
+    }
+
+    pub fn get_osi(&self) -> Option<bool> {
+        self.osi
+    }
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct CondaDeny {
@@ -62,8 +118,18 @@ pub struct CondaDeny {
     lockfile_spec: Option<LockfileSpec>,
     osi: Option<bool>,
     ignore_pypi: Option<bool>,
+    fail_on: Option<Vec<FailOn>>,
+    reason_actions: Option<ReasonActions>,
+    runtime_closure: Option<RuntimeClosureConfig>,
+    baseline: Option<PathBuf>,
+    license_history: Option<PathBuf>,
+    license_changes: Option<ReasonAction>,
+    inconsistent_licenses: Option<ReasonAction>,
     pub safe_licenses: Option<Vec<String>>,
+    pub warn_licenses: Option<Vec<String>>,
+    pub deny_licenses: Option<Vec<String>>,
     pub ignore_packages: Option<Vec<IgnorePackage>>,
+    pub policies: Option<BTreeMap<String, PolicyConfig>>,
 }
 
 impl CondaDenyTomlConfig {
@@ -82,16 +148,7 @@ impl CondaDenyTomlConfig {
     }
 
     pub fn get_license_allowlists(&self) -> Result<Vec<String>> {
-        let paths = match &self.tool.conda_deny.license_allowlist {
-            None => return Ok(vec![]),
-            Some(LicenseAllowlist::Single(p)) => vec![p.clone()],
-            Some(LicenseAllowlist::Multiple(ps)) => ps.clone(),
-        };
-
-        let resolved = expand_env_vars(&paths)
-            .with_context(|| format!("Failed to parse license allowlist: {:?}", paths))?;
-
-        Ok(resolved)
+        resolve_license_allowlists(&self.tool.conda_deny.license_allowlist)
     }
 
     pub fn get_platform_spec(&self) -> Option<Vec<Platform>> {
@@ -126,6 +183,46 @@ impl CondaDenyTomlConfig {
         self.tool.conda_deny.ignore_pypi
     }
 
+    pub fn get_fail_on(&self) -> Option<Vec<FailOn>> {
+        self.tool.conda_deny.fail_on.clone()
+    }
+
+    pub fn get_reason_actions(&self) -> ReasonActions {
+        self.tool.conda_deny.reason_actions.unwrap_or_default()
+    }
+
+    pub fn get_runtime_closure(&self) -> Option<RuntimeClosureConfig> {
+        self.tool.conda_deny.runtime_closure.clone()
+    }
+
+    pub fn get_baseline(&self) -> Option<PathBuf> {
+        self.tool.conda_deny.baseline.clone()
+    }
+
+    pub fn get_license_history(&self) -> Option<PathBuf> {
+        self.tool.conda_deny.license_history.clone()
+    }
+
+    /// License changes are reported as warnings unless configured otherwise
+    pub fn get_license_changes(&self) -> ReasonAction {
+        self.tool
+            .conda_deny
+            .license_changes
+            .unwrap_or(ReasonAction::Warn)
+    }
+
+    /// Licenses that differ between platforms or builds are reported as warnings unless configured otherwise
+    pub fn get_inconsistent_licenses(&self) -> ReasonAction {
+        self.tool
+            .conda_deny
+            .inconsistent_licenses
+            .unwrap_or(ReasonAction::Warn)
+    }
+
+    pub fn get_policies(&self) -> BTreeMap<String, PolicyConfig> {
+        self.tool.conda_deny.policies.clone().unwrap_or_default()
+    }
+
     pub fn empty() -> Self {
         CondaDenyTomlConfig {
             tool: Tool {
@@ -136,14 +233,37 @@ impl CondaDenyTomlConfig {
                     lockfile_spec: None,
                     osi: None,
                     ignore_pypi: None,
+                    fail_on: None,
+                    reason_actions: None,
+                    runtime_closure: None,
+                    baseline: None,
+                    license_history: None,
+                    license_changes: None,
+                    inconsistent_licenses: None,
                     safe_licenses: None,
+                    warn_licenses: None,
+                    deny_licenses: None,
                     ignore_packages: None,
+                    policies: None,
                 },
             },
         }
     }
 }
 
+fn resolve_license_allowlists(license_allowlist: &Option<LicenseAllowlist>) -> Result<Vec<String>> {
+    let paths = match license_allowlist {
+        None => return Ok(vec![]),
+        Some(LicenseAllowlist::Single(p)) => vec![p.clone()],
+        Some(LicenseAllowlist::Multiple(ps)) => ps.clone(),
+    };
+
+    let resolved = expand_env_vars(&paths)
+        .with_context(|| format!("Failed to parse license allowlist: {:?}", paths))?;
+
+    Ok(resolved)
+}
+
 pub fn parse_paths_in_config(paths: &[String]) -> Result<Vec<PathBuf>> {
     let resolved_paths_envs = expand_env_vars(paths)?;
     let paths = resolve_glob_patterns(&resolved_paths_envs)?;
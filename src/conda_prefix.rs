@@ -0,0 +1,129 @@
+use std::{collections::BTreeSet, path::Path};
+
+use anyhow::{Context, Result};
+use rattler_conda_types::prefix_record::PrefixRecord;
+
+use crate::dependency_graph::{dependency_name, DependencyGraph};
+
+/// Returns the names of the packages that were requested by the user, according to
+/// `conda-meta/history` and the requested specs of the prefix records.
+///
+/// Returns `None` if the prefix does not record any requested packages.
+pub fn requested_packages(
+    conda_prefix: &Path,
+    records: &[PrefixRecord],
+) -> Result<Option<BTreeSet<String>>> {
+    let history_path = conda_prefix.join("conda-meta").join("history");
+    let mut requested = if history_path.is_file() {
+        let history = std::fs::read_to_string(&history_path)
+            .with_context(|| format!("Failed to read conda history: {history_path:?}"))?;
+        requested_packages_from_history(&history)
+    } else {
+        BTreeSet::new()
+    };
+
+    for record in records {
+        #[allow(deprecated)]
+        let requested_specs = record
+            .requested_specs
+            .iter()
+            .chain(record.requested_spec.as_ref());
+        for spec in requested_specs {
+            let name = package_name_of_spec(spec);
+            if !name.is_empty() && name != "None" {
+                requested.insert(name.to_string());
+            }
+        }
+    }
+
+    Ok(if requested.is_empty() {
+        None
+    } else {
+        Some(requested)
+    })
+}
+
+/// Builds the dependency graph of a conda prefix, with the requested packages as direct dependencies.
+pub fn dependency_graph(conda_prefix: &Path, records: &[PrefixRecord]) -> Result<DependencyGraph> {
+    let direct_dependencies = requested_packages(conda_prefix, records)?;
+    Ok(DependencyGraph::new(
+        records.iter().map(|record| {
+            let package_record = &record.repodata_record.package_record;
+            (
+                package_record.name.as_source().to_string(),
+                package_record.depends.as_slice(),
+            )
+        }),
+        direct_dependencies,
+    ))
+}
+
+/// Replays the `update specs` and `remove specs` entries of a `conda-meta/history` file.
+fn requested_packages_from_history(history: &str) -> BTreeSet<String> {
+    let mut requested = BTreeSet::new();
+    for line in history.lines() {
+        if let Some(specs) = line.strip_prefix("# update specs:") {
+            requested.extend(parse_specs(specs).map(str::to_string));
+        } else if let Some(specs) = line.strip_prefix("# remove specs:") {
+            for name in parse_specs(specs) {
+                requested.remove(name);
+            }
+        }
+    }
+    requested
+}
+
+/// Parses a spec list like `["numpy", 'pandas >=2']` into package names.
+fn parse_specs(specs: &str) -> impl Iterator<Item = &str> {
+    specs
+        .trim()
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .split(',')
+        .map(|spec| package_name_of_spec(spec.trim().trim_matches(|c| c == '"' || c == '\'')))
+        .filter(|name| !name.is_empty())
+}
+
+/// Returns the package name of a spec that may be prefixed with a channel, like `conda-forge::numpy`.
+fn package_name_of_spec(spec: &str) -> &str {
+    let spec = spec.trim();
+    let spec = spec.rsplit_once("::").map_or(spec, |(_, spec)| spec);
+    dependency_name(spec)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_requested_packages_from_history() {
+        let history = "\
+==> 2024-10-17 18:06:23 <==
+# cmd: micromamba install ipython
+# update specs: [\"ipython\"]
++https://conda.anaconda.org/conda-forge/noarch::ipython-8.28.0-pyh707e725_0
+==> 2024-10-17 18:07:00 <==
+# cmd: conda install numpy pandas
+# update specs: ['conda-forge::numpy >=2', 'pandas']
+==> 2024-10-17 18:08:00 <==
+# cmd: conda remove pandas
+# remove specs: ['pandas']
+";
+        assert_eq!(
+            requested_packages_from_history(history),
+            BTreeSet::from(["ipython".to_string(), "numpy".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_requested_packages_of_prefix() {
+        let prefix = Path::new("tests/test_conda_prefixes/test-env");
+        let records = PrefixRecord::collect_from_prefix::<PrefixRecord>(prefix).unwrap();
+        assert_eq!(
+            requested_packages(prefix, &records).unwrap(),
+            Some(BTreeSet::from(
+                ["ipython", "multipledispatch", "numpy", "pandas"].map(str::to_string)
+            ))
+        );
+    }
+}
@@ -0,0 +1,91 @@
+use serde_json::{json, Value};
+
+use crate::{license_info::LicenseState, sbom::SbomPackage};
+
+const CYCLONEDX_SCHEMA: &str = "http://cyclonedx.org/schema/bom-1.5.schema.json";
+
+fn licenses(license: &LicenseState) -> Option<Value> {
+    match license {
+        LicenseState::Valid(expression) => Some(json!([{ "expression": expression.to_string() }])),
+        // Licenses that are not valid SPDX expressions can only be given by name
+        LicenseState::Invalid(license) => Some(json!([{ "license": { "name": license } }])),
+        LicenseState::NoLicense => None,
+    }
+}
+
+fn component(package: &SbomPackage) -> Value {
+    let mut component = json!({
+        "type": "library",
+        "bom-ref": package.reference(),
+        "name": package.name,
+    });
+    if let Some(version) = &package.version {
+        component["version"] = json!(version);
+    }
+    if let Some(purl) = package.purl() {
+        component["purl"] = json!(purl);
+    }
+    if let Some(licenses) = licenses(&package.license) {
+        component["licenses"] = licenses;
+    }
+
+    let hashes: Vec<Value> = [("SHA-256", &package.sha256), ("MD5", &package.md5)]
+        .into_iter()
+        .filter_map(|(alg, content)| Some(json!({ "alg": alg, "content": content.as_ref()? })))
+        .collect();
+    if !hashes.is_empty() {
+        component["hashes"] = json!(hashes);
+    }
+    if let Some(url) = &package.url {
+        component["externalReferences"] = json!([{ "type": "distribution", "url": url }]);
+    }
+
+    let mut properties = Vec::new();
+    for (name, value) in [
+        ("conda:build", &package.build),
+        ("conda:subdir", &package.subdir),
+        ("conda:source", &package.source_identifier),
+    ] {
+        if let Some(value) = value {
+            properties.push(json!({ "name": name, "value": value }));
+        }
+    }
+    for environment in &package.environments {
+        properties.push(json!({ "name": "conda:environment", "value": environment }));
+    }
+    component["properties"] = json!(properties);
+    component
+}
+
+/// Builds a CycloneDX 1.5 BOM with a component per package and the dependencies between them.
+///
+/// The BOM has no serial number or timestamp, so that it only changes when the packages change.
+pub(crate) fn cyclonedx_bom(packages: &[SbomPackage]) -> Value {
+    let dependencies: Vec<Value> = packages
+        .iter()
+        .map(|package| {
+            json!({
+                "ref": package.reference(),
+                "dependsOn": package.dependencies,
+            })
+        })
+        .collect();
+
+    json!({
+        "$schema": CYCLONEDX_SCHEMA,
+        "bomFormat": "CycloneDX",
+        "specVersion": "1.5",
+        "version": 1,
+        "metadata": {
+            "tools": {
+                "components": [{
+                    "type": "application",
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                }],
+            },
+        },
+        "components": packages.iter().map(component).collect::<Vec<_>>(),
+        "dependencies": dependencies,
+    })
+}
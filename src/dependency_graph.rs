@@ -0,0 +1,238 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use rattler_conda_types::Platform;
+
+/// The dependencies between the packages of one environment on one platform
+#[derive(Debug, Clone, Default)]
+pub struct DependencyGraph {
+    /// Package name to the names of the packages it depends on
+    dependencies: BTreeMap<String, BTreeSet<String>>,
+    /// The packages that were requested directly, e.g. in the pixi manifest
+    direct_dependencies: BTreeSet<String>,
+}
+
+/// The dependency graph of one pixi environment (or conda prefix) on one platform
+#[derive(Debug, Clone)]
+pub struct EnvironmentGraph {
+    /// Not set for conda prefixes
+    pub lockfile: Option<String>,
+    pub environment: String,
+    /// Not known for conda prefixes
+    pub platform: Option<Platform>,
+    pub graph: DependencyGraph,
+}
+
+/// The packages needed at runtime by the direct dependencies of a pixi feature,
+/// in one environment on one platform
+#[derive(Debug, Clone)]
+pub struct RuntimeClosure {
+    pub lockfile: Option<String>,
+    pub environment: String,
+    pub platform: Option<Platform>,
+    pub packages: BTreeSet<String>,
+}
+
+/// Returns the package name of a dependency spec like `python >=3.8,<3.13.0a0`.
+pub fn dependency_name(spec: &str) -> &str {
+    spec.split(|c: char| c.is_whitespace() || "=<>!~[".contains(c))
+        .next()
+        .unwrap_or(spec)
+}
+
+impl DependencyGraph {
+    /// Builds the graph from package names and their dependency specs.
+    ///
+    /// Without `direct_dependencies`, the packages that no other package depends on are
+    /// considered direct dependencies.
+    pub fn new<'a>(
+        packages: impl IntoIterator<Item = (String, &'a [String])>,
+        direct_dependencies: Option<BTreeSet<String>>,
+    ) -> Self {
+        let dependencies: BTreeMap<String, BTreeSet<String>> = packages
+            .into_iter()
+            .map(|(name, depends)| {
+                let depends = depends
+                    .iter()
+                    .map(|spec| dependency_name(spec).to_string())
+                    .collect();
+                (name, depends)
+            })
+            .collect();
+
+        let direct_dependencies = match direct_dependencies {
+            Some(direct_dependencies) => direct_dependencies
+                .into_iter()
+                .filter(|name| dependencies.contains_key(name))
+                .collect(),
+            None => {
+                let depended_on: BTreeSet<&String> = dependencies.values().flatten().collect();
+                dependencies
+                    .keys()
+                    .filter(|name| !depended_on.contains(name))
+                    .cloned()
+                    .collect()
+            }
+        };
+
+        DependencyGraph {
+            dependencies,
+            direct_dependencies,
+        }
+    }
+
+    pub fn contains(&self, package_name: &str) -> bool {
+        self.dependencies.contains_key(package_name)
+    }
+
+    pub fn direct_dependencies(&self) -> &BTreeSet<String> {
+        &self.direct_dependencies
+    }
+
+    /// Returns a shortest dependency chain from `from` to `to`, including both ends.
+    pub fn shortest_path(&self, from: &str, to: &str) -> Option<Vec<String>> {
+        let mut predecessors: BTreeMap<&str, &str> = BTreeMap::new();
+        let mut queue = VecDeque::from([from]);
+        let mut visited = BTreeSet::from([from]);
+
+        while let Some(package) = queue.pop_front() {
+            if package == to {
+                let mut path = vec![to.to_string()];
+                let mut current = to;
+                while let Some(predecessor) = predecessors.get(current) {
+                    path.push(predecessor.to_string());
+                    current = predecessor;
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for dependency in self.dependencies.get(package).into_iter().flatten() {
+                if self.contains(dependency) && visited.insert(dependency) {
+                    predecessors.insert(dependency, package);
+                    queue.push_back(dependency);
+                }
+            }
+        }
+        None
+    }
+
+    /// Returns a shortest chain from every direct dependency that pulls in the package,
+    /// shortest chains first.
+    pub fn paths_from_direct_dependencies(&self, package_name: &str) -> Vec<Vec<String>> {
+        let mut paths: Vec<Vec<String>> = self
+            .direct_dependencies
+            .iter()
+            .filter_map(|direct| self.shortest_path(direct, package_name))
+            .collect();
+        paths.sort_by(|a, b| a.len().cmp(&b.len()).then_with(|| a.cmp(b)));
+        paths
+    }
+
+    /// Returns the given packages together with everything they depend on, transitively.
+    pub fn closure(&self, roots: &BTreeSet<String>) -> BTreeSet<String> {
+        let mut closure: BTreeSet<String> = roots
+            .iter()
+            .filter(|root| self.contains(root))
+            .cloned()
+            .collect();
+        let mut queue: VecDeque<String> = closure.iter().cloned().collect();
+
+        while let Some(package) = queue.pop_front() {
+            for dependency in self.dependencies.get(&package).into_iter().flatten() {
+                if self.contains(dependency) && closure.insert(dependency.clone()) {
+                    queue.push_back(dependency.clone());
+                }
+            }
+        }
+        closure
+    }
+
+    /// Returns the direct dependencies that pull in the package.
+    pub fn pulled_in_by(&self, package_name: &str) -> BTreeSet<String> {
+        self.paths_from_direct_dependencies(package_name)
+            .into_iter()
+            .filter_map(|path| path.into_iter().next())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph(direct_dependencies: Option<BTreeSet<String>>) -> DependencyGraph {
+        let packages = [
+            ("app", vec!["lib-a >=1.0", "lib-b"]),
+            ("lib-a", vec!["libc"]),
+            ("lib-b", vec!["lib-a", "libc 2.*"]),
+            ("tool", vec!["lib-b"]),
+            ("libc", vec!["__glibc >=2.17"]),
+        ]
+        .map(|(name, depends)| {
+            (
+                name.to_string(),
+                depends.into_iter().map(str::to_string).collect::<Vec<_>>(),
+            )
+        });
+        DependencyGraph::new(
+            packages
+                .iter()
+                .map(|(name, depends)| (name.clone(), depends.as_slice())),
+            direct_dependencies,
+        )
+    }
+
+    #[test]
+    fn test_dependency_name() {
+        assert_eq!(dependency_name("python >=3.8,<3.13.0a0"), "python");
+        assert_eq!(dependency_name("libzlib>=1.2.13"), "libzlib");
+        assert_eq!(dependency_name("openssl"), "openssl");
+    }
+
+    #[test]
+    fn test_shortest_paths() {
+        let graph = graph(None);
+        assert_eq!(
+            graph.direct_dependencies(),
+            &BTreeSet::from(["app".to_string(), "tool".to_string()])
+        );
+        assert_eq!(
+            graph.paths_from_direct_dependencies("libc"),
+            vec![vec!["app", "lib-a", "libc"], vec!["tool", "lib-b", "libc"]]
+        );
+        assert_eq!(
+            graph.pulled_in_by("lib-a"),
+            BTreeSet::from(["app".to_string(), "tool".to_string()])
+        );
+        assert_eq!(graph.shortest_path("lib-a", "tool"), None);
+    }
+
+    #[test]
+    fn test_closure() {
+        let graph = graph(None);
+        assert_eq!(
+            graph.closure(&BTreeSet::from(["lib-b".to_string()])),
+            BTreeSet::from(["lib-a", "lib-b", "libc"].map(str::to_string))
+        );
+        assert_eq!(
+            graph.closure(&BTreeSet::from(["not-installed".to_string()])),
+            BTreeSet::new()
+        );
+    }
+
+    #[test]
+    fn test_explicit_direct_dependencies() {
+        let graph = graph(Some(BTreeSet::from([
+            "lib-b".to_string(),
+            "not-installed".to_string(),
+        ])));
+        assert_eq!(
+            graph.direct_dependencies(),
+            &BTreeSet::from(["lib-b".to_string()])
+        );
+        assert_eq!(
+            graph.paths_from_direct_dependencies("lib-b"),
+            vec![vec!["lib-b"]]
+        );
+        assert!(graph.paths_from_direct_dependencies("app").is_empty());
+    }
+}
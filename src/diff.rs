@@ -0,0 +1,600 @@
+use std::{
+    cmp::Ordering,
+    collections::BTreeMap,
+    env, fmt,
+    io::Write,
+    path::{Component, Path, PathBuf},
+    process::Command,
+    str::FromStr,
+};
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+use rattler_conda_types::Version;
+use rattler_lock::LockFile;
+use serde::Serialize;
+
+use crate::{
+    check::{check_exit_status, format_check_output},
+    json_output::{checked_packages, write_json_document},
+    license_changes::{inconsistent_licenses, LicenseChange},
+    license_info::{CheckResult, IgnoredPackages, LicenseInfo, LicenseInfos},
+    markdown::format_check_output_markdown,
+    CheckOutput, CondaDenyDiffConfig, LockfileOrPrefix, OutputFormat, ReasonAction, UnsafeReason,
+};
+
+/// Where to read one side of a diff from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LockfileSource {
+    Path(PathBuf),
+    /// A lockfile at a git revision, read from the object database of the current repository
+    Git {
+        revision: String,
+        path: PathBuf,
+    },
+}
+
+impl FromStr for LockfileSource {
+    type Err = anyhow::Error;
+
+    /// Parses a path or `git:<rev>:<path>`.
+    fn from_str(source: &str) -> Result<Self> {
+        let Some(git_source) = source.strip_prefix("git:") else {
+            return Ok(LockfileSource::Path(PathBuf::from(source)));
+        };
+        match git_source.split_once(':') {
+            Some((revision, path)) if !revision.is_empty() && !path.is_empty() => {
+                Ok(LockfileSource::Git {
+                    revision: revision.to_string(),
+                    path: PathBuf::from(path),
+                })
+            }
+            _ => Err(anyhow::anyhow!(
+                "Invalid lockfile {source}, expected git:<rev>:<path>"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for LockfileSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LockfileSource::Path(path) => write!(f, "{}", path.display()),
+            LockfileSource::Git { revision, path } => {
+                write!(f, "git:{revision}:{}", path.display())
+            }
+        }
+    }
+}
+
+impl LockfileSource {
+    fn read(&self) -> Result<LockFile> {
+        match self {
+            LockfileSource::Path(path) => LockFile::from_path(path)
+                .with_context(|| format!("Failed to read pixi.lock file: {path:?}")),
+            LockfileSource::Git { revision, path } => {
+                let (toplevel, path_in_repository) = git_path(path)?;
+                let object = format!("{revision}:{path_in_repository}");
+                let output = Command::new("git")
+                    .arg("-C")
+                    .arg(&toplevel)
+                    .args(["show", &object])
+                    .output()
+                    .with_context(|| "Failed to run git")?;
+                anyhow::ensure!(
+                    output.status.success(),
+                    "Failed to read {self} from git: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                );
+                let content = String::from_utf8(output.stdout)
+                    .with_context(|| format!("{self} is not valid UTF-8"))?;
+                LockFile::from_str_with_base_directory(&content, path.parent())
+                    .with_context(|| format!("Failed to parse pixi.lock file: {self}"))
+            }
+        }
+    }
+}
+
+/// Returns the root of the git repository containing the path and the path relative to it.
+///
+/// Relative paths are relative to the current directory, wherever it is in the repository.
+fn git_path(path: &Path) -> Result<(PathBuf, String)> {
+    let current_dir = env::current_dir()?;
+    let path = normalize_path(&current_dir.join(path));
+    // The lockfile may not exist in the working tree, but its directory usually does
+    let directory = path
+        .ancestors()
+        .skip(1)
+        .find(|directory| directory.is_dir())
+        .unwrap_or(&current_dir);
+
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(directory)
+        .args(["rev-parse", "--show-toplevel"])
+        .output()
+        .with_context(|| "Failed to run git")?;
+    anyhow::ensure!(
+        output.status.success(),
+        "{} is not in a git repository: {}",
+        path.display(),
+        String::from_utf8_lossy(&output.stderr).trim()
+    );
+    let toplevel = PathBuf::from(String::from_utf8(output.stdout)?.trim());
+
+    // git reports the toplevel with symlinks resolved
+    let canonical_directory = directory.canonicalize()?;
+    let relative_directory = canonical_directory
+        .strip_prefix(&toplevel)
+        .with_context(|| format!("{} is outside of {}", path.display(), toplevel.display()))?;
+    let relative_path = relative_directory.join(path.strip_prefix(directory)?);
+    let path_in_repository = relative_path
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+    Ok((toplevel, path_in_repository))
+}
+
+/// Resolves `.` and `..` without touching the file system.
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// How a package changed between the two lockfiles
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Upgraded,
+    Downgraded,
+    /// Same version, but a different build or license
+    Rebuilt,
+}
+
+impl fmt::Display for ChangeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            ChangeKind::Added => "added",
+            ChangeKind::Removed => "removed",
+            ChangeKind::Upgraded => "upgraded",
+            ChangeKind::Downgraded => "downgraded",
+            ChangeKind::Rebuilt => "rebuilt",
+        };
+        write!(f, "{kind}")
+    }
+}
+
+/// A package that was added, removed or changed, with the verdict for its new version
+#[derive(Debug, Clone, Serialize)]
+struct PackageChange {
+    change: ChangeKind,
+    package_name: String,
+    platform: Option<String>,
+    old_version: Option<String>,
+    new_version: Option<String>,
+    old_license: Option<String>,
+    new_license: Option<String>,
+    license_changed: bool,
+    /// Not set for removed packages, which are not checked
+    result: Option<CheckResult>,
+    reason: Option<UnsafeReason>,
+    policy: Option<String>,
+}
+
+pub fn diff<W: Write>(config: CondaDenyDiffConfig, mut out: W) -> Result<()> {
+    let check_config = &config.check_config;
+    let LockfileOrPrefix::Lockfile(lockfile_spec) = &check_config.lockfile_or_prefix else {
+        return Err(anyhow::anyhow!("diff only supports pixi lockfiles"));
+    };
+
+    let read_license_infos = |source: &LockfileSource| -> Result<LicenseInfos> {
+        let lock_file = source.read()?;
+        LicenseInfos::from_lock_file(
+            &lock_file,
+            &source.to_string(),
+            lockfile_spec,
+            &check_config.ignore_packages,
+            IgnoredPackages::Keep,
+        )
+        .with_context(|| format!("Getting license information from {source} failed."))
+    };
+    let old = read_license_infos(&config.old)?;
+    let new = read_license_infos(&config.new)?;
+
+    let changed = changed_packages(&old, &new);
+
+    // Only the new side of the delta is checked
+    let delta = LicenseInfos {
+        license_infos: changed
+            .iter()
+            .filter_map(|(_, _, new)| new.cloned())
+            .collect(),
+    };
+    let mut check_output = delta.check(check_config)?;
+    if check_config.license_changes != ReasonAction::Ignore {
+        check_output.license_changes = changed
+            .iter()
+            .filter_map(|(_, old, new)| LicenseChange::between((*old)?, (*new)?))
+            .collect();
+    }
+    if check_config.inconsistent_licenses != ReasonAction::Ignore {
+        check_output.inconsistent_licenses =
+            inconsistent_licenses(&delta.license_infos.iter().collect::<Vec<_>>());
+    }
+
+    let changes: Vec<PackageChange> = changed
+        .iter()
+        .map(|(change, old, new)| package_change(*change, *old, *new, &check_output))
+        .collect();
+
+    match check_config.output_format {
+        OutputFormat::Default => {
+            writeln!(
+                out,
+                "{}",
+                format_changes(&config, &changes, check_output.clone())
+            )?;
+        }
+        OutputFormat::Json | OutputFormat::JsonPretty => {
+            write_json_document(
+                &mut out,
+                "diff",
+                &check_config.lockfile_or_prefix,
+                json_output(&config, &changes, &check_output)?,
+                check_config.output_format,
+            )?;
+        }
+        OutputFormat::Csv => {
+            let mut writer = csv::WriterBuilder::new().from_writer(vec![]);
+            for change in &changes {
+                writer.serialize(change).with_context(|| {
+                    format!("Failed to serialize the following change to CSV: {change:?}")
+                })?;
+            }
+            out.write_all(&writer.into_inner()?)?;
+        }
+        OutputFormat::Markdown => {
+            write!(
+                out,
+                "{}\n{}",
+                format_changes_markdown(&config, &changes),
+                format_check_output_markdown(&check_output)
+            )?;
+        }
+        OutputFormat::Sarif | OutputFormat::Junit | OutputFormat::Template => {
+            unreachable!("Unsupported output formats are rejected by get_config_options")
+        }
+    }
+    out.flush()?;
+
+    check_exit_status(&check_output, check_config)
+}
+
+type Change<'a> = (ChangeKind, Option<&'a LicenseInfo>, Option<&'a LicenseInfo>);
+
+/// Pairs the packages of both sides by name and platform and returns the ones that differ.
+fn changed_packages<'a>(old: &'a LicenseInfos, new: &'a LicenseInfos) -> Vec<Change<'a>> {
+    type Key<'a> = (&'a str, Option<&'a str>);
+    let group = |license_infos: &'a LicenseInfos| {
+        let mut groups: BTreeMap<Key<'a>, Vec<&'a LicenseInfo>> = BTreeMap::new();
+        for license_info in &license_infos.license_infos {
+            groups
+                .entry((
+                    license_info.package_name.as_str(),
+                    license_info.platform.as_deref(),
+                ))
+                .or_default()
+                .push(license_info);
+        }
+        groups
+    };
+    let old_groups = group(old);
+    let mut new_groups = group(new);
+
+    let mut changes = Vec::new();
+    for (key, old_infos) in old_groups {
+        let Some(new_infos) = new_groups.remove(&key) else {
+            changes.extend(
+                old_infos
+                    .into_iter()
+                    .map(|old| (ChangeKind::Removed, Some(old), None)),
+            );
+            continue;
+        };
+
+        // If a package occurs in several versions, e.g. in different environments, the latest ones are compared
+        let latest = |infos: Vec<&'a LicenseInfo>| {
+            infos
+                .into_iter()
+                .max_by(|a, b| compare_versions(&a.version, &b.version))
+                .expect("Groups are never empty")
+        };
+        let old_info = latest(old_infos);
+        let new_info = latest(new_infos);
+        if old_info == new_info && old_info.license == new_info.license {
+            continue;
+        }
+        let change = match compare_versions(&old_info.version, &new_info.version) {
+            Ordering::Less => ChangeKind::Upgraded,
+            Ordering::Greater => ChangeKind::Downgraded,
+            Ordering::Equal => ChangeKind::Rebuilt,
+        };
+        changes.push((change, Some(old_info), Some(new_info)));
+    }
+    changes.extend(
+        new_groups
+            .into_values()
+            .flatten()
+            .map(|new| (ChangeKind::Added, None, Some(new))),
+    );
+    changes.sort_by(|a, b| {
+        let key = |(_, old, new): &Change<'a>| {
+            let info = new.or(*old).expect("Changes have at least one side");
+            (info.package_name.clone(), info.platform.clone())
+        };
+        key(a).cmp(&key(b))
+    });
+    changes
+}
+
+fn compare_versions(a: &Option<String>, b: &Option<String>) -> Ordering {
+    let parse = |version: &Option<String>| {
+        version
+            .as_deref()
+            .and_then(|version| Version::from_str(version).ok())
+    };
+    match (parse(a), parse(b)) {
+        (Some(a), Some(b)) => a.cmp(&b),
+        _ => a.cmp(b),
+    }
+}
+
+fn package_change(
+    change: ChangeKind,
+    old: Option<&LicenseInfo>,
+    new: Option<&LicenseInfo>,
+    check_output: &CheckOutput,
+) -> PackageChange {
+    let old_license = old.and_then(|old| old.license.license_string());
+    let new_license = new.and_then(|new| new.license.license_string());
+
+    // A package checked against several policies gets the most severe result
+    let checked = new.map(|new| {
+        [
+            (CheckResult::Unsafe, &check_output.unsafe_dependencies),
+            (CheckResult::Warn, &check_output.warn_dependencies),
+            (CheckResult::Ignored, &check_output.ignored_dependencies),
+            (CheckResult::Safe, &check_output.safe_dependencies),
+        ]
+        .into_iter()
+        .find_map(|(result, license_infos)| {
+            license_infos
+                .iter()
+                .find(|license_info| *license_info == new)
+                .map(|license_info| (result, license_info))
+        })
+    });
+
+    let (result, reason, policy) = match checked {
+        Some(Some((result, license_info))) => (
+            Some(result),
+            license_info.reason,
+            license_info.policy.clone(),
+        ),
+        // Ignored by the ignore-packages of its policy
+        Some(None) => (Some(CheckResult::Ignored), None, None),
+        None => (None, None, None),
+    };
+
+    let info = new.or(old).expect("Changes have at least one side");
+    PackageChange {
+        change,
+        package_name: info.package_name.clone(),
+        platform: info.platform.clone(),
+        old_version: old.and_then(|old| old.version.clone()),
+        new_version: new.and_then(|new| new.version.clone()),
+        license_changed: matches!((old, new), (Some(old), Some(new)) if LicenseChange::between(old, new).is_some()),
+        old_license,
+        new_license,
+        result,
+        reason,
+        policy,
+    }
+}
+
+fn json_output(
+    config: &CondaDenyDiffConfig,
+    changes: &[PackageChange],
+    check_output: &CheckOutput,
+) -> Result<serde_json::Value> {
+    Ok(serde_json::json!({
+        "old": config.old.to_string(),
+        "new": config.new.to_string(),
+        "changes": changes,
+        "packages": checked_packages(check_output)?,
+        "license_changes": check_output.license_changes,
+        "inconsistent_licenses": check_output.inconsistent_licenses,
+    }))
+}
+
+fn format_version_change(change: &PackageChange) -> String {
+    match (&change.old_version, &change.new_version) {
+        (Some(old), Some(new)) if old != new => format!("{old} → {new}"),
+        (_, Some(version)) | (Some(version), None) => version.clone(),
+        (None, None) => "unknown-source".to_string(),
+    }
+}
+
+fn format_license_change(change: &PackageChange) -> String {
+    let old = change.old_license.as_deref().unwrap_or("no license");
+    let new = change.new_license.as_deref().unwrap_or("no license");
+    match change.change {
+        ChangeKind::Removed => old.to_string(),
+        _ if change.license_changed => format!("{old} → {new}"),
+        _ => new.to_string(),
+    }
+}
+
+fn format_changes(
+    config: &CondaDenyDiffConfig,
+    changes: &[PackageChange],
+    check_output: CheckOutput,
+) -> String {
+    if changes.is_empty() {
+        return format!(
+            "No package changes between {} and {}.",
+            config.old, config.new
+        );
+    }
+
+    let mut output = format!(
+        "📦 {}:\n\n",
+        format!("Package changes between {} and {}", config.old, config.new).bold()
+    );
+    for change in changes {
+        let marker = match change.change {
+            ChangeKind::Added => "+".green(),
+            ChangeKind::Removed => "-".red(),
+            ChangeKind::Upgraded => "↑".cyan(),
+            ChangeKind::Downgraded => "↓".yellow(),
+            ChangeKind::Rebuilt => "~".bright_black(),
+        };
+        let license = format_license_change(change);
+        output.push_str(&format!(
+            "{marker} {} {} ({}): {}{}\n",
+            change.package_name.blue(),
+            format_version_change(change).cyan(),
+            change
+                .platform
+                .as_deref()
+                .unwrap_or("unknown-source")
+                .bright_purple(),
+            license.yellow(),
+            if change.license_changed {
+                " (license changed)".bright_black().to_string()
+            } else {
+                String::new()
+            },
+        ));
+    }
+
+    output.push_str(&format_check_output(check_output, false, &[]));
+    output
+}
+
+fn format_changes_markdown(config: &CondaDenyDiffConfig, changes: &[PackageChange]) -> String {
+    let mut output = String::from("## conda-deny diff\n\n");
+    if changes.is_empty() {
+        output.push_str(&format!(
+            "No package changes between `{}` and `{}`.\n",
+            config.old, config.new
+        ));
+        return output;
+    }
+
+    let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+    for change in changes {
+        *counts.entry(change.change.to_string()).or_default() += 1;
+    }
+    let counts: Vec<String> = counts
+        .into_iter()
+        .map(|(kind, count)| format!("{count} {kind}"))
+        .collect();
+    output.push_str(&format!(
+        "Changes between `{}` and `{}`: {}.\n\n",
+        config.old,
+        config.new,
+        counts.join(", ")
+    ));
+
+    output.push_str("| Change | Package | Platform | Version | License | Verdict |\n");
+    output.push_str("| --- | --- | --- | --- | --- | --- |\n");
+    for change in changes {
+        let verdict = match (change.result, change.reason) {
+            (None, _) => String::new(),
+            (Some(result), reason) => {
+                let icon = match result {
+                    CheckResult::Safe => "✅",
+                    CheckResult::Warn => "⚠️",
+                    CheckResult::Unsafe => "❌",
+                    CheckResult::Ignored => "⏭️",
+                };
+                match reason {
+                    Some(reason) => format!("{icon} {result} ({reason})"),
+                    None => format!("{icon} {result}"),
+                }
+            }
+        };
+        output.push_str(&format!(
+            "| {} | {} | {} | {} | {}{} | {} |\n",
+            change.change,
+            change.package_name,
+            change.platform.as_deref().unwrap_or("source"),
+            format_version_change(change),
+            format_license_change(change),
+            if change.license_changed {
+                " (changed)"
+            } else {
+                ""
+            },
+            verdict,
+        ));
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_lockfile_source() {
+        assert_eq!(
+            LockfileSource::from_str("pixi.lock").unwrap(),
+            LockfileSource::Path(PathBuf::from("pixi.lock"))
+        );
+        assert_eq!(
+            LockfileSource::from_str("git:HEAD~1:env/pixi.lock").unwrap(),
+            LockfileSource::Git {
+                revision: "HEAD~1".to_string(),
+                path: PathBuf::from("env/pixi.lock"),
+            }
+        );
+        assert!(LockfileSource::from_str("git:HEAD").is_err());
+    }
+
+    #[test]
+    fn test_git_path() {
+        let (toplevel, path) = git_path(Path::new("tests/../src/./diff.rs")).unwrap();
+        assert_eq!(path, "src/diff.rs");
+        assert!(toplevel.join(&path).is_file());
+
+        let absolute = env::current_dir().unwrap().join("tests/default_pixi.lock");
+        let (_, path) = git_path(&absolute).unwrap();
+        assert_eq!(path, "tests/default_pixi.lock");
+    }
+
+    #[test]
+    fn test_compare_versions() {
+        assert_eq!(
+            compare_versions(&Some("1.10.0".to_string()), &Some("1.9.0".to_string())),
+            Ordering::Greater
+        );
+        assert_eq!(
+            compare_versions(&Some("3.3.1".to_string()), &Some("3.3.2".to_string())),
+            Ordering::Less
+        );
+    }
+}
@@ -0,0 +1,302 @@
+use std::io::Write;
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+use serde::Serialize;
+use serde_json::json;
+
+use crate::{
+    collect_license_infos,
+    explanation::{pretty_print_requirements, RequirementEvaluation},
+    expression_utils::format_parsed_expression,
+    json_output::write_json_document,
+    license_allowlist::{join_ignore_rules, IgnorePackage},
+    license_info::{CheckResult, IgnoredPackages, LicenseInfo, LicenseState},
+    CondaDenyCheckConfig, CondaDenyExplainConfig, CondaDenyPolicy, OutputFormat, ReasonAction,
+    UnsafeReason,
+};
+
+/// Everything that went into the verdict for one occurrence of a package
+#[derive(Debug, Clone, Serialize)]
+struct PackageExplanation {
+    package_name: String,
+    version: Option<String>,
+    build: Option<String>,
+    platform: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source_identifier: Option<String>,
+    environments: Vec<String>,
+    license: LicenseState,
+    /// The license expression with explicit grouping, if it could be parsed
+    parsed_license: Option<String>,
+    policy: String,
+    /// The ignore-packages rules that matched the package
+    ignored_by: Vec<IgnorePackage>,
+    requirements: Vec<RequirementEvaluation>,
+    result: CheckResult,
+    reason: Option<UnsafeReason>,
+    /// Why the result came out as it did
+    summary: String,
+}
+
+pub fn explain<W: Write>(config: CondaDenyExplainConfig, mut out: W) -> Result<()> {
+    let check_config = &config.check_config;
+
+    // Ignored packages are collected as well, so that we can tell which rule ignored them
+    let license_infos = collect_license_infos(
+        check_config.lockfile_or_prefix.clone(),
+        &check_config.ignore_packages,
+        IgnoredPackages::Keep,
+    )
+    .with_context(|| "Fetching license information failed.")?;
+
+    let occurrences: Vec<&LicenseInfo> = license_infos
+        .license_infos
+        .iter()
+        .filter(|license_info| license_info.package_name == config.package_name)
+        .filter(|license_info| config.version.is_none() || license_info.version == config.version)
+        .collect();
+
+    if occurrences.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Package {}{} was not found",
+            config.package_name,
+            config
+                .version
+                .as_ref()
+                .map(|version| format!("=={version}"))
+                .unwrap_or_default()
+        ));
+    }
+
+    let default_policy = check_config.default_policy();
+    let mut explanations = Vec::new();
+    for license_info in occurrences {
+        for policy in
+            check_config.resolve_policies(&default_policy, &license_info.environments())?
+        {
+            explanations.push(explain_package(license_info, policy, check_config)?);
+        }
+    }
+
+    match check_config.output_format {
+        OutputFormat::Default => {
+            let output: Vec<String> = explanations.iter().map(pretty_print).collect();
+            writeln!(out, "{}", output.join("\n"))?;
+        }
+        OutputFormat::Json | OutputFormat::JsonPretty => {
+            write_json_document(
+                &mut out,
+                "explain",
+                &check_config.lockfile_or_prefix,
+                json!({ "explanations": explanations }),
+                check_config.output_format,
+            )?;
+        }
+        OutputFormat::Markdown
+        | OutputFormat::Sarif
+        | OutputFormat::Junit
+        | OutputFormat::Template => {
+            unreachable!("Unsupported output formats are rejected by get_config_options")
+        }
+        OutputFormat::Csv => {
+            #[derive(Debug, Clone, Serialize)]
+            struct PackageExplanationRow {
+                package_name: String,
+                version: Option<String>,
+                build: Option<String>,
+                platform: Option<String>,
+                environments: String,
+                license: Option<String>,
+                parsed_license: Option<String>,
+                policy: String,
+                ignored_by: String,
+                requirements: String,
+                result: CheckResult,
+                reason: Option<UnsafeReason>,
+                summary: String,
+            }
+
+            let mut writer = csv::WriterBuilder::new().from_writer(vec![]);
+
+            for explanation in explanations {
+                let row = PackageExplanationRow {
+                    package_name: explanation.package_name,
+                    version: explanation.version,
+                    build: explanation.build,
+                    platform: explanation.platform,
+                    environments: explanation.environments.join(";"),
+                    license: explanation.license.license_string(),
+                    parsed_license: explanation.parsed_license,
+                    policy: explanation.policy,
+                    ignored_by: join_ignore_rules(&explanation.ignored_by),
+                    requirements: explanation
+                        .requirements
+                        .iter()
+                        .map(|evaluation| {
+                            format!("{}: {}", evaluation.requirement, evaluation.result)
+                        })
+                        .collect::<Vec<_>>()
+                        .join("; "),
+                    result: explanation.result,
+                    reason: explanation.reason,
+                    summary: explanation.summary,
+                };
+                writer.serialize(&row).with_context(|| {
+                    format!("Failed to serialize the following explanation to CSV: {row:?}")
+                })?;
+            }
+
+            out.write_all(&writer.into_inner()?)?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+fn explain_package(
+    license_info: &LicenseInfo,
+    policy: &CondaDenyPolicy,
+    config: &CondaDenyCheckConfig,
+) -> Result<PackageExplanation> {
+    let ignored_by = license_info
+        .matching_ignore_rules(config.ignore_packages.iter().chain(&policy.ignore_packages))?;
+
+    let (result, checked_license_info) = license_info.check_against(policy, config);
+    let result = if ignored_by.is_empty() {
+        result
+    } else {
+        CheckResult::Ignored
+    };
+    let reason = checked_license_info.reason;
+
+    let summary = if ignored_by.is_empty() {
+        summarize(result, reason, config)
+    } else {
+        format!(
+            "The package is ignored by ignore-packages: {}",
+            join_ignore_rules(&ignored_by)
+        )
+    };
+
+    let parsed_license = match &license_info.license {
+        LicenseState::Valid(expression) => Some(format_parsed_expression(expression)),
+        _ => None,
+    };
+
+    Ok(PackageExplanation {
+        package_name: license_info.package_name.clone(),
+        version: license_info.version.clone(),
+        build: license_info.build.clone(),
+        platform: license_info.platform.clone(),
+        source_identifier: license_info.source_identifier.clone(),
+        environments: license_info.environments().into_iter().collect(),
+        license: license_info.license.clone(),
+        parsed_license,
+        policy: policy.name.clone(),
+        ignored_by,
+        requirements: checked_license_info
+            .explanation
+            .map(|explanation| explanation.requirements)
+            .unwrap_or_default(),
+        result,
+        reason,
+        summary,
+    })
+}
+
+fn summarize(
+    result: CheckResult,
+    reason: Option<UnsafeReason>,
+    config: &CondaDenyCheckConfig,
+) -> String {
+    let Some(reason) = reason else {
+        return match result {
+            CheckResult::Warn => {
+                "The license expression can only be satisfied with warn-licenses".to_string()
+            }
+            _ => "The license expression can be satisfied with allowed licenses".to_string(),
+        };
+    };
+
+    let summary = match reason {
+        UnsafeReason::Disallowed => {
+            "The license expression cannot be satisfied with allowed licenses"
+        }
+        UnsafeReason::Denied => {
+            "The license expression cannot be satisfied without a license from deny-licenses"
+        }
+        UnsafeReason::NonSpdx => "The license is not a valid SPDX expression",
+        UnsafeReason::Missing => "The package has no license information",
+    };
+    match config.reason_actions.action(reason) {
+        ReasonAction::Fail => summary.to_string(),
+        ReasonAction::Warn => {
+            format!("{summary}, which reason-actions or fail-on report as a warning")
+        }
+        ReasonAction::Ignore => format!("{summary}, which reason-actions ignores"),
+    }
+}
+
+fn pretty_print(explanation: &PackageExplanation) -> String {
+    let mut output = String::new();
+
+    let name = explanation
+        .source_identifier
+        .as_deref()
+        .unwrap_or(&explanation.package_name);
+    output.push_str(&format!(
+        "{} {}-{} ({})\n",
+        name.blue(),
+        explanation
+            .version
+            .as_deref()
+            .unwrap_or("unknown-source")
+            .cyan(),
+        explanation
+            .build
+            .as_deref()
+            .unwrap_or("unknown-source")
+            .bright_cyan()
+            .italic(),
+        explanation
+            .platform
+            .as_deref()
+            .unwrap_or("unknown-source")
+            .bright_purple(),
+    ));
+    output.push_str(&format!(
+        "  Found in: {}\n",
+        explanation.environments.join(", ")
+    ));
+    output.push_str(&format!(
+        "  License: {}\n",
+        explanation
+            .license
+            .license_string()
+            .as_deref()
+            .unwrap_or("no license")
+            .yellow()
+    ));
+    if let Some(parsed_license) = &explanation.parsed_license {
+        output.push_str(&format!("  Parsed: {parsed_license}\n"));
+    }
+    output.push_str(&format!("  Policy: {}\n", explanation.policy.bold()));
+    output.push_str(&pretty_print_requirements(&explanation.requirements));
+    if !explanation.ignored_by.is_empty() {
+        output.push_str(&format!(
+            "  Ignored by: {}\n",
+            join_ignore_rules(&explanation.ignored_by)
+        ));
+    }
+
+    let result = match explanation.result {
+        CheckResult::Safe => "safe".green(),
+        CheckResult::Warn => "warn".yellow(),
+        CheckResult::Unsafe => "unsafe".red(),
+        CheckResult::Ignored => "ignored".bright_black(),
+    };
+    output.push_str(&format!("  Verdict: {result} ({})\n", explanation.summary));
+
+    output
+}
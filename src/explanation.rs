@@ -0,0 +1,250 @@
+use std::fmt;
+
+use colored::Colorize;
+use serde::Serialize;
+use spdx::{Expression, LicenseReq};
+
+use crate::{
+    expression_utils::{find_license_req, is_osi_approved},
+    license_allowlist::{AllowlistEntry, LicenseSource},
+    license_info::LicenseState,
+    CondaDenyPolicy,
+};
+
+/// Why a license was considered safe or unsafe by a policy
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Explanation {
+    pub policy: String,
+    /// Every requirement of the license expression, in the order they appear
+    pub requirements: Vec<RequirementEvaluation>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RequirementEvaluation {
+    pub requirement: String,
+    pub result: RequirementResult,
+    /// The configured entry that decided the result, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub matched: Option<AllowlistEntry>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RequirementResult {
+    Allowed,
+    Warn,
+    Denied,
+    NotAllowed,
+}
+
+impl Explanation {
+    /// Evaluates each requirement of the license individually against the policy.
+    ///
+    /// Invalid and missing licenses have no requirements to evaluate.
+    pub fn new(license: &LicenseState, policy: &CondaDenyPolicy) -> Self {
+        let requirements = match license {
+            LicenseState::Valid(expression) => expression
+                .requirements()
+                .map(|requirement| evaluate_requirement(&requirement.req, policy))
+                .collect(),
+            LicenseState::Invalid(_) | LicenseState::NoLicense => vec![],
+        };
+
+        Explanation {
+            policy: policy.name.clone(),
+            requirements,
+        }
+    }
+
+    pub fn pretty_print(&self) -> String {
+        pretty_print_requirements(&self.requirements)
+    }
+}
+
+pub fn pretty_print_requirements(requirements: &[RequirementEvaluation]) -> String {
+    let mut output = String::new();
+    for evaluation in requirements {
+        let result = match evaluation.result {
+            RequirementResult::Allowed => "allowed".green(),
+            RequirementResult::Warn => "warn".yellow(),
+            RequirementResult::Denied => "denied".red(),
+            RequirementResult::NotAllowed => "not allowed".red(),
+        };
+        output.push_str(&format!("    ↳ {}: {}", evaluation.requirement, result));
+        if let Some(matched) = &evaluation.matched {
+            output.push_str(&format!(
+                " by {} {}",
+                matched.entry.yellow(),
+                format!("({})", matched.source).bright_black()
+            ));
+        }
+        output.push('\n');
+    }
+    output
+}
+
+/// Evaluates a requirement with the same matching as the verdict, in the same order:
+/// deny-licenses first, then the allowlist, OSI approved licenses and warn-licenses.
+fn evaluate_requirement(
+    license_req: &LicenseReq,
+    policy: &CondaDenyPolicy,
+) -> RequirementEvaluation {
+    let requirement = license_req.to_string();
+    let entry = |license: &Expression, source: LicenseSource| AllowlistEntry {
+        entry: license.to_string(),
+        source,
+    };
+
+    let (result, matched) =
+        if let Some(license) = find_license_req(license_req, &policy.deny_licenses) {
+            let entry = entry(license, LicenseSource::DenyLicenses);
+            (RequirementResult::Denied, Some(entry))
+        } else if let Some(license) = find_license_req(license_req, &policy.safe_licenses) {
+            // The sources only tell which part of the allowlist the license came from
+            let source = policy
+                .safe_license_sources
+                .get(&requirement)
+                .map_or(LicenseSource::SafeLicenses, |entry| entry.source.clone());
+            (RequirementResult::Allowed, Some(entry(license, source)))
+        } else if policy.osi && is_osi_approved(&requirement) {
+            let entry = AllowlistEntry {
+                entry: requirement.clone(),
+                source: LicenseSource::Osi,
+            };
+            (RequirementResult::Allowed, Some(entry))
+        } else if let Some(license) = find_license_req(license_req, &policy.warn_licenses) {
+            let entry = entry(license, LicenseSource::WarnLicenses);
+            (RequirementResult::Warn, Some(entry))
+        } else {
+            (RequirementResult::NotAllowed, None)
+        };
+
+    RequirementEvaluation {
+        requirement,
+        result,
+        matched,
+    }
+}
+
+impl fmt::Display for RequirementResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RequirementResult::Allowed => write!(f, "allowed"),
+            RequirementResult::Warn => write!(f, "warn"),
+            RequirementResult::Denied => write!(f, "denied"),
+            RequirementResult::NotAllowed => write!(f, "not-allowed"),
+        }
+    }
+}
+
+impl fmt::Display for LicenseSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LicenseSource::SafeLicenses => write!(f, "safe-licenses"),
+            LicenseSource::LicenseAllowlist(location) => write!(f, "license-allowlist {location}"),
+            LicenseSource::Osi => write!(f, "osi"),
+            LicenseSource::WarnLicenses => write!(f, "warn-licenses"),
+            LicenseSource::DenyLicenses => write!(f, "deny-licenses"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        expression_utils::parse_expression, license_allowlist::license_sources, DEFAULT_POLICY_NAME,
+    };
+
+    fn policy(osi: bool) -> CondaDenyPolicy {
+        let safe_licenses = vec![parse_expression("MIT").unwrap()];
+        CondaDenyPolicy {
+            name: DEFAULT_POLICY_NAME.to_string(),
+            osi,
+            safe_license_sources: license_sources(
+                &safe_licenses,
+                LicenseSource::LicenseAllowlist("allowlist.toml".to_string()),
+            ),
+            safe_licenses,
+            warn_licenses: vec![parse_expression("MPL-2.0").unwrap()],
+            deny_licenses: vec![parse_expression("AGPL-3.0-only").unwrap()],
+            ignore_packages: vec![],
+            environments: vec![],
+            prefixes: vec![],
+        }
+    }
+
+    #[test]
+    fn test_explain_requirements() {
+        let license = LicenseState::Valid(
+            parse_expression("MIT AND (MPL-2.0 OR AGPL-3.0-only) AND GPL-3.0-only").unwrap(),
+        );
+        let explanation = Explanation::new(&license, &policy(false));
+
+        let results: Vec<_> = explanation
+            .requirements
+            .iter()
+            .map(|evaluation| (evaluation.requirement.as_str(), evaluation.result))
+            .collect();
+        assert_eq!(
+            results,
+            vec![
+                ("MIT", RequirementResult::Allowed),
+                ("MPL-2.0", RequirementResult::Warn),
+                ("AGPL-3.0-only", RequirementResult::Denied),
+                ("GPL-3.0-only", RequirementResult::NotAllowed),
+            ]
+        );
+        assert_eq!(
+            explanation.requirements[0].matched,
+            Some(AllowlistEntry {
+                entry: "MIT".to_string(),
+                source: LicenseSource::LicenseAllowlist("allowlist.toml".to_string()),
+            })
+        );
+        assert_eq!(explanation.requirements[3].matched, None);
+    }
+
+    #[test]
+    fn test_explain_matches_verdict() {
+        // Allowlists without recorded sources still name the entry that allowed the license
+        let mut policy = CondaDenyPolicy {
+            safe_license_sources: Default::default(),
+            ..policy(false)
+        };
+        let license = LicenseState::Valid(parse_expression("MIT OR AGPL-3.0-only").unwrap());
+        let explanation = Explanation::new(&license, &policy);
+        assert_eq!(
+            explanation.requirements[0].matched,
+            Some(AllowlistEntry {
+                entry: "MIT".to_string(),
+                source: LicenseSource::SafeLicenses,
+            })
+        );
+
+        // deny-licenses win over the allowlist, as in the verdict
+        policy.deny_licenses.push(parse_expression("MIT").unwrap());
+        let explanation = Explanation::new(&license, &policy);
+        assert_eq!(
+            explanation.requirements[0].result,
+            RequirementResult::Denied
+        );
+    }
+
+    #[test]
+    fn test_explain_osi() {
+        let license = LicenseState::Valid(parse_expression("Apache-2.0").unwrap());
+        let explanation = Explanation::new(&license, &policy(true));
+        assert_eq!(
+            explanation.requirements[0].result,
+            RequirementResult::Allowed
+        );
+        assert_eq!(
+            explanation.requirements[0].matched.as_ref().unwrap().source,
+            LicenseSource::Osi
+        );
+
+        let explanation = Explanation::new(&LicenseState::NoLicense, &policy(true));
+        assert!(explanation.requirements.is_empty());
+    }
+}
@@ -1,5 +1,9 @@
 use anyhow::{Context, Result};
-use spdx::{Expression, LicenseReq, ParseMode};
+use serde::Serialize;
+use spdx::{
+    expression::{ExprNode, Operator},
+    Expression, LicenseReq, ParseMode,
+};
 
 pub fn extract_license_texts(expression: &Expression) -> Vec<String> {
     expression
@@ -8,19 +12,184 @@ pub fn extract_license_texts(expression: &Expression) -> Vec<String> {
         .collect()
 }
 
-fn check_license_req_safety(license_req: &LicenseReq, safe_licenses: &[Expression]) -> bool {
-    let safe_license_requirements: Vec<String> = safe_licenses
+/// Returns the first of the configured licenses that contains the requirement.
+///
+/// This decides both the verdict and the entry named by the explanation.
+pub fn find_license_req<'a>(
+    license_req: &LicenseReq,
+    licenses: &'a [Expression],
+) -> Option<&'a Expression> {
+    let requirement = license_req.to_string();
+    licenses
         .iter()
-        .flat_map(extract_license_texts)
-        .collect();
+        .find(|license| extract_license_texts(license).contains(&requirement))
+}
 
-    safe_license_requirements.contains(&license_req.to_string())
+fn check_license_req_safety(license_req: &LicenseReq, safe_licenses: &[Expression]) -> bool {
+    find_license_req(license_req, safe_licenses).is_some()
+}
+
+/// Returns true if the license of a requirement is OSI approved.
+pub fn is_osi_approved(requirement: &str) -> bool {
+    spdx::license_id(requirement).is_some_and(|id| id.is_osi_approved())
 }
 
 pub fn check_expression_safety(expression: &Expression, safe_licenses: &[Expression]) -> bool {
     expression.evaluate(|req| check_license_req_safety(req, safe_licenses))
 }
 
+/// Returns true if the expression can be satisfied with safe licenses that are not denied.
+pub fn check_expression_safety_without_denied(
+    expression: &Expression,
+    safe_licenses: &[Expression],
+    deny_licenses: &[Expression],
+) -> bool {
+    expression.evaluate(|req| {
+        check_license_req_safety(req, safe_licenses)
+            && !check_license_req_safety(req, deny_licenses)
+    })
+}
+
+/// Returns true if the expression cannot be satisfied without one of the denied licenses.
+pub fn check_expression_denied(expression: &Expression, deny_licenses: &[Expression]) -> bool {
+    !expression.evaluate(|req| !check_license_req_safety(req, deny_licenses))
+}
+
+/// Formats the parsed expression with explicit parentheses around every operation,
+/// which shows how `AND` and `OR` were grouped.
+pub fn format_parsed_expression(expression: &Expression) -> String {
+    let mut operands: Vec<String> = Vec::new();
+    for node in expression.iter() {
+        match node {
+            ExprNode::Req(req) => operands.push(req.req.to_string()),
+            ExprNode::Op(operator) => {
+                let right = operands.pop().expect("Operators always have two operands");
+                let left = operands.pop().expect("Operators always have two operands");
+                let operator = match operator {
+                    Operator::And => "AND",
+                    Operator::Or => "OR",
+                };
+                operands.push(format!("({left} {operator} {right})"));
+            }
+        }
+    }
+    operands.pop().unwrap_or_default()
+}
+
+/// Formats the expression so that equivalent ways of writing it compare equal:
+/// operands of the same operator are flattened, sorted and deduplicated.
+pub fn normalize_expression(expression: &Expression) -> String {
+    enum Node {
+        Req(String),
+        Op(Operator, Vec<Node>),
+    }
+
+    fn format_node(node: &Node, parenthesize: bool) -> String {
+        match node {
+            Node::Req(req) => req.clone(),
+            Node::Op(operator, operands) => {
+                let separator = match operator {
+                    Operator::And => " AND ",
+                    Operator::Or => " OR ",
+                };
+                let mut operands: Vec<String> = operands
+                    .iter()
+                    .map(|node| format_node(node, true))
+                    .collect();
+                operands.sort();
+                operands.dedup();
+                if operands.len() == 1 {
+                    return operands.remove(0);
+                }
+                let joined = operands.join(separator);
+                if parenthesize {
+                    format!("({joined})")
+                } else {
+                    joined
+                }
+            }
+        }
+    }
+
+    let mut nodes: Vec<Node> = Vec::new();
+    for node in expression.iter() {
+        match node {
+            ExprNode::Req(req) => nodes.push(Node::Req(req.req.to_string())),
+            ExprNode::Op(operator) => {
+                let right = nodes.pop().expect("Operators always have two operands");
+                let left = nodes.pop().expect("Operators always have two operands");
+                let mut operands = Vec::new();
+                for operand in [left, right] {
+                    match operand {
+                        Node::Op(inner, inner_operands) if inner == *operator => {
+                            operands.extend(inner_operands)
+                        }
+                        operand => operands.push(operand),
+                    }
+                }
+                nodes.push(Node::Op(*operator, operands));
+            }
+        }
+    }
+    nodes
+        .pop()
+        .map(|node| format_node(&node, false))
+        .unwrap_or_default()
+}
+
+/// A parsed license expression, as written to the JSON output
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ExpressionTree {
+    /// A license with an optional exception, e.g. `GPL-3.0-only WITH GCC-exception-3.1`
+    License {
+        license: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        exception: Option<String>,
+    },
+    /// All operands are required
+    And { operands: Vec<ExpressionTree> },
+    /// One of the operands is required
+    Or { operands: Vec<ExpressionTree> },
+}
+
+impl ExpressionTree {
+    /// Builds the tree of the expression, with the operands of nested operations of the same
+    /// kind flattened, e.g. `MIT OR Apache-2.0 OR BSD-3-Clause` has three operands.
+    pub fn from_expression(expression: &Expression) -> Self {
+        let mut nodes: Vec<ExpressionTree> = Vec::new();
+        for node in expression.iter() {
+            match node {
+                ExprNode::Req(req) => nodes.push(ExpressionTree::License {
+                    license: req.req.license.to_string(),
+                    exception: req.req.addition.as_ref().map(ToString::to_string),
+                }),
+                ExprNode::Op(operator) => {
+                    let right = nodes.pop().expect("Operators always have two operands");
+                    let left = nodes.pop().expect("Operators always have two operands");
+                    let mut operands = Vec::new();
+                    for operand in [left, right] {
+                        match (operator, operand) {
+                            (Operator::And, ExpressionTree::And { operands: inner })
+                            | (Operator::Or, ExpressionTree::Or { operands: inner }) => {
+                                operands.extend(inner)
+                            }
+                            (_, operand) => operands.push(operand),
+                        }
+                    }
+                    nodes.push(match operator {
+                        Operator::And => ExpressionTree::And { operands },
+                        Operator::Or => ExpressionTree::Or { operands },
+                    });
+                }
+            }
+        }
+        nodes
+            .pop()
+            .expect("A parsed expression has at least one license")
+    }
+}
+
 pub fn parse_expression(expression_str: &str) -> Result<Expression> {
     let parse_mode = ParseMode::STRICT;
 
@@ -34,6 +203,22 @@ mod tests {
 
     use crate::expression_utils::parse_expression;
 
+    #[test]
+    fn test_normalize_expression() {
+        let normalize =
+            |expression: &str| normalize_expression(&parse_expression(expression).unwrap());
+        assert_eq!(
+            normalize("MIT OR Apache-2.0"),
+            normalize("Apache-2.0 OR MIT")
+        );
+        assert_eq!(
+            normalize("(BSD-3-Clause AND MIT) OR Apache-2.0 OR MIT"),
+            "(BSD-3-Clause AND MIT) OR Apache-2.0 OR MIT"
+        );
+        assert_eq!(normalize("MIT AND MIT"), "MIT");
+        assert_ne!(normalize("MIT"), normalize("BUSL-1.1"));
+    }
+
     #[test]
     fn test_extract_license_texts() {
         let expression = parse_expression("MIT OR GPL-3.0-or-later").unwrap();
@@ -73,6 +258,18 @@ mod tests {
         assert!(!license_allowed);
     }
 
+    #[test]
+    fn test_format_parsed_expression() {
+        let expression = parse_expression("MIT AND Apache-2.0 OR GPL-3.0-only").unwrap();
+        assert_eq!(
+            format_parsed_expression(&expression),
+            "((MIT AND Apache-2.0) OR GPL-3.0-only)"
+        );
+
+        let expression = parse_expression("MIT").unwrap();
+        assert_eq!(format_parsed_expression(&expression), "MIT");
+    }
+
     #[test]
     fn test_extract_license_ids() {
         let expression = parse_expression("MIT OR GPL-3.0-or-later").unwrap();
@@ -121,6 +318,19 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_check_expression_denied() {
+        let deny_licenses = vec![Expression::parse("GPL-3.0-only").unwrap()];
+
+        let denied = parse_expression("GPL-3.0-only").unwrap();
+        let denied_and = parse_expression("MIT AND GPL-3.0-only").unwrap();
+        let choice = parse_expression("MIT OR GPL-3.0-only").unwrap();
+
+        assert!(check_expression_denied(&denied, &deny_licenses));
+        assert!(check_expression_denied(&denied_and, &deny_licenses));
+        assert!(!check_expression_denied(&choice, &deny_licenses));
+    }
+
     #[test]
     fn test_parse_expression_lowercase_and_or() {
         let expression_lower_and = super::parse_expression("MIT and PSF-2.0").unwrap();
@@ -145,4 +355,32 @@ mod tests {
             "MIT OR PSF-2.0".to_string()
         );
     }
+
+    #[test]
+    fn test_expression_tree() {
+        let expression =
+            parse_expression("MIT OR Apache-2.0 OR (GPL-3.0-only WITH GCC-exception-3.1 AND Zlib)")
+                .unwrap();
+        assert_eq!(
+            serde_json::to_value(ExpressionTree::from_expression(&expression)).unwrap(),
+            serde_json::json!({
+                "type": "or",
+                "operands": [
+                    { "type": "license", "license": "MIT" },
+                    { "type": "license", "license": "Apache-2.0" },
+                    {
+                        "type": "and",
+                        "operands": [
+                            {
+                                "type": "license",
+                                "license": "GPL-3.0-only",
+                                "exception": "GCC-exception-3.1"
+                            },
+                            { "type": "license", "license": "Zlib" },
+                        ]
+                    },
+                ]
+            })
+        );
+    }
 }
@@ -0,0 +1,159 @@
+use std::io::Write;
+
+use anyhow::Result;
+use serde_json::{json, Value};
+
+use crate::{
+    spdx_document::{created, format_timestamp},
+    CheckOutput, LockfileOrPrefix, OutputFormat,
+};
+
+/// Version of the JSON output, increased on incompatible changes.
+///
+/// The output is described by `schema/output.schema.json`.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// What was run on which lockfiles or conda prefixes.
+pub(crate) fn metadata(command: &str, lockfile_or_prefix: &LockfileOrPrefix) -> Result<Value> {
+    let (lockfiles, prefixes, environments, platforms) = match lockfile_or_prefix {
+        LockfileOrPrefix::Lockfile(lockfile_spec) => (
+            lockfile_spec.lockfiles.clone(),
+            vec![],
+            lockfile_spec.environments.clone(),
+            lockfile_spec
+                .platforms
+                .as_ref()
+                .map(|platforms| platforms.iter().map(|p| p.to_string()).collect::<Vec<_>>()),
+        ),
+        LockfileOrPrefix::Prefix(prefixes) => (vec![], prefixes.clone(), None, None),
+    };
+    Ok(json!({
+        "tool": env!("CARGO_PKG_NAME"),
+        "version": env!("CARGO_PKG_VERSION"),
+        "command": command,
+        "created": format_timestamp(created()?),
+        "lockfiles": lockfiles,
+        "prefixes": prefixes,
+        "environments": environments,
+        "platforms": platforms,
+    }))
+}
+
+/// The packages of a check, each with the `result` of the check in addition to the fields that
+/// `list` writes.
+pub(crate) fn checked_packages(check_output: &CheckOutput) -> Result<Vec<Value>> {
+    let results = [
+        ("safe", &check_output.safe_dependencies),
+        ("warn", &check_output.warn_dependencies),
+        ("unsafe", &check_output.unsafe_dependencies),
+        ("ignored", &check_output.ignored_dependencies),
+        ("baselined", &check_output.baselined_dependencies),
+        (
+            "outside-closure",
+            &check_output.outside_closure_dependencies,
+        ),
+    ];
+    let mut packages = Vec::new();
+    for (result, license_infos) in results {
+        for license_info in license_infos {
+            let mut package = serde_json::to_value(license_info)?;
+            package["result"] = json!(result);
+            packages.push(package);
+        }
+    }
+    Ok(packages)
+}
+
+/// Wraps the output of a command in the versioned document shared by all commands:
+/// the fields of `content` with the schema version and the metadata of the run.
+fn json_document(
+    command: &str,
+    lockfile_or_prefix: &LockfileOrPrefix,
+    content: Value,
+) -> Result<Value> {
+    let Value::Object(content) = content else {
+        return Err(anyhow::anyhow!(
+            "The JSON output of {command} must be an object"
+        ));
+    };
+    let mut document = serde_json::Map::new();
+    document.insert("schema_version".to_string(), json!(SCHEMA_VERSION));
+    document.insert(
+        "metadata".to_string(),
+        metadata(command, lockfile_or_prefix)?,
+    );
+    document.extend(content);
+    Ok(Value::Object(document))
+}
+
+/// Writes the versioned document of a command, on one line for `json` and indented for
+/// `json-pretty`, followed by a newline.
+pub(crate) fn write_json_document<W: Write>(
+    out: &mut W,
+    command: &str,
+    lockfile_or_prefix: &LockfileOrPrefix,
+    content: Value,
+    output_format: OutputFormat,
+) -> Result<()> {
+    let document = json_document(command, lockfile_or_prefix, content)?;
+    if matches!(output_format, OutputFormat::JsonPretty) {
+        serde_json::to_writer_pretty(&mut *out, &document)?;
+    } else {
+        serde_json::to_writer(&mut *out, &document)?;
+    }
+    writeln!(out)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schema_version() {
+        let schema: Value =
+            serde_json::from_str(include_str!("../schema/output.schema.json")).unwrap();
+        assert_eq!(
+            schema["properties"]["schema_version"]["const"],
+            json!(SCHEMA_VERSION)
+        );
+    }
+
+    #[test]
+    fn test_json_document() {
+        let document = json_document(
+            "list",
+            &LockfileOrPrefix::Prefix(vec!["/opt/conda".into()]),
+            json!({ "packages": [] }),
+        )
+        .unwrap();
+        assert_eq!(document["schema_version"], json!(SCHEMA_VERSION));
+        assert_eq!(document["packages"], json!([]));
+        assert_eq!(document["metadata"]["command"], "list");
+        assert_eq!(document["metadata"]["prefixes"], json!(["/opt/conda"]));
+    }
+
+    #[test]
+    fn test_write_json_document() {
+        let lockfile_or_prefix = LockfileOrPrefix::Prefix(vec!["/opt/conda".into()]);
+        let write = |output_format| {
+            let mut out = Vec::new();
+            write_json_document(
+                &mut out,
+                "list",
+                &lockfile_or_prefix,
+                json!({ "packages": [] }),
+                output_format,
+            )
+            .unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        let compact = write(OutputFormat::Json);
+        assert!(compact.ends_with("}\n"));
+        assert_eq!(compact.lines().count(), 1);
+        let pretty = write(OutputFormat::JsonPretty);
+        assert!(pretty.ends_with("}\n"));
+        assert!(pretty.lines().count() > 1);
+    }
+}
@@ -0,0 +1,308 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    license_allowlist::join_ignore_rules,
+    license_info::{LicenseInfo, Provenance},
+    CheckOutput, CondaDenyCheckConfig, ReasonAction,
+};
+
+#[derive(Clone)]
+enum Outcome {
+    Passed,
+    Failure {
+        kind: String,
+        message: String,
+        details: String,
+    },
+    Skipped {
+        message: String,
+    },
+}
+
+struct TestCase {
+    name: String,
+    outcome: Outcome,
+    /// Warnings that do not fail the test case
+    system_out: Vec<String>,
+}
+
+/// Escapes text for use in XML and HTML attributes and elements.
+pub(crate) fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn test_case_name(license_info: &LicenseInfo) -> String {
+    let mut name = format!(
+        "{} {}",
+        license_info.package_name,
+        license_info.version.as_deref().unwrap_or("unknown")
+    );
+    if let Some(build) = &license_info.build {
+        name.push_str(&format!(" ({build})"));
+    }
+    if let Some(policy) = &license_info.policy {
+        name.push_str(&format!(" [{policy}]"));
+    }
+    name
+}
+
+fn failure(license_info: &LicenseInfo) -> Outcome {
+    let reason = license_info
+        .reason
+        .map_or("unsafe".to_string(), |reason| reason.to_string());
+    let license = license_info.license.display_string();
+    let mut details = format!("license: {license}\nreason: {reason}");
+    if let Some(policy) = &license_info.policy {
+        details.push_str(&format!("\npolicy: {policy}"));
+    }
+    Outcome::Failure {
+        message: format!("{reason}: {license}"),
+        kind: reason,
+        details,
+    }
+}
+
+fn skipped(license_info: &LicenseInfo, why: &str) -> Outcome {
+    let why = if license_info.ignored_by.is_empty() {
+        why.to_string()
+    } else {
+        format!(
+            "{why} by ignore-packages: {}",
+            join_ignore_rules(&license_info.ignored_by)
+        )
+    };
+    let message = match license_info.reason {
+        Some(reason) => format!(
+            "{why} ({reason}: {})",
+            license_info.license.display_string()
+        ),
+        None => why,
+    };
+    Outcome::Skipped { message }
+}
+
+/// Name of the test suite of a lockfile, environment and platform
+fn suite_name(provenance: &Provenance) -> String {
+    let platform = provenance.platform.as_deref().unwrap_or("unknown");
+    match &provenance.lockfile {
+        Some(lockfile) => format!("{lockfile}:{}:{platform}", provenance.environment),
+        // The environment of a conda prefix is its path
+        None => format!("{}:{platform}", provenance.environment),
+    }
+}
+
+/// Formats the check output as JUnit XML with a test suite per lockfile, environment and platform
+/// and a test case per package.
+///
+/// Unsafe packages are failures, ignored, baselined and packages outside the runtime closure are skipped.
+pub fn junit_report(check_output: &CheckOutput, config: &CondaDenyCheckConfig) -> String {
+    let outcomes = check_output
+        .safe_dependencies
+        .iter()
+        .map(|license_info| (license_info, Outcome::Passed, None))
+        .chain(check_output.warn_dependencies.iter().map(|license_info| {
+            let warning = format!(
+                "warning: {} requires attention",
+                license_info.license.display_string()
+            );
+            (license_info, Outcome::Passed, Some(warning))
+        }))
+        .chain(
+            check_output
+                .unsafe_dependencies
+                .iter()
+                .map(|license_info| (license_info, failure(license_info), None)),
+        )
+        .chain(
+            check_output
+                .ignored_dependencies
+                .iter()
+                .map(|license_info| (license_info, skipped(license_info, "ignored"), None)),
+        )
+        .chain(
+            check_output
+                .baselined_dependencies
+                .iter()
+                .map(|license_info| {
+                    (
+                        license_info,
+                        skipped(license_info, "known from the baseline"),
+                        None,
+                    )
+                }),
+        )
+        .chain(
+            check_output
+                .outside_closure_dependencies
+                .iter()
+                .map(|license_info| {
+                    (
+                        license_info,
+                        skipped(license_info, "outside the runtime closure"),
+                        None,
+                    )
+                }),
+        );
+
+    let mut suites: BTreeMap<String, Vec<TestCase>> = BTreeMap::new();
+    for (license_info, outcome, warning) in outcomes {
+        let mut findings = Vec::new();
+        for change in &check_output.license_changes {
+            if change.package_name == license_info.package_name
+                && change.platform == license_info.platform
+                && change.new_version == license_info.version
+            {
+                findings.push((
+                    config.license_changes,
+                    "license-change",
+                    format!(
+                        "license changed from {} to {}",
+                        change.old_license.as_deref().unwrap_or("no license"),
+                        change.new_license.as_deref().unwrap_or("no license")
+                    ),
+                ));
+            }
+        }
+        for inconsistency in &check_output.inconsistent_licenses {
+            if inconsistency.package_name == license_info.package_name
+                && license_info.version.as_ref() == Some(&inconsistency.version)
+            {
+                findings.push((
+                    config.inconsistent_licenses,
+                    "inconsistent-license",
+                    "license differs between platforms or builds".to_string(),
+                ));
+            }
+        }
+
+        let suite_names: Vec<String> = if license_info.found_in.is_empty() {
+            vec!["unknown".to_string()]
+        } else {
+            license_info.found_in.iter().map(suite_name).collect()
+        };
+        for suite_name in suite_names {
+            let mut test_case = TestCase {
+                name: test_case_name(license_info),
+                outcome: outcome.clone(),
+                system_out: warning.iter().cloned().collect(),
+            };
+            for (action, kind, message) in &findings {
+                match action {
+                    ReasonAction::Fail if matches!(test_case.outcome, Outcome::Passed) => {
+                        test_case.outcome = Outcome::Failure {
+                            kind: kind.to_string(),
+                            message: message.clone(),
+                            details: message.clone(),
+                        };
+                    }
+                    ReasonAction::Ignore => {}
+                    _ => test_case.system_out.push(format!("warning: {message}")),
+                }
+            }
+            suites.entry(suite_name).or_default().push(test_case);
+        }
+    }
+
+    // Returns the number of tests, failures and skipped tests
+    let counts = |test_cases: &[&TestCase]| {
+        let failures = test_cases
+            .iter()
+            .filter(|test_case| matches!(test_case.outcome, Outcome::Failure { .. }))
+            .count();
+        let skipped = test_cases
+            .iter()
+            .filter(|test_case| matches!(test_case.outcome, Outcome::Skipped { .. }))
+            .count();
+        (test_cases.len(), failures, skipped)
+    };
+    let (tests, failures, skipped) = counts(&suites.values().flatten().collect::<Vec<_>>());
+
+    let mut output = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    output.push_str(&format!(
+        "<testsuites name=\"conda-deny\" tests=\"{tests}\" failures=\"{failures}\" skipped=\"{skipped}\">\n"
+    ));
+    for (name, test_cases) in &suites {
+        let (tests, failures, skipped) = counts(&test_cases.iter().collect::<Vec<_>>());
+        output.push_str(&format!(
+            "  <testsuite name=\"{}\" tests=\"{tests}\" failures=\"{failures}\" skipped=\"{skipped}\">\n",
+            escape(name)
+        ));
+        for test_case in test_cases {
+            output.push_str(&format!(
+                "    <testcase classname=\"{}\" name=\"{}\"",
+                escape(name),
+                escape(&test_case.name)
+            ));
+            if matches!(test_case.outcome, Outcome::Passed) && test_case.system_out.is_empty() {
+                output.push_str("/>\n");
+                continue;
+            }
+            output.push_str(">\n");
+            match &test_case.outcome {
+                Outcome::Passed => {}
+                Outcome::Failure {
+                    kind,
+                    message,
+                    details,
+                } => output.push_str(&format!(
+                    "      <failure type=\"{}\" message=\"{}\">{}</failure>\n",
+                    escape(kind),
+                    escape(message),
+                    escape(details)
+                )),
+                Outcome::Skipped { message } => output.push_str(&format!(
+                    "      <skipped message=\"{}\"/>\n",
+                    escape(message)
+                )),
+            }
+            if !test_case.system_out.is_empty() {
+                output.push_str(&format!(
+                    "      <system-out>{}</system-out>\n",
+                    escape(&test_case.system_out.join("\n"))
+                ));
+            }
+            output.push_str("    </testcase>\n");
+        }
+        output.push_str("  </testsuite>\n");
+    }
+    output.push_str("</testsuites>\n");
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_suite_name() {
+        let provenance = |lockfile: Option<&str>| Provenance {
+            lockfile: lockfile.map(str::to_string),
+            environment: "default".to_string(),
+            platform: Some("linux-64".to_string()),
+        };
+        assert_eq!(
+            suite_name(&provenance(Some("a/pixi.lock"))),
+            "a/pixi.lock:default:linux-64"
+        );
+        assert_eq!(suite_name(&provenance(None)), "default:linux-64");
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(
+            escape("GPL-2.0 <with \"exceptions\"> & more"),
+            "GPL-2.0 &lt;with &quot;exceptions&quot;&gt; &amp; more"
+        );
+    }
+}
@@ -1,37 +1,76 @@
+pub mod baseline;
 pub mod bundle;
 pub mod check;
 pub mod cli;
 pub mod conda_deny_config;
 mod conda_meta_package;
+mod conda_prefix;
+mod cyclonedx;
+mod dependency_graph;
+pub mod diff;
+pub mod explain;
+mod explanation;
 pub mod expression_utils;
+pub mod json_output;
+mod junit;
 pub mod license_allowlist;
+mod license_changes;
 mod license_info;
+pub mod licenses;
 pub mod list;
+mod markdown;
 mod pixi_lock;
+mod pixi_manifest;
+pub mod query;
+pub mod report;
+mod sarif;
+pub mod sbom;
+mod spdx_document;
+mod template;
+pub mod why;
 
-use std::{env, path::PathBuf};
+use std::{collections::HashMap, env, fmt, path::PathBuf};
 
 use cli::CondaDenyCliConfig;
 use conda_deny_config::CondaDenyTomlConfig;
-use license_allowlist::{get_license_information_from_toml_config, IgnorePackage};
+use license_allowlist::{
+    get_deny_licenses_from_toml_config, get_license_information_from_toml_config,
+    get_policies_from_toml_config, get_warn_licenses_from_toml_config, IgnorePackage,
+    LicenseSources,
+};
 use license_info::LicenseInfo;
 
 use anyhow::{Context, Result};
 use log::{debug, warn};
-use rattler_conda_types::Platform;
-use serde::Deserialize;
+use rattler_conda_types::{prefix_record::PrefixRecord, Platform};
+use serde::{Deserialize, Serialize};
 use spdx::Expression;
 
-use crate::{conda_deny_config::parse_paths_in_config, license_info::LicenseInfos};
+use crate::{
+    baseline::BaselineEntry,
+    conda_deny_config::parse_paths_in_config,
+    dependency_graph::{EnvironmentGraph, RuntimeClosure},
+    diff::LockfileSource,
+    license_changes::{LicenseChange, LicenseInconsistency},
+    license_info::{IgnoredPackages, LicenseInfos},
+    pixi_lock::{get_dependency_graphs_for_pixi_lock, get_runtime_closures_for_pixi_lock},
+    query::{Field, Filter, GroupBy},
+};
 
 #[derive(Debug)]
 pub enum CondaDenyConfig {
     Check(CondaDenyCheckConfig),
     List(CondaDenyListConfig),
+    Licenses(CondaDenyLicensesConfig),
     Bundle(CondaDenyBundleConfig),
+    Explain(CondaDenyExplainConfig),
+    Why(CondaDenyWhyConfig),
+    Diff(CondaDenyDiffConfig),
+    Report(CondaDenyReportConfig),
+    Sbom(CondaDenySbomConfig),
 }
 
-#[derive(Debug, Clone, clap::ValueEnum, Default, Deserialize, Copy)]
+#[derive(Debug, Clone, clap::ValueEnum, Default, Deserialize, Copy, PartialEq, Eq)]
 #[serde(rename_all = "kebab-case")]
 pub enum OutputFormat {
     #[default]
@@ -39,6 +78,201 @@ pub enum OutputFormat {
     Json,
     JsonPretty,
     Csv,
+    /// Markdown, e.g. for pull request comments
+    Markdown,
+    /// SARIF, e.g. for code scanning
+    Sarif,
+    /// JUnit XML, e.g. for CI test reports
+    Junit,
+    /// A user-defined Tera template, see `--template`
+    Template,
+}
+
+/// Format of the software bill of materials written by `sbom`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum SbomFormat {
+    /// CycloneDX 1.5 JSON
+    CyclonedxJson,
+    /// SPDX 2.3 JSON
+    SpdxJson,
+    /// SPDX 2.3 tag-value
+    SpdxTv,
+}
+
+/// Name of the policy formed by the top-level `safe-licenses`, `license-allowlist` and `osi` options
+pub const DEFAULT_POLICY_NAME: &str = "default";
+
+/// A named set of licenses that packages are checked against
+#[derive(Debug, Clone)]
+pub struct CondaDenyPolicy {
+    pub name: String,
+    pub osi: bool,
+    pub safe_licenses: Vec<Expression>,
+    /// Where each requirement of `safe_licenses` was configured
+    pub safe_license_sources: LicenseSources,
+    /// Licenses that are reported, but not considered unsafe
+    pub warn_licenses: Vec<Expression>,
+    /// Licenses that are never allowed, even if they are in the allowlist
+    pub deny_licenses: Vec<Expression>,
+    pub ignore_packages: Vec<IgnorePackage>,
+    /// Pixi environments that are checked with this policy
+    pub environments: Vec<String>,
+    /// Conda prefixes that are checked with this policy
+    pub prefixes: Vec<PathBuf>,
+}
+
+impl CondaDenyPolicy {
+    fn is_empty(&self) -> bool {
+        !self.osi && self.safe_licenses.is_empty()
+    }
+
+    fn applies_to(&self, environment: &str) -> bool {
+        self.environments.iter().any(|e| e == environment)
+            || self
+                .prefixes
+                .iter()
+                .any(|prefix| prefix_matches(prefix, environment))
+    }
+}
+
+fn prefix_matches(prefix: &std::path::Path, environment: &str) -> bool {
+    let environment = std::path::Path::new(environment);
+    if prefix == environment {
+        return true;
+    }
+    match (prefix.canonicalize(), environment.canonicalize()) {
+        (Ok(prefix), Ok(environment)) => prefix == environment,
+        _ => false,
+    }
+}
+
+/// Findings that make `conda-deny check` exit with a non-zero exit code, named after the
+/// [`UnsafeReason`]s
+#[derive(Debug, Clone, clap::ValueEnum, Deserialize, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum FailOn {
+    /// Valid licenses that are not allowed by the policy
+    Disallowed,
+    /// Licenses that can only be satisfied with a license from deny-licenses
+    Denied,
+    /// Valid licenses that are not allowed, both `disallowed` and `denied`
+    Unsafe,
+    /// Licenses that are not valid SPDX expressions
+    #[value(alias = "invalid")]
+    #[serde(alias = "invalid")]
+    NonSpdx,
+    /// Packages without license information
+    #[value(alias = "no-license")]
+    #[serde(alias = "no-license")]
+    Missing,
+    /// Licenses that are only allowed as warn-licenses, and other warnings
+    Warn,
+}
+
+impl FailOn {
+    /// Whether failing on `self` includes failing on `other`.
+    fn includes(self, other: FailOn) -> bool {
+        self == other
+            || (self == FailOn::Unsafe && matches!(other, FailOn::Disallowed | FailOn::Denied))
+    }
+}
+
+pub const FAIL_ON_DEFAULT: [FailOn; 4] = [
+    FailOn::Disallowed,
+    FailOn::Denied,
+    FailOn::NonSpdx,
+    FailOn::Missing,
+];
+
+/// Why a package was not considered safe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum UnsafeReason {
+    /// The license is valid, but not allowed by the policy
+    Disallowed,
+    /// The license can only be satisfied with a license from deny-licenses
+    Denied,
+    /// The license is not a valid SPDX expression
+    NonSpdx,
+    /// The package has no license information
+    Missing,
+}
+
+impl fmt::Display for UnsafeReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnsafeReason::Disallowed => write!(f, "disallowed"),
+            UnsafeReason::Denied => write!(f, "denied"),
+            UnsafeReason::NonSpdx => write!(f, "non-spdx"),
+            UnsafeReason::Missing => write!(f, "missing"),
+        }
+    }
+}
+
+/// What to do with a package that was not considered safe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ReasonAction {
+    #[default]
+    Fail,
+    Warn,
+    Ignore,
+}
+
+/// Action per [`UnsafeReason`], configured in `[tool.conda-deny.reason-actions]`
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct ReasonActions {
+    #[serde(default)]
+    pub disallowed: ReasonAction,
+    #[serde(default)]
+    pub denied: ReasonAction,
+    #[serde(default)]
+    pub non_spdx: ReasonAction,
+    #[serde(default)]
+    pub missing: ReasonAction,
+}
+
+impl ReasonActions {
+    pub fn action(&self, reason: UnsafeReason) -> ReasonAction {
+        match reason {
+            UnsafeReason::Disallowed => self.disallowed,
+            UnsafeReason::Denied => self.denied,
+            UnsafeReason::NonSpdx => self.non_spdx,
+            UnsafeReason::Missing => self.missing,
+        }
+    }
+
+    /// Applies `--fail-on`: reasons that would fail but are not listed are reported as warnings.
+    ///
+    /// Ignored reasons stay ignored, so `reason-actions` decides whether a package is reported
+    /// and `fail-on` only decides which of the failing reasons fail.
+    pub fn with_fail_on(self, fail_on: &[FailOn]) -> Self {
+        let action = |action: ReasonAction, fail_on_reason: FailOn| match action {
+            ReasonAction::Fail if !fail_on.iter().any(|f| f.includes(fail_on_reason)) => {
+                ReasonAction::Warn
+            }
+            action => action,
+        };
+        ReasonActions {
+            disallowed: action(self.disallowed, FailOn::Disallowed),
+            denied: action(self.denied, FailOn::Denied),
+            non_spdx: action(self.non_spdx, FailOn::NonSpdx),
+            missing: action(self.missing, FailOn::Missing),
+        }
+    }
+}
+
+/// Restricts the check to the packages needed at runtime, configured in
+/// `[tool.conda-deny.runtime-closure]`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct RuntimeClosureConfig {
+    /// Pixi feature (or environment) whose direct dependencies are the roots of the closure
+    pub of: String,
+    /// Check the closure against this policy and all other packages as usual,
+    /// instead of only checking the closure
+    pub policy: Option<String>,
 }
 
 /// Configuration for the check command
@@ -47,8 +281,154 @@ pub struct CondaDenyCheckConfig {
     pub lockfile_or_prefix: LockfileOrPrefix,
     pub osi: bool,
     pub safe_licenses: Vec<Expression>,
+    pub safe_license_sources: LicenseSources,
+    pub warn_licenses: Vec<Expression>,
+    pub deny_licenses: Vec<Expression>,
     pub ignore_packages: Vec<IgnorePackage>,
     pub output_format: OutputFormat,
+    /// Tera template to render the output with, when the output format is `template`
+    pub template: Option<PathBuf>,
+    /// Only report the packages matching this filter
+    pub filter: Option<Filter>,
+    /// Sort the packages by these fields
+    pub sort_by: Vec<Field>,
+    /// Merge entries with the same name, version and license across platforms
+    pub aggregate: bool,
+    /// Group the packages by where they were found, only used by the default output
+    pub group_by: Vec<GroupBy>,
+    /// Show why each license was considered safe or unsafe
+    pub explain: bool,
+    /// Annotate packages with the direct dependencies that pull them in
+    pub pulled_in_by: bool,
+    pub runtime_closure: Option<RuntimeClosureConfig>,
+    /// Only fail on unsafe dependencies that are not in this baseline file
+    pub baseline: Option<PathBuf>,
+    /// Record the unsafe dependencies in this baseline file instead of failing
+    pub write_baseline: Option<PathBuf>,
+    /// License snapshot written by `list --output json` to detect license changes against
+    pub license_history: Option<PathBuf>,
+    /// What to do with packages whose license changed
+    pub license_changes: ReasonAction,
+    /// What to do with packages whose license differs between platforms or builds
+    pub inconsistent_licenses: ReasonAction,
+    /// Whether warnings fail the check, set by `--fail-on warn`
+    pub fail_on_warn: bool,
+    /// Action per reason, with `--fail-on` already applied
+    pub reason_actions: ReasonActions,
+    /// Named policies from `[tool.conda-deny.policies]`
+    pub policies: Vec<CondaDenyPolicy>,
+    /// Check every package against this policy instead of the configured mapping
+    pub policy_override: Option<String>,
+}
+
+impl CondaDenyCheckConfig {
+    /// The policy formed by the top-level configuration options.
+    pub fn default_policy(&self) -> CondaDenyPolicy {
+        CondaDenyPolicy {
+            name: DEFAULT_POLICY_NAME.to_string(),
+            osi: self.osi,
+            safe_licenses: self.safe_licenses.clone(),
+            safe_license_sources: self.safe_license_sources.clone(),
+            warn_licenses: self.warn_licenses.clone(),
+            deny_licenses: self.deny_licenses.clone(),
+            ignore_packages: vec![],
+            environments: vec![],
+            prefixes: vec![],
+        }
+    }
+
+    /// Returns the policies that apply to a package found in the given environments.
+    pub fn resolve_policies<'a, 'e>(
+        &'a self,
+        default_policy: &'a CondaDenyPolicy,
+        environments: impl IntoIterator<Item = &'e String>,
+    ) -> Result<Vec<&'a CondaDenyPolicy>> {
+        if let Some(policy_name) = &self.policy_override {
+            return Ok(vec![self.policy_by_name(default_policy, policy_name)?]);
+        }
+
+        let mut resolved: Vec<&CondaDenyPolicy> = Vec::new();
+        let mut unmapped = Vec::new();
+        for environment in environments {
+            match self.policies.iter().find(|p| p.applies_to(environment)) {
+                Some(policy) => resolved.push(policy),
+                None => unmapped.push(environment.as_str()),
+            }
+        }
+        if !unmapped.is_empty() || resolved.is_empty() {
+            if default_policy.is_empty() {
+                return Err(anyhow::anyhow!(
+                    "No license allowlist provided for environment(s) {unmapped:?}. \
+                     Map them to a policy or configure a top-level license allowlist."
+                ));
+            }
+            resolved.push(default_policy);
+        }
+        resolved.sort_by(|a, b| a.name.cmp(&b.name));
+        resolved.dedup_by(|a, b| a.name == b.name);
+        Ok(resolved)
+    }
+
+    pub fn policy_by_name<'a>(
+        &'a self,
+        default_policy: &'a CondaDenyPolicy,
+        name: &str,
+    ) -> Result<&'a CondaDenyPolicy> {
+        if name == DEFAULT_POLICY_NAME {
+            return Ok(default_policy);
+        }
+        self.policies
+            .iter()
+            .find(|p| p.name == name)
+            .ok_or_else(|| anyhow::anyhow!("Unknown policy: {name}"))
+    }
+}
+
+#[cfg(test)]
+impl Default for CondaDenyCheckConfig {
+    /// An empty configuration with the defaults of `get_config_options`, for tests.
+    fn default() -> Self {
+        CondaDenyCheckConfig {
+            lockfile_or_prefix: LockfileOrPrefix::Prefix(vec![]),
+            osi: false,
+            safe_licenses: vec![],
+            safe_license_sources: LicenseSources::new(),
+            warn_licenses: vec![],
+            deny_licenses: vec![],
+            ignore_packages: vec![],
+            output_format: OutputFormat::Default,
+            template: None,
+            filter: None,
+            sort_by: vec![],
+            aggregate: false,
+            group_by: vec![],
+            explain: false,
+            pulled_in_by: false,
+            runtime_closure: None,
+            baseline: None,
+            write_baseline: None,
+            license_history: None,
+            license_changes: ReasonAction::Warn,
+            inconsistent_licenses: ReasonAction::Warn,
+            fail_on_warn: false,
+            reason_actions: ReasonActions::default(),
+            policies: vec![],
+            policy_override: None,
+        }
+    }
+}
+
+/// Configuration for the sbom command
+#[derive(Debug)]
+pub struct CondaDenySbomConfig {
+    pub lockfile_or_prefix: LockfileOrPrefix,
+    /// Only used to ignore pypi packages, ignored conda packages are still part of the SBOM
+    pub ignore_packages: Vec<IgnorePackage>,
+    pub format: SbomFormat,
+    /// Where to write the SBOM, stdout if not set
+    pub output: Option<PathBuf>,
+    /// Directory with the license texts collected by `bundle`
+    pub bundle_directory: Option<PathBuf>,
 }
 
 /// Shared configuration between check, list, and bundle commands
@@ -57,13 +437,75 @@ pub struct CondaDenyListConfig {
     pub lockfile_or_prefix: LockfileOrPrefix,
     pub ignore_packages: Vec<IgnorePackage>,
     pub output_format: OutputFormat,
+    /// Tera template to render the output with, when the output format is `template`
+    pub template: Option<PathBuf>,
+    /// Only list the packages matching this filter
+    pub filter: Option<Filter>,
+    /// Sort the packages by these fields
+    pub sort_by: Vec<Field>,
+    /// Only show these fields
+    pub columns: Option<Vec<Field>>,
+    /// Merge entries with the same name, version and license across platforms
+    pub aggregate: bool,
+    /// Group the packages by where they were found, only used by the default output
+    pub group_by: Vec<GroupBy>,
+}
+
+/// Configuration for the licenses command
+#[derive(Debug)]
+pub struct CondaDenyLicensesConfig {
+    /// The check configuration that gives the verdict per license
+    pub check_config: CondaDenyCheckConfig,
+    /// Group by the individual SPDX license ids instead of the whole license expressions
+    pub by_id: bool,
+}
+
+/// Configuration for the explain command
+#[derive(Debug)]
+pub struct CondaDenyExplainConfig {
+    pub package_name: String,
+    /// Only explain this version of the package
+    pub version: Option<String>,
+    /// The check configuration whose verdict is explained
+    pub check_config: CondaDenyCheckConfig,
+}
+
+/// Configuration for the why command
+#[derive(Debug)]
+pub struct CondaDenyWhyConfig {
+    pub lockfile_or_prefix: LockfileOrPrefix,
+    pub package_name: String,
+    pub output_format: OutputFormat,
+}
+
+/// Configuration for the diff command
+#[derive(Debug)]
+pub struct CondaDenyDiffConfig {
+    pub old: LockfileSource,
+    pub new: LockfileSource,
+    /// The policy configuration the changed packages are checked against,
+    /// its lockfile spec only selects environments and platforms
+    pub check_config: CondaDenyCheckConfig,
+}
+
+/// Configuration for the report command
+#[derive(Debug)]
+pub struct CondaDenyReportConfig {
+    pub check_config: CondaDenyCheckConfig,
+    pub html: PathBuf,
+    /// Directory with the license texts collected by `bundle`
+    pub bundle_directory: Option<PathBuf>,
 }
 
 /// Shared configuration between check, list, and bundle commands
 #[derive(Debug, Clone)]
 pub struct CondaDenyBundleConfig {
     pub lockfile_or_prefix: LockfileOrPrefix,
+    /// Only used to ignore pypi packages, the licenses of ignored conda packages are still bundled
+    pub ignore_packages: Vec<IgnorePackage>,
     pub directory: Option<PathBuf>,
+    /// Also write all license texts to this file, in Markdown if it ends with `.md`
+    pub notice_file: Option<PathBuf>,
 }
 
 #[derive(Debug, Clone)]
@@ -80,24 +522,113 @@ pub enum LockfileOrPrefix {
     Prefix(Vec<PathBuf>),
 }
 
-pub type CheckOutput = (Vec<LicenseInfo>, Vec<LicenseInfo>);
+#[derive(Debug, Clone, Default)]
+pub struct CheckOutput {
+    pub safe_dependencies: Vec<LicenseInfo>,
+    /// Dependencies that are only allowed because of warn-licenses or whose reason is configured to warn
+    pub warn_dependencies: Vec<LicenseInfo>,
+    pub unsafe_dependencies: Vec<LicenseInfo>,
+    /// Dependencies matched by ignore-packages or whose reason is configured to be ignored
+    pub ignored_dependencies: Vec<LicenseInfo>,
+    /// Dependencies outside the runtime closure, which were not checked
+    pub outside_closure_dependencies: Vec<LicenseInfo>,
+    /// Unsafe dependencies that are known from the baseline
+    pub baselined_dependencies: Vec<LicenseInfo>,
+    /// Baseline entries that no longer match an unsafe dependency
+    pub fixed_baseline_entries: Vec<BaselineEntry>,
+    /// Packages whose license changed compared to the old lockfile, baseline or license history
+    pub license_changes: Vec<LicenseChange>,
+    /// Packages whose license differs between platforms or builds of the same version
+    pub inconsistent_licenses: Vec<LicenseInconsistency>,
+}
+
+impl CheckOutput {
+    /// All dependencies that were checked against a policy.
+    pub fn checked_dependencies(&self) -> impl Iterator<Item = &LicenseInfo> {
+        self.safe_dependencies
+            .iter()
+            .chain(&self.warn_dependencies)
+            .chain(&self.unsafe_dependencies)
+            .chain(&self.ignored_dependencies)
+            .chain(&self.baselined_dependencies)
+    }
+}
 
 pub fn collect_license_infos(
     lockfile_or_prefix: LockfileOrPrefix,
     ignore_packages: &[IgnorePackage],
+    ignored_packages: IgnoredPackages,
 ) -> Result<LicenseInfos> {
     match lockfile_or_prefix {
         LockfileOrPrefix::Lockfile(lockfile_spec) => {
-            LicenseInfos::from_pixi_lockfiles(lockfile_spec, ignore_packages)
+            LicenseInfos::from_pixi_lockfiles(lockfile_spec, ignore_packages, ignored_packages)
                 .with_context(|| "Getting license information from config file failed.")
         }
         LockfileOrPrefix::Prefix(prefixes) => {
-            LicenseInfos::from_conda_prefixes(&prefixes, ignore_packages)
+            LicenseInfos::from_conda_prefixes(&prefixes, ignore_packages, ignored_packages)
                 .with_context(|| "Getting license information from conda prefixes failed.")
         }
     }
 }
 
+/// Builds the dependency graphs of all environments (or conda prefixes) that are checked.
+pub fn collect_dependency_graphs(
+    lockfile_or_prefix: &LockfileOrPrefix,
+) -> Result<Vec<EnvironmentGraph>> {
+    match lockfile_or_prefix {
+        LockfileOrPrefix::Lockfile(lockfile_spec) => {
+            let mut graphs = Vec::new();
+            for lockfile in &lockfile_spec.lockfiles {
+                graphs.extend(get_dependency_graphs_for_pixi_lock(
+                    lockfile,
+                    &lockfile_spec.environments,
+                    &lockfile_spec.platforms,
+                )?);
+            }
+            Ok(graphs)
+        }
+        LockfileOrPrefix::Prefix(prefixes) => prefixes
+            .iter()
+            .map(|prefix| {
+                let records: Vec<PrefixRecord> = PrefixRecord::collect_from_prefix(prefix)
+                    .with_context(|| format!("Failed to collect from: {prefix:?}"))?;
+                let graph = conda_prefix::dependency_graph(prefix, &records)?;
+                Ok(EnvironmentGraph {
+                    lockfile: None,
+                    environment: prefix.display().to_string(),
+                    platform: None,
+                    graph,
+                })
+            })
+            .collect(),
+    }
+}
+
+/// Computes the runtime closure of the given pixi feature (or environment) for every
+/// selected environment and platform.
+pub fn collect_runtime_closures(
+    lockfile_or_prefix: &LockfileOrPrefix,
+    of: &str,
+) -> Result<Vec<RuntimeClosure>> {
+    match lockfile_or_prefix {
+        LockfileOrPrefix::Lockfile(lockfile_spec) => {
+            let mut closures = Vec::new();
+            for lockfile in &lockfile_spec.lockfiles {
+                closures.extend(get_runtime_closures_for_pixi_lock(
+                    lockfile,
+                    &lockfile_spec.environments,
+                    &lockfile_spec.platforms,
+                    of,
+                )?);
+            }
+            Ok(closures)
+        }
+        LockfileOrPrefix::Prefix(_) => Err(anyhow::anyhow!(
+            "Runtime closures can only be computed for pixi lockfiles"
+        )),
+    }
+}
+
 const IGNORE_PYPI_DEFAULT: bool = false;
 
 fn get_lockfile_or_prefix(
@@ -136,6 +667,20 @@ fn get_lockfile_or_prefix(
     if lockfiles.is_empty() {
         warn!("Your lockfile glob patterns did not match any files. This will do nothing.");
     }
+    Ok(LockfileOrPrefix::Lockfile(get_lockfile_selection(
+        cli_config,
+        toml_config,
+        lockfiles,
+    )))
+}
+
+/// Builds the lockfile spec for the given lockfiles, selecting platforms and environments
+/// from the CLI or, as a fallback, the toml config.
+fn get_lockfile_selection(
+    cli_config: &CondaDenyCliConfig,
+    toml_config: &CondaDenyTomlConfig,
+    lockfiles: Vec<PathBuf>,
+) -> LockfileSpec {
     let platforms = cli_config.platform().or(toml_config.get_platform_spec());
     let environments = cli_config
         .environment()
@@ -144,12 +689,128 @@ fn get_lockfile_or_prefix(
         .ignore_pypi()
         .or(toml_config.get_ignore_pypi())
         .unwrap_or(IGNORE_PYPI_DEFAULT);
-    Ok(LockfileOrPrefix::Lockfile(LockfileSpec {
+    LockfileSpec {
         lockfiles,
         platforms,
         environments,
         ignore_pypi,
-    }))
+    }
+}
+
+fn validate_policy_mapping(policies: &[CondaDenyPolicy]) -> Result<()> {
+    let mut mapped: HashMap<String, &str> = HashMap::new();
+    for policy in policies {
+        let targets = policy.environments.iter().cloned().chain(
+            policy
+                .prefixes
+                .iter()
+                .map(|prefix| prefix.display().to_string()),
+        );
+        for target in targets {
+            if let Some(other) = mapped.insert(target.clone(), &policy.name) {
+                return Err(anyhow::anyhow!(
+                    "'{target}' is mapped to both policy '{other}' and policy '{}'",
+                    policy.name
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn get_check_config(
+    toml_config: &CondaDenyTomlConfig,
+    lockfile_or_prefix: LockfileOrPrefix,
+    output_format: OutputFormat,
+    osi: Option<bool>,
+    policy: Option<String>,
+    fail_on: Option<Vec<FailOn>>,
+    runtime_closure_of: Option<String>,
+) -> Result<CondaDenyCheckConfig> {
+    let osi = osi.or(toml_config.get_osi()).unwrap_or(false);
+
+    let (safe_licenses, ignore_packages, safe_license_sources) =
+        get_license_information_from_toml_config(toml_config)?;
+    if osi && !safe_licenses.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Cannot use OSI mode and safe-licenses at the same time"
+        ));
+    }
+
+    let warn_licenses = get_warn_licenses_from_toml_config(toml_config)?;
+    let deny_licenses = get_deny_licenses_from_toml_config(toml_config)?;
+    let fail_on = fail_on
+        .or(toml_config.get_fail_on())
+        .unwrap_or(FAIL_ON_DEFAULT.to_vec());
+    let reason_actions = toml_config.get_reason_actions().with_fail_on(&fail_on);
+
+    let policies = get_policies_from_toml_config(toml_config)?;
+    validate_policy_mapping(&policies)?;
+
+    let has_default_policy = osi || !safe_licenses.is_empty();
+    if !has_default_policy && policies.is_empty() {
+        return Err(anyhow::anyhow!("No license allowlist provided"));
+    }
+
+    let runtime_closure = match (runtime_closure_of, toml_config.get_runtime_closure()) {
+        (Some(of), Some(runtime_closure)) => Some(RuntimeClosureConfig {
+            of,
+            ..runtime_closure
+        }),
+        (Some(of), None) => Some(RuntimeClosureConfig { of, policy: None }),
+        (None, runtime_closure) => runtime_closure,
+    };
+
+    let selected_policies = policy.iter().chain(
+        runtime_closure
+            .as_ref()
+            .and_then(|runtime_closure| runtime_closure.policy.as_ref()),
+    );
+    for policy in selected_policies {
+        let known = if policy == DEFAULT_POLICY_NAME {
+            has_default_policy
+        } else {
+            policies.iter().any(|p| &p.name == policy)
+        };
+        if !known {
+            return Err(anyhow::anyhow!(
+                "Policy '{policy}' is not configured. Available policies: {:?}",
+                has_default_policy
+                    .then_some(DEFAULT_POLICY_NAME)
+                    .into_iter()
+                    .chain(policies.iter().map(|p| p.name.as_str()))
+                    .collect::<Vec<_>>()
+            ));
+        }
+    }
+
+    Ok(CondaDenyCheckConfig {
+        lockfile_or_prefix,
+        osi,
+        safe_licenses,
+        safe_license_sources,
+        warn_licenses,
+        deny_licenses,
+        ignore_packages,
+        output_format,
+        template: None,
+        filter: None,
+        sort_by: vec![],
+        aggregate: false,
+        group_by: vec![],
+        explain: false,
+        pulled_in_by: false,
+        runtime_closure,
+        baseline: toml_config.get_baseline(),
+        write_baseline: None,
+        license_history: toml_config.get_license_history(),
+        license_changes: toml_config.get_license_changes(),
+        inconsistent_licenses: toml_config.get_inconsistent_licenses(),
+        fail_on_warn: fail_on.contains(&FailOn::Warn),
+        reason_actions,
+        policies,
+        policy_override: policy,
+    })
 }
 
 pub fn get_config_options(
@@ -191,49 +852,254 @@ pub fn get_config_options(
 
     debug!("Parsed TOML config: {toml_config:?}");
 
-    let output_format = cli_config.output().unwrap_or_default();
-    let lockfile_or_prefix = get_lockfile_or_prefix(&cli_config, &toml_config)?;
+    let template = cli_config.template();
+    let output_format = match (cli_config.output(), &template) {
+        (None, Some(_)) => OutputFormat::Template,
+        (output_format, _) => output_format.unwrap_or_default(),
+    };
+    if !cli_config
+        .supported_output_formats()
+        .contains(&output_format)
+    {
+        return Err(anyhow::anyhow!(
+            "--output {} is not supported by {}",
+            clap::ValueEnum::to_possible_value(&output_format)
+                .map(|value| value.get_name().to_string())
+                .unwrap_or_default(),
+            cli_config.name()
+        ));
+    }
+    if template.is_some() && !matches!(output_format, OutputFormat::Template) {
+        return Err(anyhow::anyhow!(
+            "--template can only be used with --output template"
+        ));
+    }
+    let filter = cli_config.filter().and_then(Filter::all);
+    let sort_by = cli_config.sort_by().unwrap_or_default();
+    let columns = cli_config.columns();
+    // Aggregation is the default for the formats that are read by humans
+    let aggregate = cli_config.aggregate().unwrap_or(matches!(
+        output_format,
+        OutputFormat::Default | OutputFormat::Markdown
+    ));
+    if aggregate && matches!(output_format, OutputFormat::Sarif | OutputFormat::Junit) {
+        return Err(anyhow::anyhow!(
+            "--aggregate cannot be used with SARIF or JUnit output, which report each platform"
+        ));
+    }
+    let group_by = cli_config.group_by().unwrap_or_default();
+    if !group_by.is_empty() && !matches!(output_format, OutputFormat::Default) {
+        return Err(anyhow::anyhow!(
+            "--group-by is only supported by the default output"
+        ));
+    }
+    let lockfile_or_prefix = match &cli_config {
+        // The lockfiles of diff are passed as arguments
+        CondaDenyCliConfig::Diff { .. } => {
+            LockfileOrPrefix::Lockfile(get_lockfile_selection(&cli_config, &toml_config, vec![]))
+        }
+        _ => get_lockfile_or_prefix(&cli_config, &toml_config)?,
+    };
 
     let config = match cli_config {
-        CondaDenyCliConfig::Check { osi, .. } => {
-            let osi = osi.or(toml_config.get_osi()).unwrap_or(false);
-
-            let (safe_licenses, ignore_packages) =
-                get_license_information_from_toml_config(&toml_config)?;
-            if osi && !safe_licenses.is_empty() {
+        CondaDenyCliConfig::Check {
+            osi,
+            policy,
+            fail_on,
+            pulled_in_by,
+            runtime_closure,
+            baseline,
+            write_baseline,
+            license_history,
+            ..
+        } => {
+            let mut check_config = get_check_config(
+                &toml_config,
+                lockfile_or_prefix,
+                output_format,
+                osi,
+                policy,
+                fail_on,
+                runtime_closure,
+            )?;
+            check_config.pulled_in_by = pulled_in_by;
+            check_config.baseline = baseline.or(check_config.baseline);
+            check_config.write_baseline = write_baseline;
+            check_config.license_history = license_history.or(check_config.license_history);
+            check_config.template = template;
+            check_config.filter = filter;
+            check_config.sort_by = sort_by;
+            check_config.aggregate = aggregate;
+            check_config.group_by = group_by;
+            CondaDenyConfig::Check(check_config)
+        }
+        CondaDenyCliConfig::List { .. } => {
+            let uses_verdict = filter
+                .as_ref()
+                .is_some_and(|filter| filter.uses(Field::Verdict))
+                || sort_by.contains(&Field::Verdict)
+                || columns
+                    .as_ref()
+                    .is_some_and(|columns| columns.contains(&Field::Verdict));
+            if uses_verdict {
                 return Err(anyhow::anyhow!(
-                    "Cannot use OSI mode and safe-licenses at the same time"
+                    "The verdict field is only available with check"
                 ));
             }
-
-            if !osi && safe_licenses.is_empty() {
-                return Err(anyhow::anyhow!("No license allowlist provided"));
+            if columns.is_some() && matches!(output_format, OutputFormat::Template) {
+                return Err(anyhow::anyhow!(
+                    "--columns cannot be used with --output template"
+                ));
             }
 
-            CondaDenyConfig::Check(CondaDenyCheckConfig {
+            let (_, ignore_packages, _) = get_license_information_from_toml_config(&toml_config)?;
+            CondaDenyConfig::List(CondaDenyListConfig {
                 lockfile_or_prefix,
-                osi,
-                safe_licenses,
                 ignore_packages,
                 output_format,
+                template,
+                filter,
+                sort_by,
+                columns,
+                aggregate,
+                group_by,
             })
         }
-        CondaDenyCliConfig::List { .. } => {
-            let (_, ignore_packages) = get_license_information_from_toml_config(&toml_config)?;
-            CondaDenyConfig::List(CondaDenyListConfig {
+        CondaDenyCliConfig::Licenses {
+            osi, policy, by_id, ..
+        } => {
+            let mut check_config = get_check_config(
+                &toml_config,
                 lockfile_or_prefix,
-                ignore_packages,
                 output_format,
+                osi,
+                policy,
+                None,
+                None,
+            )?;
+            check_config.template = template;
+            CondaDenyConfig::Licenses(CondaDenyLicensesConfig {
+                check_config,
+                by_id,
             })
         }
-        CondaDenyCliConfig::Bundle { directory, .. } => {
-            CondaDenyConfig::Bundle(CondaDenyBundleConfig {
-                lockfile_or_prefix,
-                directory,
+        CondaDenyCliConfig::Bundle {
+            directory,
+            notice_file,
+            ..
+        } => CondaDenyConfig::Bundle(CondaDenyBundleConfig {
+            lockfile_or_prefix,
+            ignore_packages: get_license_information_from_toml_config(&toml_config)?.1,
+            directory,
+            notice_file,
+        }),
+        CondaDenyCliConfig::Explain {
+            package,
+            osi,
+            policy,
+            ..
+        } => {
+            let (package_name, version) = match package.split_once("==") {
+                Some((package_name, version)) => (package_name.trim(), Some(version.trim())),
+                None => (package.trim(), None),
+            };
+            CondaDenyConfig::Explain(CondaDenyExplainConfig {
+                package_name: package_name.to_string(),
+                version: version.map(str::to_string),
+                check_config: get_check_config(
+                    &toml_config,
+                    lockfile_or_prefix,
+                    output_format,
+                    osi,
+                    policy,
+                    None,
+                    None,
+                )?,
             })
         }
+        CondaDenyCliConfig::Why { package, .. } => CondaDenyConfig::Why(CondaDenyWhyConfig {
+            lockfile_or_prefix,
+            package_name: package,
+            output_format,
+        }),
+        CondaDenyCliConfig::Diff {
+            old,
+            new,
+            osi,
+            policy,
+            fail_on,
+            ..
+        } => CondaDenyConfig::Diff(CondaDenyDiffConfig {
+            old: old.parse()?,
+            new: new.parse()?,
+            check_config: get_check_config(
+                &toml_config,
+                lockfile_or_prefix,
+                output_format,
+                osi,
+                policy,
+                fail_on,
+                None,
+            )?,
+        }),
+        CondaDenyCliConfig::Report {
+            osi,
+            policy,
+            html,
+            bundle_directory,
+            ..
+        } => CondaDenyConfig::Report(CondaDenyReportConfig {
+            check_config: get_check_config(
+                &toml_config,
+                lockfile_or_prefix,
+                output_format,
+                osi,
+                policy,
+                None,
+                None,
+            )?,
+            html,
+            bundle_directory,
+        }),
+        CondaDenyCliConfig::Sbom {
+            format,
+            output,
+            bundle_directory,
+            ..
+        } => CondaDenyConfig::Sbom(CondaDenySbomConfig {
+            lockfile_or_prefix,
+            ignore_packages: get_license_information_from_toml_config(&toml_config)?.1,
+            format,
+            output,
+            bundle_directory,
+        }),
         CondaDenyCliConfig::Completion { .. } => unreachable!(),
     };
 
     Ok(config)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_with_fail_on() {
+        let reason_actions = ReasonActions {
+            missing: ReasonAction::Ignore,
+            ..ReasonActions::default()
+        };
+
+        let unsafe_only = reason_actions.with_fail_on(&[FailOn::Unsafe]);
+        assert_eq!(unsafe_only.disallowed, ReasonAction::Fail);
+        assert_eq!(unsafe_only.denied, ReasonAction::Fail);
+        assert_eq!(unsafe_only.non_spdx, ReasonAction::Warn);
+        // Ignored reasons stay ignored
+        assert_eq!(unsafe_only.missing, ReasonAction::Ignore);
+
+        let denied_only = reason_actions.with_fail_on(&[FailOn::Denied, FailOn::NonSpdx]);
+        assert_eq!(denied_only.disallowed, ReasonAction::Warn);
+        assert_eq!(denied_only.denied, ReasonAction::Fail);
+        assert_eq!(denied_only.non_spdx, ReasonAction::Fail);
+    }
+}
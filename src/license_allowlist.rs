@@ -1,14 +1,68 @@
-use std::{env, fs, str::FromStr};
+use std::{collections::BTreeMap, env, fmt, fs, str::FromStr};
 
 use anyhow::{Context, Result};
 use async_trait::async_trait;
 use log::{debug, info};
 use rattler_conda_types::{ParseStrictness, Version, VersionSpec};
 use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use spdx::Expression;
 
-use crate::{conda_deny_config::CondaDenyTomlConfig, expression_utils::parse_expression};
+use crate::{
+    conda_deny_config::CondaDenyTomlConfig,
+    expression_utils::{extract_license_texts, parse_expression},
+    CondaDenyPolicy, DEFAULT_POLICY_NAME,
+};
+
+/// Where a license requirement was allowed, warned about or denied
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case", tag = "type", content = "location")]
+pub enum LicenseSource {
+    /// `safe-licenses` in the conda-deny configuration
+    SafeLicenses,
+    /// A `license-allowlist` path or URL
+    LicenseAllowlist(String),
+    /// The OSI approved licenses
+    Osi,
+    /// `warn-licenses` in the conda-deny configuration
+    WarnLicenses,
+    /// `deny-licenses` in the conda-deny configuration
+    DenyLicenses,
+}
+
+/// A configured license expression together with where it was configured
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AllowlistEntry {
+    pub entry: String,
+    pub source: LicenseSource,
+}
+
+/// Maps license requirements to the first entry that contains them
+pub type LicenseSources = BTreeMap<String, AllowlistEntry>;
+
+fn add_license_sources(
+    sources: &mut LicenseSources,
+    licenses: &[Expression],
+    source: &LicenseSource,
+) {
+    for license in licenses {
+        for license_text in extract_license_texts(license) {
+            sources
+                .entry(license_text)
+                .or_insert_with(|| AllowlistEntry {
+                    entry: license.to_string(),
+                    source: source.clone(),
+                });
+        }
+    }
+}
+
+/// Maps the requirements of the given licenses to their entries, all with the same source.
+pub fn license_sources(licenses: &[Expression], source: LicenseSource) -> LicenseSources {
+    let mut sources = LicenseSources::new();
+    add_license_sources(&mut sources, licenses, &source);
+    sources
+}
 
 #[derive(Debug, Deserialize)]
 pub struct LicenseAllowlistConfig {
@@ -21,12 +75,66 @@ struct RemoteAllowlistTool {
     conda_deny: LicenseAllowlist,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 pub struct IgnorePackage {
     package: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
     version: Option<String>,
 }
 
+impl IgnorePackage {
+    pub fn new(package: String, version: Option<String>) -> Self {
+        IgnorePackage { package, version }
+    }
+
+    /// Returns true if this rule ignores the package.
+    ///
+    /// Packages without a version are only matched by rules without a version requirement.
+    pub fn matches(&self, package_name: &str, package_version: Option<&str>) -> Result<bool> {
+        if self.package != package_name {
+            return Ok(false);
+        }
+        match (&self.version, package_version) {
+            (None, _) => Ok(true),
+            (Some(_), None) => Ok(false),
+            (Some(version_req_str), Some(package_version)) => {
+                let parsed_package_version =
+                    Version::from_str(package_version).with_context(|| {
+                        format!(
+                            "Error parsing package version: {package_version} for package: {package_name}"
+                        )
+                    })?;
+                let version_req = VersionSpec::from_str(version_req_str, ParseStrictness::Strict)
+                    .with_context(|| {
+                        format!(
+                            "Error parsing version requirement: {version_req_str} for package: {package_name}"
+                        )
+                    })?;
+
+                Ok(version_req.matches(&parsed_package_version))
+            }
+        }
+    }
+}
+
+impl fmt::Display for IgnorePackage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.version {
+            Some(version) => write!(f, "{} {version}", self.package),
+            None => write!(f, "{}", self.package),
+        }
+    }
+}
+
+/// Formats ignore-packages rules as a comma separated list.
+pub fn join_ignore_rules(ignore_packages: &[IgnorePackage]) -> String {
+    ignore_packages
+        .iter()
+        .map(|ignore_package| ignore_package.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
 #[derive(Debug, Deserialize)]
 struct LicenseAllowlist {
     #[serde(rename = "safe-licenses")]
@@ -41,31 +149,8 @@ pub fn is_package_ignored(
     package_version: &str,
 ) -> Result<bool> {
     for ignore_package in ignore_packages {
-        if ignore_package.package == package_name {
-            match &ignore_package.version {
-                Some(version_req_str) => {
-                    let parsed_package_version =
-                        Version::from_str(package_version).with_context(|| {
-                            format!(
-                                "Error parsing package version: {package_version} for package: {package_name}"
-                            )
-                        })?;
-                    let version_req =
-                        VersionSpec::from_str(version_req_str, ParseStrictness::Strict)
-                            .with_context(|| {
-                                format!(
-                                    "Error parsing version requirement: {version_req_str} for package: {package_name}"
-                                )
-                            })?;
-
-                    if version_req.matches(&parsed_package_version) {
-                        return Ok(true);
-                    }
-                }
-                None => {
-                    return Ok(true);
-                }
-            }
+        if ignore_package.matches(package_name, Some(package_version))? {
+            return Ok(true);
         }
     }
 
@@ -188,47 +273,48 @@ pub fn fetch_safe_licenses(
 pub fn build_license_allowlist(
     license_allowlist: &[String],
 ) -> Result<(Vec<Expression>, Vec<IgnorePackage>)> {
+    let (safe_licenses, ignore_packages, _) =
+        build_license_allowlist_with_sources(license_allowlist)?;
+    Ok((safe_licenses, ignore_packages))
+}
+
+fn build_license_allowlist_with_sources(
+    license_allowlist: &[String],
+) -> Result<(Vec<Expression>, Vec<IgnorePackage>, LicenseSources)> {
     let mut all_safe_licenses = Vec::new();
     let mut all_ignore_packages = Vec::new();
+    let mut license_sources = LicenseSources::new();
 
     for license_allowlist_path in license_allowlist.iter() {
         // todo: use Url (or Path)
-        if license_allowlist_path.starts_with("http") {
+        let (safe_licenses, ignore_packages) = if license_allowlist_path.starts_with("http") {
             let reader = RealRemoteConfigReader;
 
-            match fetch_safe_licenses(license_allowlist_path, &reader) {
-                Ok((safe_licenses, ignore_packages)) => {
-                    all_safe_licenses.extend(safe_licenses);
-                    all_ignore_packages.extend(ignore_packages);
-                }
-                Err(e) => {
-                    return Err(e).with_context(|| {
-                        format!("Failed to fetch safe licenses from URL: {license_allowlist_path}")
-                    });
-                }
-            }
+            fetch_safe_licenses(license_allowlist_path, &reader).with_context(|| {
+                format!("Failed to fetch safe licenses from URL: {license_allowlist_path}")
+            })?
         } else {
-            match license_config_from_toml_str(license_allowlist_path) {
-                Ok((safe_licenses, ignore_packages)) => {
-                    all_safe_licenses.extend(safe_licenses);
-                    all_ignore_packages.extend(ignore_packages);
-                }
-                Err(e) => {
-                    return Err(e).with_context(|| {
-                        format!("Failed to parse TOML file at path: {license_allowlist_path}")
-                    });
-                }
-            }
-        }
+            license_config_from_toml_str(license_allowlist_path).with_context(|| {
+                format!("Failed to parse TOML file at path: {license_allowlist_path}")
+            })?
+        };
+
+        add_license_sources(
+            &mut license_sources,
+            &safe_licenses,
+            &LicenseSource::LicenseAllowlist(license_allowlist_path.clone()),
+        );
+        all_safe_licenses.extend(safe_licenses);
+        all_ignore_packages.extend(ignore_packages);
     }
 
     debug!("License allowlist built successfully.");
-    Ok((all_safe_licenses, all_ignore_packages))
+    Ok((all_safe_licenses, all_ignore_packages, license_sources))
 }
 
 pub fn get_license_information_from_toml_config(
     toml_config: &CondaDenyTomlConfig,
-) -> Result<(Vec<Expression>, Vec<IgnorePackage>)> {
+) -> Result<(Vec<Expression>, Vec<IgnorePackage>, LicenseSources)> {
     let safe_licenses_from_toml = toml_config
         .tool
         .conda_deny
@@ -243,13 +329,127 @@ pub fn get_license_information_from_toml_config(
         .unwrap_or_default();
 
     let license_allowlist_urls = toml_config.get_license_allowlists()?;
-    let (safe_licenses, ignore_packages) = build_license_allowlist(&license_allowlist_urls)?;
+    combine_license_information(
+        &safe_licenses_from_toml,
+        &ignore_packages_from_toml,
+        &license_allowlist_urls,
+    )
+}
 
-    // TODO: Remove duplicates
-    let safe_licenses = safe_licenses_from_toml
+pub fn get_warn_licenses_from_toml_config(
+    toml_config: &CondaDenyTomlConfig,
+) -> Result<Vec<Expression>> {
+    parse_license_expressions(
+        &toml_config
+            .tool
+            .conda_deny
+            .warn_licenses
+            .clone()
+            .unwrap_or_default(),
+    )
+}
+
+pub fn get_deny_licenses_from_toml_config(
+    toml_config: &CondaDenyTomlConfig,
+) -> Result<Vec<Expression>> {
+    parse_license_expressions(
+        &toml_config
+            .tool
+            .conda_deny
+            .deny_licenses
+            .clone()
+            .unwrap_or_default(),
+    )
+}
+
+fn parse_license_expressions(licenses: &[String]) -> Result<Vec<Expression>> {
+    licenses
+        .iter()
+        .map(|license| {
+            parse_expression(license)
+                .with_context(|| format!("Failed to parse license expression: {license}"))
+        })
+        .collect()
+}
+
+pub fn get_policies_from_toml_config(
+    toml_config: &CondaDenyTomlConfig,
+) -> Result<Vec<CondaDenyPolicy>> {
+    let mut policies = Vec::new();
+
+    for (name, policy_config) in toml_config.get_policies() {
+        if name == DEFAULT_POLICY_NAME {
+            return Err(anyhow::anyhow!(
+                "The policy name '{DEFAULT_POLICY_NAME}' is reserved for the top-level configuration"
+            ));
+        }
+
+        let license_allowlist_urls = policy_config.get_license_allowlists()?;
+        let (safe_licenses, ignore_packages, safe_license_sources) = combine_license_information(
+            &policy_config.safe_licenses.clone().unwrap_or_default(),
+            &policy_config.ignore_packages.clone().unwrap_or_default(),
+            &license_allowlist_urls,
+        )
+        .with_context(|| format!("Failed to build license allowlist for policy '{name}'"))?;
+        let osi = policy_config.get_osi().unwrap_or(false);
+
+        if osi && !safe_licenses.is_empty() {
+            return Err(anyhow::anyhow!(
+                "Cannot use OSI mode and safe-licenses at the same time in policy '{name}'"
+            ));
+        }
+        if !osi && safe_licenses.is_empty() {
+            return Err(anyhow::anyhow!(
+                "No license allowlist provided for policy '{name}'"
+            ));
+        }
+
+        let warn_licenses =
+            parse_license_expressions(&policy_config.warn_licenses.clone().unwrap_or_default())?;
+        let deny_licenses =
+            parse_license_expressions(&policy_config.deny_licenses.clone().unwrap_or_default())?;
+
+        policies.push(CondaDenyPolicy {
+            name,
+            osi,
+            safe_licenses,
+            safe_license_sources,
+            warn_licenses,
+            deny_licenses,
+            ignore_packages,
+            environments: policy_config.get_environment_spec(),
+            prefixes: policy_config.get_prefix_spec()?,
+        });
+    }
+
+    Ok(policies)
+}
+
+fn combine_license_information(
+    safe_licenses_from_toml: &[String],
+    ignore_packages_from_toml: &[IgnorePackage],
+    license_allowlist_urls: &[String],
+) -> Result<(Vec<Expression>, Vec<IgnorePackage>, LicenseSources)> {
+    let (safe_licenses, ignore_packages, allowlist_sources) =
+        build_license_allowlist_with_sources(license_allowlist_urls)?;
+
+    let safe_licenses_from_toml = safe_licenses_from_toml
         .iter()
         .map(|license_str| parse_expression(license_str))
-        .collect::<Result<Vec<_>>>()?
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut license_sources = LicenseSources::new();
+    add_license_sources(
+        &mut license_sources,
+        &safe_licenses_from_toml,
+        &LicenseSource::SafeLicenses,
+    );
+    for (license_text, source) in allowlist_sources {
+        license_sources.entry(license_text).or_insert(source);
+    }
+
+    // TODO: Remove duplicates
+    let safe_licenses = safe_licenses_from_toml
         .into_iter()
         .chain(safe_licenses)
         .collect::<Vec<_>>();
@@ -260,7 +460,7 @@ pub fn get_license_information_from_toml_config(
         .cloned()
         .chain(ignore_packages)
         .collect::<Vec<_>>();
-    Ok((safe_licenses, ignore_packages))
+    Ok((safe_licenses, ignore_packages, license_sources))
 }
 
 #[cfg(test)]
@@ -392,7 +592,7 @@ ignore-packages = [
         let temp_config_path = temp_config_file.path().to_str().unwrap();
 
         let toml_config = CondaDenyTomlConfig::from_path(temp_config_path.into()).unwrap();
-        let (safe_licenses, ignored_packages) =
+        let (safe_licenses, ignored_packages, license_sources) =
             get_license_information_from_toml_config(&toml_config).unwrap();
         assert_eq!(safe_licenses.len(), 7);
         assert_eq!(
@@ -410,5 +610,10 @@ ignore-packages = [
         );
         assert_eq!(ignored_packages.len(), 2);
         assert_eq!(ignored_packages[0].version, None);
+        assert_eq!(license_sources["MIT"].source, LicenseSource::SafeLicenses);
+        assert_eq!(
+            license_sources["Apache-2.0"].source,
+            LicenseSource::LicenseAllowlist("tests/default_license_allowlist.toml".to_string())
+        );
     }
 }
@@ -0,0 +1,406 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    baseline::Baseline,
+    expression_utils::{normalize_expression, parse_expression},
+    license_info::LicenseInfo,
+};
+
+/// A package whose license differs from an earlier state, e.g. after a version upgrade
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LicenseChange {
+    pub package_name: String,
+    pub platform: Option<String>,
+    pub old_version: Option<String>,
+    pub new_version: Option<String>,
+    pub old_license: Option<String>,
+    pub new_license: Option<String>,
+}
+
+/// Returns the license with equivalent expressions normalized to the same string.
+pub fn normalized_license(license: Option<&str>) -> Option<String> {
+    let license = license?.trim();
+    Some(
+        parse_expression(license)
+            .map(|expression| normalize_expression(&expression))
+            .unwrap_or_else(|_| license.to_string()),
+    )
+}
+
+impl LicenseChange {
+    /// Returns the change if the normalized licenses of the two versions differ.
+    pub fn between(old: &LicenseInfo, new: &LicenseInfo) -> Option<Self> {
+        let old_license = old.license.license_string();
+        let new_license = new.license.license_string();
+        if normalized_license(old_license.as_deref()) == normalized_license(new_license.as_deref())
+        {
+            return None;
+        }
+        Some(LicenseChange {
+            package_name: new.package_name.clone(),
+            platform: new.platform.clone(),
+            old_version: old.version.clone(),
+            new_version: new.version.clone(),
+            old_license,
+            new_license,
+        })
+    }
+}
+
+/// A package version whose license differs between platforms or builds
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LicenseInconsistency {
+    pub package_name: String,
+    pub version: String,
+    /// One entry per distinct license, with the platforms and builds that carry it
+    pub licenses: Vec<LicenseVariant>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LicenseVariant {
+    pub license: Option<String>,
+    pub builds: Vec<PlatformBuild>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct PlatformBuild {
+    pub platform: Option<String>,
+    pub build: Option<String>,
+}
+
+/// Groups the packages by name and version and returns the ones whose normalized license
+/// differs between platforms or builds, which usually points to a packaging bug.
+///
+/// Source packages have no version and are skipped, their variants are different packages.
+pub fn inconsistent_licenses(license_infos: &[&LicenseInfo]) -> Vec<LicenseInconsistency> {
+    type Variants = BTreeMap<Option<String>, (Option<String>, BTreeSet<PlatformBuild>)>;
+    let mut packages: BTreeMap<(&str, &str), Variants> = BTreeMap::new();
+    for license_info in license_infos {
+        let Some(version) = &license_info.version else {
+            continue;
+        };
+        let license = license_info.license.license_string();
+        let (_, builds) = packages
+            .entry((license_info.package_name.as_str(), version.as_str()))
+            .or_default()
+            .entry(normalized_license(license.as_deref()))
+            .or_insert_with(|| (license, BTreeSet::new()));
+        builds.insert(PlatformBuild {
+            platform: license_info.platform.clone(),
+            build: license_info.build.clone(),
+        });
+    }
+
+    packages
+        .into_iter()
+        .filter(|(_, variants)| variants.len() > 1)
+        .map(|((package_name, version), variants)| LicenseInconsistency {
+            package_name: package_name.to_string(),
+            version: version.to_string(),
+            licenses: variants
+                .into_values()
+                .map(|(license, builds)| LicenseVariant {
+                    license,
+                    builds: builds.into_iter().collect(),
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+/// Compares the packages with the license recorded for them in the baseline.
+///
+/// Only packages whose version is covered by a baseline entry are compared.
+pub fn license_changes_from_baseline(
+    baseline: &Baseline,
+    license_infos: &[&LicenseInfo],
+) -> Result<Vec<LicenseChange>> {
+    let mut changes = Vec::new();
+    for entry in baseline.entries.iter().chain(&baseline.licenses) {
+        for license_info in license_infos {
+            if !entry
+                .package
+                .matches(&license_info.package_name, license_info.version.as_deref())?
+            {
+                continue;
+            }
+            let new_license = license_info.license.license_string();
+            if normalized_license(entry.license.as_deref())
+                != normalized_license(new_license.as_deref())
+            {
+                changes.push(LicenseChange {
+                    package_name: license_info.package_name.clone(),
+                    platform: license_info.platform.clone(),
+                    old_version: None,
+                    new_version: license_info.version.clone(),
+                    old_license: entry.license.clone(),
+                    new_license,
+                });
+            }
+        }
+    }
+    deduplicate(&mut changes);
+    Ok(changes)
+}
+
+/// A license snapshot, as written by `conda-deny list --output json`
+#[derive(Debug, Deserialize)]
+struct LicenseHistory {
+    /// Called `license_infos` in histories written by older versions
+    #[serde(alias = "license_infos")]
+    packages: Vec<HistoryEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HistoryEntry {
+    package_name: String,
+    version: Option<String>,
+    platform: Option<String>,
+    license: HistoryLicense,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum HistoryLicense {
+    /// Written before the JSON schema was versioned, tried first as `raw` is optional
+    Legacy(LegacyLicense),
+    /// Written since version 1 of the JSON schema
+    Structured { raw: Option<String> },
+}
+
+#[derive(Debug, Deserialize)]
+enum LegacyLicense {
+    Valid(String),
+    Invalid(String),
+    NoLicense,
+}
+
+/// Compares the packages with a license snapshot written by `conda-deny list --output json`.
+///
+/// Packages are matched by name and platform, so version upgrades are compared as well.
+pub fn license_changes_from_history(
+    path: &Path,
+    license_infos: &[&LicenseInfo],
+) -> Result<Vec<LicenseChange>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read license history: {path:?}"))?;
+    let history: LicenseHistory = serde_json::from_str(&content)
+        .with_context(|| format!("Failed to parse license history: {path:?}"))?;
+
+    let mut recorded: BTreeMap<(&str, Option<&str>), Vec<&HistoryEntry>> = BTreeMap::new();
+    for entry in &history.packages {
+        recorded
+            .entry((entry.package_name.as_str(), entry.platform.as_deref()))
+            .or_default()
+            .push(entry);
+    }
+
+    let mut changes = Vec::new();
+    for license_info in license_infos {
+        let Some(entries) = recorded.get(&(
+            license_info.package_name.as_str(),
+            license_info.platform.as_deref(),
+        )) else {
+            continue;
+        };
+        let new_license = license_info.license.license_string();
+        let new_normalized = normalized_license(new_license.as_deref());
+
+        // Several versions may have been recorded, e.g. for different environments
+        let unchanged = entries
+            .iter()
+            .any(|entry| normalized_license(entry.license().as_deref()) == new_normalized);
+        if unchanged {
+            continue;
+        }
+        let entry = entries[0];
+        changes.push(LicenseChange {
+            package_name: license_info.package_name.clone(),
+            platform: license_info.platform.clone(),
+            old_version: entry.version.clone(),
+            new_version: license_info.version.clone(),
+            old_license: entry.license(),
+            new_license,
+        });
+    }
+    deduplicate(&mut changes);
+    Ok(changes)
+}
+
+impl HistoryEntry {
+    fn license(&self) -> Option<String> {
+        match &self.license {
+            HistoryLicense::Structured { raw } => raw.clone(),
+            HistoryLicense::Legacy(LegacyLicense::Valid(license)) => {
+                Some(expression_from_postfix(license).unwrap_or_else(|| license.clone()))
+            }
+            HistoryLicense::Legacy(LegacyLicense::Invalid(license)) => Some(license.clone()),
+            HistoryLicense::Legacy(LegacyLicense::NoLicense) => None,
+        }
+    }
+}
+
+/// Valid licenses were written in postfix notation, e.g. `MIT Apache-2.0 OR`.
+fn expression_from_postfix(license: &str) -> Option<String> {
+    let mut stack: Vec<String> = Vec::new();
+    let mut tokens = license.split_whitespace();
+    while let Some(token) = tokens.next() {
+        match token {
+            "AND" | "OR" => {
+                let right = stack.pop()?;
+                let left = stack.pop()?;
+                stack.push(format!("({left} {token} {right})"));
+            }
+            "WITH" => {
+                let license = stack.pop()?;
+                stack.push(format!("{license} WITH {}", tokens.next()?));
+            }
+            _ => stack.push(token.to_string()),
+        }
+    }
+    match stack.as_slice() {
+        [expression] => Some(expression.clone()),
+        _ => None,
+    }
+}
+
+/// The same package is checked once per policy
+fn deduplicate(changes: &mut Vec<LicenseChange>) {
+    changes.sort_by(|a, b| {
+        (&a.package_name, &a.platform, &a.new_version).cmp(&(
+            &b.package_name,
+            &b.platform,
+            &b.new_version,
+        ))
+    });
+    changes.dedup();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CheckOutput;
+
+    #[test]
+    fn test_normalized_license() {
+        assert_eq!(
+            normalized_license(Some("MIT OR Apache-2.0")),
+            normalized_license(Some("Apache-2.0 OR MIT"))
+        );
+        assert_ne!(
+            normalized_license(Some("BSD-3-Clause")),
+            normalized_license(Some("BUSL-1.1"))
+        );
+        assert_eq!(
+            normalized_license(Some(" Custom license ")),
+            Some("Custom license".to_string())
+        );
+        assert_eq!(normalized_license(None), None);
+    }
+
+    #[test]
+    fn test_license_changes_from_baseline() {
+        let baseline = Baseline::from_check_output(&CheckOutput {
+            safe_dependencies: vec![LicenseInfo::for_test("foo", "1.0", "BSD-3-Clause")],
+            ..Default::default()
+        });
+        assert!(baseline.entries.is_empty());
+
+        let relicensed = LicenseInfo::for_test("foo", "1.0", "BUSL-1.1");
+        let changes = license_changes_from_baseline(&baseline, &[&relicensed]).unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].old_license.as_deref(), Some("BSD-3-Clause"));
+        assert_eq!(changes[0].new_license.as_deref(), Some("BUSL-1.1"));
+    }
+
+    #[test]
+    fn test_inconsistent_licenses() {
+        let license_info = |platform: &str, build: &str, license: &str| LicenseInfo {
+            platform: Some(platform.to_string()),
+            build: Some(build.to_string()),
+            ..LicenseInfo::for_test("foo", "1.0", license)
+        };
+        let consistent = [
+            license_info("linux-64", "h0", "MIT OR Apache-2.0"),
+            license_info("win-64", "h1", "Apache-2.0 OR MIT"),
+        ];
+        assert!(inconsistent_licenses(&consistent.iter().collect::<Vec<_>>()).is_empty());
+
+        let inconsistent = [
+            license_info("linux-64", "h0", "MIT"),
+            license_info("osx-64", "h0", "MIT"),
+            license_info("win-64", "h1", "BSD-3-Clause"),
+        ];
+        let inconsistencies = inconsistent_licenses(&inconsistent.iter().collect::<Vec<_>>());
+        assert_eq!(inconsistencies.len(), 1);
+        assert_eq!(inconsistencies[0].licenses.len(), 2);
+        assert_eq!(
+            inconsistencies[0].licenses[1],
+            LicenseVariant {
+                license: Some("MIT".to_string()),
+                builds: vec![
+                    PlatformBuild {
+                        platform: Some("linux-64".to_string()),
+                        build: Some("h0".to_string()),
+                    },
+                    PlatformBuild {
+                        platform: Some("osx-64".to_string()),
+                        build: Some("h0".to_string()),
+                    },
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn test_expression_from_postfix() {
+        let license = expression_from_postfix("BSD-3-Clause MIT OpenSSL AND AND").unwrap();
+        assert_eq!(
+            normalized_license(Some(&license)),
+            normalized_license(Some("BSD-3-Clause AND MIT AND OpenSSL"))
+        );
+        assert_eq!(
+            expression_from_postfix("GPL-2.0-only WITH Classpath-exception-2.0 MIT OR").unwrap(),
+            "(GPL-2.0-only WITH Classpath-exception-2.0 OR MIT)"
+        );
+        assert_eq!(expression_from_postfix("MIT OR"), None);
+    }
+
+    #[test]
+    fn test_history_license() {
+        let license = |license: serde_json::Value| {
+            let entry: HistoryEntry = serde_json::from_value(serde_json::json!({
+                "package_name": "k9s",
+                "version": "0.40.0",
+                "platform": "linux-64",
+                "license": license,
+            }))
+            .unwrap();
+            entry.license()
+        };
+        assert_eq!(
+            license(serde_json::json!({
+                "raw": "MIT OR Apache-2.0",
+                "state": "valid",
+                "expression": null,
+            })),
+            Some("MIT OR Apache-2.0".to_string())
+        );
+        assert_eq!(
+            license(serde_json::json!({ "raw": null, "state": "missing", "expression": null })),
+            None
+        );
+        // Histories written before the JSON schema was versioned
+        assert_eq!(
+            license(serde_json::json!({ "Valid": "MIT Apache-2.0 OR" })),
+            Some("(MIT OR Apache-2.0)".to_string())
+        );
+        assert_eq!(license(serde_json::json!("NoLicense")), None);
+    }
+}
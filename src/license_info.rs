@@ -1,23 +1,33 @@
-use std::{collections::BTreeSet, path::PathBuf};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    path::PathBuf,
+};
 
 use anyhow::{Context, Result};
 use colored::Colorize;
 use rattler_conda_types::prefix_record::PrefixRecord;
-use rattler_conda_types::PackageRecord;
-use rattler_lock::{CondaPackageData, CondaSourceData, SourceIdentifier};
+use rattler_conda_types::{PackageRecord, Platform};
+use rattler_lock::{CondaSourceData, LockFile, SourceIdentifier};
 use rayon::prelude::*;
-use serde::Serialize;
+use serde::{ser::SerializeStruct, Serialize};
 use spdx::Expression;
 
 use crate::{
-    expression_utils::{check_expression_safety, extract_license_texts, parse_expression},
+    conda_prefix,
+    dependency_graph::{EnvironmentGraph, RuntimeClosure},
+    explanation::Explanation,
+    expression_utils::{
+        check_expression_denied, check_expression_safety_without_denied, extract_license_texts,
+        is_osi_approved, parse_expression, ExpressionTree,
+    },
     license_allowlist::IgnorePackage,
     license_allowlist::{is_package_ignored, is_package_ignored_by_name_only},
-    pixi_lock::get_conda_packages_for_pixi_lock,
-    CheckOutput, CondaDenyCheckConfig, LockfileSpec,
+    pixi_lock::{get_locked_conda_packages, read_pixi_lock, LockedPackages},
+    CheckOutput, CondaDenyCheckConfig, CondaDenyPolicy, LockfileSpec, ReasonAction, UnsafeReason,
 };
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct LicenseInfo {
     pub package_name: String,
     pub version: Option<String>,
@@ -26,6 +36,64 @@ pub struct LicenseInfo {
     pub build: Option<String>,
     #[serde(skip_serializing)]
     pub source_identifier: Option<String>,
+    /// Name of the policy the package was checked against, only set when named policies are configured
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub policy: Option<String>,
+    /// Why the package was not considered safe, only set by `check`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<UnsafeReason>,
+    /// Per-requirement evaluation of the license, only set by `check`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub explanation: Option<Explanation>,
+    /// Direct dependencies that pull in the package, only set by `check --pulled-in-by`
+    #[serde(skip_serializing_if = "BTreeSet::is_empty")]
+    pub pulled_in_by: BTreeSet<String>,
+    /// The ignore-packages rules that matched the package, only set by `check`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub ignored_by: Vec<IgnorePackage>,
+    /// Whether the package is part of the runtime closure, only set by `check --runtime-closure`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub in_runtime_closure: Option<bool>,
+    /// Whether the package was requested by the user, only known for conda prefixes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dependency_type: Option<DependencyType>,
+    /// What the entries merged into this one differ in, only set by `--aggregate`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aggregation: Option<Aggregation>,
+    /// Lockfiles, environments and platforms the package was found in
+    #[serde(skip_serializing_if = "BTreeSet::is_empty")]
+    pub found_in: BTreeSet<Provenance>,
+}
+
+/// Where a package was found
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct Provenance {
+    /// Not set for conda prefixes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lockfile: Option<String>,
+    /// The pixi environment, or the path of the conda prefix
+    pub environment: String,
+    /// The platform the environment was locked for, which is not `noarch` for noarch packages.
+    /// Not known for conda prefixes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub platform: Option<String>,
+}
+
+/// The platforms, builds and environments of the entries merged by [`aggregate_license_infos`]
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Aggregation {
+    pub platforms: BTreeSet<String>,
+    pub builds: BTreeSet<String>,
+    /// Number of entries that were merged
+    pub packages: usize,
+}
+
+/// Whether a package was requested by the user or pulled in by another package
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DependencyType {
+    Direct,
+    Transitive,
 }
 
 impl LicenseInfo {
@@ -36,7 +104,7 @@ impl LicenseInfo {
             license: license_state_from_optional_str(package_record.license.as_deref()),
             platform: Some(package_record.subdir),
             build: Some(package_record.build),
-            source_identifier: None,
+            ..Default::default()
         }
     }
 
@@ -50,63 +118,163 @@ impl LicenseInfo {
             platform: None,
             build: None,
             source_identifier: Some(SourceIdentifier::from_source_data(source_data).to_string()),
+            ..Default::default()
         })
     }
 
+    /// The ignore-packages rules that match the package.
+    pub fn matching_ignore_rules<'a>(
+        &self,
+        ignore_packages: impl IntoIterator<Item = &'a IgnorePackage>,
+    ) -> Result<Vec<IgnorePackage>> {
+        let mut rules = Vec::new();
+        for ignore_package in ignore_packages {
+            if ignore_package.matches(&self.package_name, self.version.as_deref())? {
+                rules.push(ignore_package.clone());
+            }
+        }
+        Ok(rules)
+    }
+
+    /// Number of packages this entry stands for, more than one if entries were merged.
+    pub fn package_count(&self) -> usize {
+        self.aggregation
+            .as_ref()
+            .map_or(1, |aggregation| aggregation.packages)
+    }
+
+    /// The platform, or the platforms of an aggregated entry separated by commas
+    pub fn platforms_string(&self) -> Option<String> {
+        match &self.aggregation {
+            Some(aggregation) if !aggregation.platforms.is_empty() => Some(
+                aggregation
+                    .platforms
+                    .iter()
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            ),
+            Some(_) => None,
+            None => self.platform.clone(),
+        }
+    }
+
+    /// The environments the package was found in, by lockfile, e.g. `pixi.lock: default, lint`.
+    ///
+    /// The environments of conda prefixes are their paths, which are listed without a lockfile.
+    pub fn found_in_string(&self) -> Option<String> {
+        let mut environments: BTreeMap<Option<&str>, BTreeSet<&str>> = BTreeMap::new();
+        for provenance in &self.found_in {
+            environments
+                .entry(provenance.lockfile.as_deref())
+                .or_default()
+                .insert(&provenance.environment);
+        }
+        if environments.is_empty() {
+            return None;
+        }
+        let parts: Vec<String> = environments
+            .into_iter()
+            .map(|(lockfile, environments)| {
+                let environments = environments.into_iter().collect::<Vec<_>>().join(", ");
+                match lockfile {
+                    Some(lockfile) => format!("{lockfile}: {environments}"),
+                    None => environments,
+                }
+            })
+            .collect();
+        Some(parts.join("; "))
+    }
+
+    /// The build, or the builds of an aggregated entry separated by commas
+    pub fn builds_string(&self) -> Option<String> {
+        match &self.aggregation {
+            Some(aggregation) if !aggregation.builds.is_empty() => Some(
+                aggregation
+                    .builds
+                    .iter()
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            ),
+            Some(_) => None,
+            None => self.build.clone(),
+        }
+    }
+
     pub fn pretty_print(&self) -> String {
-        let license_str = match &self.license {
-            LicenseState::Valid(license) => license.to_string(),
-            LicenseState::Invalid(license) => license.to_string(),
-            LicenseState::NoLicense => "no license".to_string(),
-        };
+        let license_str = self.license.display_string();
 
         let comment = match &self.license {
+            LicenseState::Valid(_) if self.reason == Some(UnsafeReason::Denied) => Some("(denied)"),
             LicenseState::Valid(_) => None,
             LicenseState::Invalid(_) => Some("(Non-SPDX)"),
             LicenseState::NoLicense => None,
         };
         let version = self.version.as_deref().unwrap_or("unknown-source");
         let build = self.build.as_deref().unwrap_or("unknown-source");
-        let platform = self.platform.as_deref().unwrap_or("unknown-source");
+        let platform = self
+            .platforms_string()
+            .unwrap_or_else(|| "unknown-source".to_string());
+        let found_in = self
+            .found_in_string()
+            .map(|found_in| format!(" [{found_in}]").bright_black().to_string())
+            .unwrap_or_default();
+        let comment = comment
+            .map(|comment| format!(" {}", comment.bright_black()))
+            .unwrap_or_default();
 
         if let Some(source_identifier) = &self.source_identifier {
-            return if let Some(comment) = comment {
-                format!(
-                    "{} ({}): {} {}\n",
-                    source_identifier.blue(),
-                    "source".bright_purple(),
-                    license_str.yellow(),
-                    comment.bright_black(),
-                )
-            } else {
-                format!(
-                    "{} ({}): {}\n",
-                    source_identifier.blue(),
-                    "source".bright_purple(),
-                    license_str.yellow(),
-                )
-            };
+            return format!(
+                "{} ({}){found_in}: {}{comment}\n",
+                source_identifier.blue(),
+                "source".bright_purple(),
+                license_str.yellow(),
+            );
         }
 
-        if let Some(comment) = comment {
-            format!(
-                "{} {}-{} ({}): {} {}\n",
-                self.package_name.blue(),
-                version.cyan(),
-                build.bright_cyan().italic(),
-                platform.bright_purple(),
-                license_str.yellow(),
-                comment.bright_black(),
-            )
+        let version = if self.aggregation.is_some() {
+            // The build is only shown if it is the same on all platforms
+            match &self.build {
+                Some(build) => format!("{}-{}", version.cyan(), build.bright_cyan().italic()),
+                None => version.cyan().to_string(),
+            }
         } else {
-            format!(
-                "{} {}-{} ({}): {}\n",
-                self.package_name.blue(),
-                version.cyan(),
-                build.bright_cyan().italic(),
-                platform.bright_purple(),
-                license_str.yellow(),
-            )
+            format!("{}-{}", version.cyan(), build.bright_cyan().italic())
+        };
+        format!(
+            "{} {} ({}){found_in}: {}{comment}\n",
+            self.package_name.blue(),
+            version,
+            platform.bright_purple(),
+            license_str.yellow(),
+        )
+    }
+}
+
+#[cfg(test)]
+impl LicenseInfo {
+    /// A linux-64 package for tests, with the license parsed as if read from a lockfile.
+    pub(crate) fn for_test(name: &str, version: &str, license: &str) -> Self {
+        LicenseInfo {
+            package_name: name.to_string(),
+            version: Some(version.to_string()),
+            license: license_state_from_optional_str(Some(license)),
+            platform: Some("linux-64".to_string()),
+            build: Some("h0_0".to_string()),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+impl Provenance {
+    /// The linux-64 platform of an environment of `pixi.lock`, for tests.
+    pub(crate) fn for_test(environment: &str) -> Self {
+        Provenance {
+            lockfile: Some("pixi.lock".to_string()),
+            environment: environment.to_string(),
+            platform: Some("linux-64".to_string()),
         }
     }
 }
@@ -142,6 +310,16 @@ impl Ord for LicenseInfo {
     }
 }
 
+/// What happens to the packages matched by ignore-packages when collecting license information.
+///
+/// Ignored pypi packages and source packages without a package record are always left out,
+/// as there is no license information for them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IgnoredPackages {
+    Skip,
+    Keep,
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct LicenseInfos {
     pub license_infos: Vec<LicenseInfo>,
@@ -159,18 +337,20 @@ impl LicenseInfos {
     pub fn from_pixi_lockfiles(
         lockfile_spec: LockfileSpec,
         ignore_packages: &[IgnorePackage],
+        ignored_packages: IgnoredPackages,
     ) -> Result<LicenseInfos> {
         anyhow::ensure!(
             !lockfile_spec.lockfiles.is_empty(),
             "No lockfiles provided in LockfileSpec"
         );
 
-        let conda_packages: Vec<CondaPackageData> = lockfile_spec
+        let conda_packages: Vec<LockedPackages> = lockfile_spec
             .lockfiles
             .par_iter()
             .map(|lockfile| {
-                get_conda_packages_for_pixi_lock(
-                    lockfile,
+                get_locked_conda_packages(
+                    &read_pixi_lock(lockfile)?,
+                    &lockfile.display().to_string(),
                     &lockfile_spec.environments,
                     &lockfile_spec.platforms,
                     lockfile_spec.ignore_pypi,
@@ -188,17 +368,57 @@ impl LicenseInfos {
             .flatten()
             .collect();
 
+        Self::from_conda_packages_by_environment(conda_packages, ignore_packages, ignored_packages)
+    }
+
+    /// Collects the license information of a lockfile that was already read, using the
+    /// environment and platform selection of `lockfile_spec`.
+    ///
+    /// `lockfile` names the lockfile in the provenance of the packages.
+    pub fn from_lock_file(
+        lock_file: &LockFile,
+        lockfile: &str,
+        lockfile_spec: &LockfileSpec,
+        ignore_packages: &[IgnorePackage],
+        ignored_packages: IgnoredPackages,
+    ) -> Result<LicenseInfos> {
+        let conda_packages = get_locked_conda_packages(
+            lock_file,
+            lockfile,
+            &lockfile_spec.environments,
+            &lockfile_spec.platforms,
+            lockfile_spec.ignore_pypi,
+            ignore_packages,
+        )?;
+        Self::from_conda_packages_by_environment(conda_packages, ignore_packages, ignored_packages)
+    }
+
+    fn from_conda_packages_by_environment(
+        conda_packages: Vec<LockedPackages>,
+        ignore_packages: &[IgnorePackage],
+        ignored_packages: IgnoredPackages,
+    ) -> Result<LicenseInfos> {
+        let conda_packages = conda_packages
+            .into_iter()
+            .flat_map(|(provenance, packages)| {
+                packages
+                    .into_iter()
+                    .map(move |package| (provenance.clone(), package))
+            });
+
         let mut license_infos = BTreeSet::new();
-        for package in conda_packages {
+        for (provenance, package) in conda_packages {
             let package_name = package.name().as_source();
 
-            if let Some(record) = package.record().cloned() {
+            let mut license_info = if let Some(record) = package.record().cloned() {
                 let package_version = record.version.version().to_string();
-                if is_package_ignored(ignore_packages, package_name, &package_version)? {
+                if ignored_packages == IgnoredPackages::Skip
+                    && is_package_ignored(ignore_packages, package_name, &package_version)?
+                {
                     continue;
                 }
 
-                license_infos.insert(LicenseInfo::from_package_record(record));
+                LicenseInfo::from_package_record(record)
             } else {
                 if is_package_ignored_by_name_only(ignore_packages, package_name) {
                     continue;
@@ -218,8 +438,11 @@ impl LicenseInfos {
                     ));
                 };
 
-                license_infos.insert(license_info);
-            }
+                license_info
+            };
+
+            license_info.found_in.insert(provenance);
+            insert_merging_environments(&mut license_infos, license_info);
         }
 
         Ok(LicenseInfos {
@@ -230,6 +453,7 @@ impl LicenseInfos {
     pub fn from_conda_prefixes(
         prefixes: &[PathBuf],
         ignore_packages: &[IgnorePackage],
+        ignored_packages: IgnoredPackages,
     ) -> Result<LicenseInfos> {
         let mut license_infos: BTreeSet<_> = BTreeSet::new();
         anyhow::ensure!(!prefixes.is_empty(), "No conda prefixes provided");
@@ -250,16 +474,31 @@ impl LicenseInfos {
                         conda_prefix.display()
                     )
                 })?;
+            let graph = conda_prefix::dependency_graph(conda_prefix, &prefix_records)?;
 
             for record in prefix_records {
                 let package_record = record.repodata_record.package_record;
                 let package_name = package_record.name.as_source();
                 let package_version = package_record.version.version().to_string();
-                if is_package_ignored(ignore_packages, package_name, &package_version)? {
+                if ignored_packages == IgnoredPackages::Skip
+                    && is_package_ignored(ignore_packages, package_name, &package_version)?
+                {
                     continue;
                 }
 
-                license_infos.insert(LicenseInfo::from_package_record(package_record));
+                let dependency_type = if graph.direct_dependencies().contains(package_name) {
+                    DependencyType::Direct
+                } else {
+                    DependencyType::Transitive
+                };
+                let mut license_info = LicenseInfo::from_package_record(package_record);
+                license_info.dependency_type = Some(dependency_type);
+                license_info.found_in.insert(Provenance {
+                    lockfile: None,
+                    environment: conda_prefix.display().to_string(),
+                    platform: None,
+                });
+                insert_merging_environments(&mut license_infos, license_info);
             }
         }
 
@@ -268,70 +507,398 @@ impl LicenseInfos {
         })
     }
 
+    /// Records which direct dependencies pull in each package.
+    pub fn annotate_pulled_in_by(&mut self, graphs: &[EnvironmentGraph]) {
+        for license_info in &mut self.license_infos {
+            for environment_graph in graphs {
+                if license_info.is_found_in(
+                    environment_graph.lockfile.as_deref(),
+                    &environment_graph.environment,
+                    environment_graph.platform,
+                ) {
+                    license_info.pulled_in_by.extend(
+                        environment_graph
+                            .graph
+                            .pulled_in_by(&license_info.package_name),
+                    );
+                }
+            }
+        }
+    }
+
+    /// Records whether each package is part of the runtime closure of any environment it was found in.
+    pub fn annotate_runtime_closure(&mut self, closures: &[RuntimeClosure]) {
+        for license_info in &mut self.license_infos {
+            let in_runtime_closure = closures.iter().any(|closure| {
+                license_info.is_found_in(
+                    closure.lockfile.as_deref(),
+                    &closure.environment,
+                    closure.platform,
+                ) && closure.packages.contains(&license_info.package_name)
+            });
+            license_info.in_runtime_closure = Some(in_runtime_closure);
+        }
+    }
+
     pub fn check(&self, config: &CondaDenyCheckConfig) -> Result<CheckOutput> {
-        let mut safe_dependencies = Vec::new();
-        let mut unsafe_dependencies = Vec::new();
+        let mut check_output = CheckOutput::default();
+
+        let default_policy = config.default_policy();
+        let closure_policy_name = config
+            .runtime_closure
+            .as_ref()
+            .and_then(|runtime_closure| runtime_closure.policy.as_ref());
+        // --policy checks every package against the override, in and outside the closure
+        let closure_policy = match closure_policy_name {
+            Some(name) if config.policy_override.is_none() => {
+                Some(config.policy_by_name(&default_policy, name)?)
+            }
+            _ => None,
+        };
 
         for license_info in &self.license_infos {
-            match &license_info.license {
-                LicenseState::Valid(license) => {
-                    if check_expression_safety(license, &config.safe_licenses) {
-                        safe_dependencies.push(license_info.clone());
-                    } else {
-                        unsafe_dependencies.push(license_info.clone());
-                    }
+            let policies = match (license_info.in_runtime_closure, closure_policy) {
+                (Some(true), Some(closure_policy)) => vec![closure_policy],
+                // Without a closure policy, only the closure is checked
+                (Some(false), _) if closure_policy_name.is_none() => {
+                    check_output
+                        .outside_closure_dependencies
+                        .push(license_info.clone());
+                    continue;
                 }
-                LicenseState::Invalid(_) | LicenseState::NoLicense => {
-                    unsafe_dependencies.push(license_info.clone());
+                _ => config.resolve_policies(&default_policy, &license_info.environments())?,
+            };
+            for policy in policies {
+                let ignored_by = license_info.matching_ignore_rules(
+                    config.ignore_packages.iter().chain(&policy.ignore_packages),
+                )?;
+                let (result, mut checked_license_info) = license_info.check_against(policy, config);
+                if !ignored_by.is_empty() {
+                    checked_license_info.ignored_by = ignored_by;
+                    check_output.ignored_dependencies.push(checked_license_info);
+                    continue;
+                }
+                match result {
+                    CheckResult::Safe => check_output.safe_dependencies.push(checked_license_info),
+                    CheckResult::Warn => check_output.warn_dependencies.push(checked_license_info),
+                    CheckResult::Unsafe => {
+                        check_output.unsafe_dependencies.push(checked_license_info)
+                    }
+                    CheckResult::Ignored => {
+                        check_output.ignored_dependencies.push(checked_license_info)
+                    }
                 }
             }
         }
 
-        Ok((safe_dependencies, unsafe_dependencies))
+        Ok(check_output)
     }
+}
 
-    pub fn osi_check(&self) -> CheckOutput {
-        let mut safe_dependencies = Vec::new();
-        let mut unsafe_dependencies = Vec::new();
+/// Which part of the check output a package ends up in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CheckResult {
+    Safe,
+    Warn,
+    Unsafe,
+    Ignored,
+}
 
-        for license_info in &self.license_infos {
-            match &license_info.license {
-                LicenseState::Valid(license) => {
-                    let license_ids = extract_license_texts(license);
-                    let is_safe = license_ids.iter().all(|license_id_str| {
-                        if let Some(license_id) = spdx::license_id(license_id_str) {
-                            license_id.is_osi_approved()
-                        } else {
-                            false
-                        }
-                    });
-
-                    if is_safe {
-                        safe_dependencies.push(license_info.clone());
-                    } else {
-                        unsafe_dependencies.push(license_info.clone());
-                    }
-                }
-                LicenseState::Invalid(_) | LicenseState::NoLicense => {
-                    unsafe_dependencies.push(license_info.clone());
+impl fmt::Display for CheckResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckResult::Safe => write!(f, "safe"),
+            CheckResult::Warn => write!(f, "warn"),
+            CheckResult::Unsafe => write!(f, "unsafe"),
+            CheckResult::Ignored => write!(f, "ignored"),
+        }
+    }
+}
+
+impl LicenseInfo {
+    /// Pixi environments (or conda prefixes) the package was found in
+    pub fn environments(&self) -> BTreeSet<String> {
+        self.found_in
+            .iter()
+            .map(|provenance| provenance.environment.clone())
+            .collect()
+    }
+
+    /// Whether the package was found in the environment of the lockfile on the given platform.
+    fn is_found_in(
+        &self,
+        lockfile: Option<&str>,
+        environment: &str,
+        platform: Option<Platform>,
+    ) -> bool {
+        self.found_in.iter().any(|provenance| {
+            provenance.lockfile.as_deref() == lockfile
+                && provenance.environment == environment
+                && provenance.platform.as_deref() == platform.map(|platform| platform.as_str())
+        })
+    }
+
+    /// Checks the license against a single policy, returning a copy annotated with the
+    /// policy, reason and explanation.
+    pub fn check_against(
+        &self,
+        policy: &CondaDenyPolicy,
+        config: &CondaDenyCheckConfig,
+    ) -> (CheckResult, LicenseInfo) {
+        let mut checked_license_info = self.clone();
+        if !config.policies.is_empty() {
+            checked_license_info.policy = Some(policy.name.clone());
+        }
+        checked_license_info.explanation = Some(Explanation::new(&self.license, policy));
+
+        let result = match evaluate_license(&self.license, policy) {
+            Verdict::Safe => CheckResult::Safe,
+            Verdict::Warn => CheckResult::Warn,
+            Verdict::Unsafe(reason) => {
+                checked_license_info.reason = Some(reason);
+                match config.reason_actions.action(reason) {
+                    ReasonAction::Fail => CheckResult::Unsafe,
+                    ReasonAction::Warn => CheckResult::Warn,
+                    ReasonAction::Ignore => CheckResult::Ignored,
                 }
             }
+        };
+        (result, checked_license_info)
+    }
+}
+
+enum Verdict {
+    Safe,
+    Warn,
+    Unsafe(UnsafeReason),
+}
+
+fn evaluate_license(license: &LicenseState, policy: &CondaDenyPolicy) -> Verdict {
+    let expression = match license {
+        LicenseState::Valid(expression) => expression,
+        LicenseState::Invalid(_) => return Verdict::Unsafe(UnsafeReason::NonSpdx),
+        LicenseState::NoLicense => return Verdict::Unsafe(UnsafeReason::Missing),
+    };
+
+    if !policy.deny_licenses.is_empty()
+        && check_expression_denied(expression, &policy.deny_licenses)
+    {
+        Verdict::Unsafe(UnsafeReason::Denied)
+    } else if is_license_allowed(
+        license,
+        policy.osi,
+        &policy.safe_licenses,
+        &policy.deny_licenses,
+    ) {
+        Verdict::Safe
+    } else if !policy.warn_licenses.is_empty()
+        && is_license_allowed(
+            license,
+            policy.osi,
+            &[policy.safe_licenses.as_slice(), &policy.warn_licenses].concat(),
+            &policy.deny_licenses,
+        )
+    {
+        Verdict::Warn
+    } else {
+        Verdict::Unsafe(UnsafeReason::Disallowed)
+    }
+}
+
+/// Checks a license against the allowed licenses and, in OSI mode, all OSI approved licenses.
+///
+/// Denied licenses are never allowed, even if they are in the allowlist.
+fn is_license_allowed(
+    license: &LicenseState,
+    osi: bool,
+    allowed_licenses: &[Expression],
+    deny_licenses: &[Expression],
+) -> bool {
+    match license {
+        LicenseState::Valid(license) if osi => {
+            let allowed_license_ids: Vec<String> = allowed_licenses
+                .iter()
+                .flat_map(extract_license_texts)
+                .collect();
+            let denied_license_ids: Vec<String> = deny_licenses
+                .iter()
+                .flat_map(extract_license_texts)
+                .collect();
+            let license_ids = extract_license_texts(license);
+            license_ids.iter().all(|license_id_str| {
+                if denied_license_ids.contains(license_id_str) {
+                    false
+                } else if allowed_license_ids.contains(license_id_str) {
+                    true
+                } else {
+                    is_osi_approved(license_id_str)
+                }
+            })
         }
+        LicenseState::Valid(license) => {
+            check_expression_safety_without_denied(license, allowed_licenses, deny_licenses)
+        }
+        LicenseState::Invalid(_) | LicenseState::NoLicense => false,
+    }
+}
 
-        (safe_dependencies, unsafe_dependencies)
+/// Adds a license info to the set, merging its environments into an already present entry.
+fn insert_merging_environments(
+    license_infos: &mut BTreeSet<LicenseInfo>,
+    license_info: LicenseInfo,
+) {
+    match license_infos.take(&license_info) {
+        Some(mut existing) => {
+            existing.found_in.extend(license_info.found_in);
+            // A package that is requested in any environment counts as direct
+            existing.dependency_type =
+                match (existing.dependency_type, license_info.dependency_type) {
+                    (Some(existing), Some(other)) => Some(existing.min(other)),
+                    (existing, other) => existing.or(other),
+                };
+            license_infos.insert(existing);
+        }
+        None => {
+            license_infos.insert(license_info);
+        }
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize)]
+#[derive(Debug, Clone, Default, PartialEq)]
 #[allow(clippy::large_enum_variant)]
 pub enum LicenseState {
-    #[serde(serialize_with = "serialize_expression")]
     Valid(Expression),
     Invalid(String),
+    #[default]
     NoLicense,
 }
 
-fn license_state_from_optional_str(license: Option<&str>) -> LicenseState {
+impl LicenseState {
+    /// Returns the license as found in the package metadata, if there is one.
+    pub fn license_string(&self) -> Option<String> {
+        match self {
+            LicenseState::Valid(expression) => Some(expression.to_string()),
+            LicenseState::Invalid(license) => Some(license.clone()),
+            LicenseState::NoLicense => None,
+        }
+    }
+
+    /// The license for display, `no license` for packages without one
+    pub fn display_string(&self) -> String {
+        self.license_string()
+            .unwrap_or_else(|| "no license".to_string())
+    }
+
+    /// `valid` for SPDX expressions, `invalid` for other licenses and `missing` without license
+    pub fn state(&self) -> &'static str {
+        match self {
+            LicenseState::Valid(_) => "valid",
+            LicenseState::Invalid(_) => "invalid",
+            LicenseState::NoLicense => "missing",
+        }
+    }
+}
+
+/// Written as the license string, its state and the parsed expression of valid licenses
+impl Serialize for LicenseState {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let expression = match self {
+            LicenseState::Valid(expression) => Some(ExpressionTree::from_expression(expression)),
+            _ => None,
+        };
+        let mut license = serializer.serialize_struct("LicenseState", 3)?;
+        license.serialize_field("raw", &self.license_string())?;
+        license.serialize_field("state", self.state())?;
+        license.serialize_field("expression", &expression)?;
+        license.end()
+    }
+}
+
+/// Merges the entries with the same name, version, license and verdict into the first of them,
+/// e.g. a noarch package that is found on every platform.
+///
+/// The merged entry keeps its platform and build only if all entries share them.
+/// Entries without duplicates are returned unchanged.
+pub fn aggregate_license_infos(license_infos: &[LicenseInfo]) -> Vec<LicenseInfo> {
+    let mut aggregated: Vec<LicenseInfo> = Vec::new();
+    let mut positions = BTreeMap::new();
+    for license_info in license_infos {
+        let key = (
+            &license_info.package_name,
+            &license_info.version,
+            license_info.license.license_string(),
+            &license_info.source_identifier,
+            &license_info.policy,
+            license_info.reason,
+        );
+        let position = *positions.entry(key).or_insert_with(|| {
+            let mut first = license_info.clone();
+            first.aggregation = Some(Aggregation::default());
+            aggregated.push(first);
+            aggregated.len() - 1
+        });
+
+        let entry = &mut aggregated[position];
+        if entry.platform != license_info.platform {
+            entry.platform = None;
+        }
+        if entry.build != license_info.build {
+            entry.build = None;
+        }
+        entry.pulled_in_by.extend(license_info.pulled_in_by.clone());
+        entry.found_in.extend(license_info.found_in.clone());
+        let aggregation = entry.aggregation.get_or_insert_default();
+        aggregation.platforms.extend(license_info.platform.clone());
+        aggregation.builds.extend(license_info.build.clone());
+        aggregation.packages += 1;
+    }
+    for entry in &mut aggregated {
+        if entry.package_count() == 1 {
+            entry.aggregation = None;
+        }
+    }
+    aggregated
+}
+
+/// Number of packages of the entries, counting the entries merged by `--aggregate` separately.
+pub fn count_packages<'a>(license_infos: impl IntoIterator<Item = &'a LicenseInfo>) -> usize {
+    license_infos
+        .into_iter()
+        .map(LicenseInfo::package_count)
+        .sum()
+}
+
+/// Number of packages per license, most common first.
+///
+/// A package checked against several policies is counted once.
+pub fn packages_per_license<'a>(
+    license_infos: impl IntoIterator<Item = &'a LicenseInfo>,
+) -> Vec<(String, usize)> {
+    let mut packages: BTreeMap<String, BTreeSet<_>> = BTreeMap::new();
+    for license_info in license_infos {
+        let license = license_info.license.display_string();
+        packages.entry(license).or_default().insert((
+            &license_info.package_name,
+            &license_info.version,
+            &license_info.platform,
+            &license_info.build,
+        ));
+    }
+    let mut counts: Vec<(String, usize)> = packages
+        .into_iter()
+        .map(|(license, packages)| (license, packages.len()))
+        .collect();
+    counts.sort_by(|(a_license, a_count), (b_license, b_count)| {
+        b_count.cmp(a_count).then(a_license.cmp(b_license))
+    });
+    counts
+}
+
+pub(crate) fn license_state_from_optional_str(license: Option<&str>) -> LicenseState {
     let Some(license) = license else {
         return LicenseState::NoLicense;
     };
@@ -341,39 +908,21 @@ fn license_state_from_optional_str(license: Option<&str>) -> LicenseState {
     }
 }
 
-fn serialize_expression<S>(expr: &Expression, serializer: S) -> Result<S::Ok, S::Error>
-where
-    S: serde::Serializer,
-{
-    serializer.serialize_str(&format!("{expr:?}"))
-}
-
 #[cfg(test)]
 mod tests {
 
     use super::*;
-    use crate::{LockfileOrPrefix, OutputFormat};
+    use crate::{
+        dependency_graph::DependencyGraph, license_allowlist::LicenseSources, LockfileOrPrefix,
+        ReasonActions, RuntimeClosureConfig,
+    };
     use spdx::Expression;
 
     #[test]
     fn test_exit_code_for_safe_and_unsafe_dependencies() {
         // Create license infos without unsafe dependencies
-        let unsafe_license_info = LicenseInfo {
-            package_name: "test".to_string(),
-            version: Some("0.1.0".to_string()),
-            license: LicenseState::Invalid("Invalid-MIT".to_string()),
-            platform: Some("linux-64".to_string()),
-            build: Some("py_0".to_string()),
-            source_identifier: None,
-        };
-        let safe_license_info = LicenseInfo {
-            package_name: "test".to_string(),
-            version: Some("0.1.0".to_string()),
-            license: LicenseState::Valid(Expression::parse("MIT").unwrap()),
-            platform: Some("linux-64".to_string()),
-            build: Some("py_0".to_string()),
-            source_identifier: None,
-        };
+        let unsafe_license_info = LicenseInfo::for_test("test", "0.1.0", "Invalid-MIT");
+        let safe_license_info = LicenseInfo::for_test("test", "0.1.0", "MIT");
 
         let unsafe_license_infos = LicenseInfos {
             license_infos: vec![unsafe_license_info, safe_license_info.clone()],
@@ -384,7 +933,6 @@ mod tests {
         };
 
         let safe_licenses = vec![Expression::parse("MIT").unwrap()];
-        let ignore_packages = vec![];
 
         let config = CondaDenyCheckConfig {
             lockfile_or_prefix: LockfileOrPrefix::Lockfile(LockfileSpec {
@@ -393,38 +941,374 @@ mod tests {
                 environments: None,
                 ignore_pypi: false,
             }),
-            osi: false,
             safe_licenses,
-            ignore_packages,
-            output_format: OutputFormat::Default,
+            ..Default::default()
         };
 
-        let (_, unsafe_dependencies) = unsafe_license_infos.check(&config).unwrap();
-        assert!(!unsafe_dependencies.is_empty());
+        let check_output = unsafe_license_infos.check(&config).unwrap();
+        assert!(!check_output.unsafe_dependencies.is_empty());
 
-        let (_, unsafe_dependencies) = safe_license_infos.check(&config).unwrap();
-        assert!(unsafe_dependencies.is_empty());
+        let check_output = safe_license_infos.check(&config).unwrap();
+        assert!(check_output.unsafe_dependencies.is_empty());
     }
 
     #[test]
-    fn test_sort_license_infos() {
-        let license_info1 = LicenseInfo {
-            package_name: "test".to_string(),
-            version: Some("0.1.0".to_string()),
-            license: LicenseState::Invalid("Invalid-MIT".to_string()),
-            platform: Some("linux-64".to_string()),
-            build: Some("py_0".to_string()),
-            source_identifier: None,
+    fn test_check_ignore_packages() {
+        let license_info = |name: &str, license: &str| LicenseInfo {
+            found_in: BTreeSet::from([Provenance::for_test("default")]),
+            ..LicenseInfo::for_test(name, "0.1.0", license)
         };
-        let license_info2 = LicenseInfo {
-            package_name: "test2".to_string(),
-            version: Some("0.1.0".to_string()),
-            license: LicenseState::Invalid("Invalid-MIT".to_string()),
-            platform: Some("linux-64".to_string()),
-            build: Some("py_0".to_string()),
-            source_identifier: None,
+        let license_infos = LicenseInfos {
+            license_infos: vec![
+                license_info("top-level", "GPL-3.0-only"),
+                license_info("policy-level", "MIT"),
+                license_info("checked", "MIT"),
+            ],
+        };
+        let top_level = IgnorePackage::new("top-level".to_string(), None);
+        let policy_level = IgnorePackage::new("policy-level".to_string(), Some("<1".to_string()));
+        let config = CondaDenyCheckConfig {
+            ignore_packages: vec![top_level.clone()],
+            policies: vec![CondaDenyPolicy {
+                name: "default".to_string(),
+                osi: false,
+                safe_licenses: vec![Expression::parse("MIT").unwrap()],
+                safe_license_sources: LicenseSources::new(),
+                warn_licenses: vec![],
+                deny_licenses: vec![],
+                ignore_packages: vec![policy_level.clone()],
+                environments: vec!["default".to_string()],
+                prefixes: vec![],
+            }],
+            ..Default::default()
+        };
+
+        let check_output = license_infos.check(&config).unwrap();
+        let ignored: Vec<(&str, &[IgnorePackage])> = check_output
+            .ignored_dependencies
+            .iter()
+            .map(|info| (info.package_name.as_str(), info.ignored_by.as_slice()))
+            .collect();
+        assert_eq!(
+            ignored,
+            vec![
+                ("top-level", std::slice::from_ref(&top_level)),
+                ("policy-level", std::slice::from_ref(&policy_level)),
+            ]
+        );
+        assert_eq!(check_output.safe_dependencies.len(), 1);
+        assert!(check_output.unsafe_dependencies.is_empty());
+    }
+
+    #[test]
+    fn test_check_with_named_policies() {
+        let license_info = |environment: &str| LicenseInfo {
+            build: Some(format!("{environment}_0")),
+            found_in: BTreeSet::from([Provenance::for_test(environment)]),
+            ..LicenseInfo::for_test("test", "0.1.0", "MPL-2.0")
+        };
+        let license_infos = LicenseInfos {
+            license_infos: vec![license_info("prod"), license_info("lint")],
         };
 
+        let mut config = CondaDenyCheckConfig {
+            policies: vec![CondaDenyPolicy {
+                name: "lenient".to_string(),
+                osi: true,
+                safe_licenses: vec![],
+                safe_license_sources: LicenseSources::new(),
+                warn_licenses: vec![],
+                deny_licenses: vec![],
+                ignore_packages: vec![],
+                environments: vec!["lint".to_string()],
+                prefixes: vec![],
+            }],
+            ..Default::default()
+        };
+
+        // "prod" is not mapped to a policy and there is no top-level allowlist
+        assert!(license_infos.check(&config).is_err());
+
+        config.safe_licenses = vec![Expression::parse("MIT").unwrap()];
+        let check_output = license_infos.check(&config).unwrap();
+        assert_eq!(check_output.safe_dependencies.len(), 1);
+        assert_eq!(
+            check_output.safe_dependencies[0].policy.as_deref(),
+            Some("lenient")
+        );
+        assert_eq!(check_output.unsafe_dependencies.len(), 1);
+        assert_eq!(
+            check_output.unsafe_dependencies[0].policy.as_deref(),
+            Some("default")
+        );
+
+        config.policy_override = Some("lenient".to_string());
+        let check_output = license_infos.check(&config).unwrap();
+        assert_eq!(check_output.safe_dependencies.len(), 2);
+        assert!(check_output.unsafe_dependencies.is_empty());
+    }
+
+    #[test]
+    fn test_annotate_matches_lockfile_environment_and_platform() {
+        let provenance = |lockfile: &str, platform: &str| Provenance {
+            lockfile: Some(lockfile.to_string()),
+            environment: "default".to_string(),
+            platform: Some(platform.to_string()),
+        };
+        let license_info = |name: &str, found_in: Provenance| LicenseInfo {
+            platform: Some("noarch".to_string()),
+            found_in: BTreeSet::from([found_in]),
+            ..LicenseInfo::for_test(name, "1.0", "MIT")
+        };
+        let mut license_infos = LicenseInfos {
+            license_infos: vec![
+                license_info("a", provenance("a/pixi.lock", "linux-64")),
+                license_info("b", provenance("b/pixi.lock", "linux-64")),
+                license_info("c", provenance("a/pixi.lock", "osx-arm64")),
+            ],
+        };
+        // Every package is in the graph and closure of the default environment of a/pixi.lock
+        // on linux-64
+        let packages = ["a", "b", "c"].map(|name| (name.to_string(), [].as_slice()));
+        let graph = DependencyGraph::new(packages, None);
+        license_infos.annotate_pulled_in_by(&[EnvironmentGraph {
+            lockfile: Some("a/pixi.lock".to_string()),
+            environment: "default".to_string(),
+            platform: Some(Platform::Linux64),
+            graph,
+        }]);
+        license_infos.annotate_runtime_closure(&[RuntimeClosure {
+            lockfile: Some("a/pixi.lock".to_string()),
+            environment: "default".to_string(),
+            platform: Some(Platform::Linux64),
+            packages: BTreeSet::from(["a".to_string(), "b".to_string(), "c".to_string()]),
+        }]);
+
+        let in_runtime_closure: Vec<_> = license_infos
+            .license_infos
+            .iter()
+            .map(|license_info| license_info.in_runtime_closure)
+            .collect();
+        assert_eq!(
+            in_runtime_closure,
+            vec![Some(true), Some(false), Some(false)]
+        );
+        assert_eq!(
+            license_infos.license_infos[0].pulled_in_by,
+            BTreeSet::from(["a".to_string()])
+        );
+        assert!(license_infos.license_infos[1].pulled_in_by.is_empty());
+        assert!(license_infos.license_infos[2].pulled_in_by.is_empty());
+    }
+
+    #[test]
+    fn test_check_runtime_closure_policy() {
+        let license_info = |name: &str, in_runtime_closure: bool| LicenseInfo {
+            in_runtime_closure: Some(in_runtime_closure),
+            ..LicenseInfo::for_test(name, "0.1.0", "MPL-2.0")
+        };
+        let license_infos = LicenseInfos {
+            license_infos: vec![license_info("runtime", true), license_info("tool", false)],
+        };
+        let policy = |name: &str, safe_license: &str| CondaDenyPolicy {
+            name: name.to_string(),
+            osi: false,
+            safe_licenses: vec![Expression::parse(safe_license).unwrap()],
+            safe_license_sources: LicenseSources::new(),
+            warn_licenses: vec![],
+            deny_licenses: vec![],
+            ignore_packages: vec![],
+            environments: vec![],
+            prefixes: vec![],
+        };
+        let mut config = CondaDenyCheckConfig {
+            safe_licenses: vec![Expression::parse("MPL-2.0").unwrap()],
+            policies: vec![policy("strict", "MIT"), policy("lenient", "MPL-2.0")],
+            runtime_closure: Some(RuntimeClosureConfig {
+                of: "default".to_string(),
+                policy: None,
+            }),
+            ..Default::default()
+        };
+        let names = |license_infos: &[LicenseInfo]| -> Vec<String> {
+            license_infos
+                .iter()
+                .map(|license_info| license_info.package_name.clone())
+                .collect()
+        };
+
+        // Without a closure policy, only the closure is checked
+        let check_output = license_infos.check(&config).unwrap();
+        assert_eq!(names(&check_output.safe_dependencies), vec!["runtime"]);
+        assert_eq!(
+            names(&check_output.outside_closure_dependencies),
+            vec!["tool"]
+        );
+
+        // With a closure policy, the closure is checked against it and the rest as usual
+        config.runtime_closure.as_mut().unwrap().policy = Some("strict".to_string());
+        let check_output = license_infos.check(&config).unwrap();
+        assert_eq!(names(&check_output.unsafe_dependencies), vec!["runtime"]);
+        assert_eq!(names(&check_output.safe_dependencies), vec!["tool"]);
+        assert!(check_output.outside_closure_dependencies.is_empty());
+
+        // --policy checks every package against the override
+        config.policy_override = Some("strict".to_string());
+        let check_output = license_infos.check(&config).unwrap();
+        assert_eq!(
+            names(&check_output.unsafe_dependencies),
+            vec!["runtime", "tool"]
+        );
+        assert!(check_output.outside_closure_dependencies.is_empty());
+    }
+
+    #[test]
+    fn test_check_with_warn_licenses() {
+        let license_info = |license: &str| LicenseInfo::for_test(license, "0.1.0", license);
+        let license_infos = LicenseInfos {
+            license_infos: vec![
+                license_info("MIT"),
+                license_info("MIT AND MPL-2.0"),
+                license_info("GPL-3.0-only"),
+            ],
+        };
+
+        let config = CondaDenyCheckConfig {
+            safe_licenses: vec![Expression::parse("MIT").unwrap()],
+            warn_licenses: vec![Expression::parse("MPL-2.0").unwrap()],
+            ..Default::default()
+        };
+
+        let check_output = license_infos.check(&config).unwrap();
+        let names = |infos: &[LicenseInfo]| {
+            infos
+                .iter()
+                .map(|info| info.package_name.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(&check_output.safe_dependencies), vec!["MIT"]);
+        assert_eq!(
+            names(&check_output.warn_dependencies),
+            vec!["MIT AND MPL-2.0"]
+        );
+        assert_eq!(
+            names(&check_output.unsafe_dependencies),
+            vec!["GPL-3.0-only"]
+        );
+    }
+
+    #[test]
+    fn test_deny_licenses_override_safe_licenses() {
+        let license_infos = LicenseInfos {
+            license_infos: vec![
+                LicenseInfo::for_test("archspec", "0.2.3", "MIT OR Apache-2.0"),
+                LicenseInfo::for_test("zlib", "1.3.1", "MIT"),
+                LicenseInfo::for_test("python", "3.12.7", "Python-2.0"),
+            ],
+        };
+        let reasons = |config: &CondaDenyCheckConfig| {
+            let check_output = license_infos.check(config).unwrap();
+            let safe: Vec<_> = check_output
+                .safe_dependencies
+                .iter()
+                .map(|info| info.package_name.as_str())
+                .collect();
+            assert_eq!(safe, vec!["python"]);
+            check_output
+                .unsafe_dependencies
+                .iter()
+                .map(|info| (info.package_name.clone(), info.reason))
+                .collect::<Vec<_>>()
+        };
+        let expected = vec![
+            ("archspec".to_string(), Some(UnsafeReason::Disallowed)),
+            ("zlib".to_string(), Some(UnsafeReason::Denied)),
+        ];
+
+        let config = CondaDenyCheckConfig {
+            safe_licenses: vec![
+                Expression::parse("MIT").unwrap(),
+                Expression::parse("Python-2.0").unwrap(),
+            ],
+            deny_licenses: vec![Expression::parse("MIT").unwrap()],
+            ..Default::default()
+        };
+        assert_eq!(reasons(&config), expected);
+
+        // MIT is OSI approved, but denied nevertheless
+        let osi_config = CondaDenyCheckConfig {
+            osi: true,
+            safe_licenses: vec![],
+            ..config
+        };
+        assert_eq!(reasons(&osi_config), expected);
+    }
+
+    #[test]
+    fn test_check_reasons() {
+        let license_info = |name: &str, license: LicenseState| LicenseInfo {
+            license,
+            ..LicenseInfo::for_test(name, "0.1.0", "")
+        };
+        let license_infos = LicenseInfos {
+            license_infos: vec![
+                license_info(
+                    "denied",
+                    LicenseState::Valid(Expression::parse("MIT AND GPL-3.0-only").unwrap()),
+                ),
+                license_info(
+                    "disallowed",
+                    LicenseState::Valid(Expression::parse("BSD-3-Clause").unwrap()),
+                ),
+                license_info("non-spdx", LicenseState::Invalid("BSD".to_string())),
+                license_info("missing", LicenseState::NoLicense),
+            ],
+        };
+
+        let config = CondaDenyCheckConfig {
+            safe_licenses: vec![
+                Expression::parse("MIT").unwrap(),
+                Expression::parse("GPL-3.0-only").unwrap(),
+            ],
+            deny_licenses: vec![Expression::parse("GPL-3.0-only").unwrap()],
+            reason_actions: ReasonActions {
+                missing: ReasonAction::Warn,
+                non_spdx: ReasonAction::Ignore,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let check_output = license_infos.check(&config).unwrap();
+        let reasons = |infos: &[LicenseInfo]| {
+            infos
+                .iter()
+                .map(|info| (info.package_name.clone(), info.reason))
+                .collect::<Vec<_>>()
+        };
+        assert!(check_output.safe_dependencies.is_empty());
+        assert_eq!(
+            reasons(&check_output.unsafe_dependencies),
+            vec![
+                ("denied".to_string(), Some(UnsafeReason::Denied)),
+                ("disallowed".to_string(), Some(UnsafeReason::Disallowed)),
+            ]
+        );
+        assert_eq!(
+            reasons(&check_output.warn_dependencies),
+            vec![("missing".to_string(), Some(UnsafeReason::Missing))]
+        );
+        assert_eq!(
+            reasons(&check_output.ignored_dependencies),
+            vec![("non-spdx".to_string(), Some(UnsafeReason::NonSpdx))]
+        );
+    }
+
+    #[test]
+    fn test_sort_license_infos() {
+        let license_info1 = LicenseInfo::for_test("test", "0.1.0", "Invalid-MIT");
+        let license_info2 = LicenseInfo::for_test("test2", "0.1.0", "Invalid-MIT");
+
         let mut license_infos = LicenseInfos {
             license_infos: vec![license_info2, license_info1],
         };
@@ -437,22 +1321,8 @@ mod tests {
 
     #[test]
     fn test_dedub_license_infos() {
-        let license_info1 = LicenseInfo {
-            package_name: "test".to_string(),
-            version: Some("0.1.0".to_string()),
-            license: LicenseState::Invalid("Invalid-MIT".to_string()),
-            platform: Some("linux-64".to_string()),
-            build: Some("py_0".to_string()),
-            source_identifier: None,
-        };
-        let license_info2 = LicenseInfo {
-            package_name: "test".to_string(),
-            version: Some("0.1.0".to_string()),
-            license: LicenseState::Invalid("Invalid-MIT".to_string()),
-            platform: Some("linux-64".to_string()),
-            build: Some("py_0".to_string()),
-            source_identifier: None,
-        };
+        let license_info1 = LicenseInfo::for_test("test", "0.1.0", "Invalid-MIT");
+        let license_info2 = LicenseInfo::for_test("test", "0.1.0", "Invalid-MIT");
 
         let mut license_infos = LicenseInfos {
             license_infos: vec![license_info1, license_info2],
@@ -462,4 +1332,69 @@ mod tests {
 
         assert_eq!(license_infos.license_infos.len(), 1);
     }
+
+    #[test]
+    fn test_aggregate_license_infos() {
+        let license_info =
+            |name: &str, platform: &str, build: &str, environment: &str| LicenseInfo {
+                platform: Some(platform.to_string()),
+                build: Some(build.to_string()),
+                found_in: BTreeSet::from([Provenance::for_test(environment)]),
+                ..LicenseInfo::for_test(name, "1.0.8", "MIT")
+            };
+        let aggregated = aggregate_license_infos(&[
+            license_info("bzip2", "linux-64", "h0_5", "default"),
+            license_info("zlib", "linux-64", "h1_0", "default"),
+            license_info("bzip2", "osx-arm64", "h2_5", "lint"),
+        ]);
+
+        assert_eq!(aggregated.len(), 2);
+        assert_eq!(aggregated[0].package_name, "bzip2");
+        assert_eq!(aggregated[0].platform, None);
+        assert_eq!(aggregated[0].build, None);
+        assert_eq!(
+            aggregated[0].platforms_string().as_deref(),
+            Some("linux-64, osx-arm64")
+        );
+        assert_eq!(
+            aggregated[0].environments(),
+            BTreeSet::from(["default".to_string(), "lint".to_string()])
+        );
+        assert_eq!(
+            aggregated[0].found_in_string().as_deref(),
+            Some("pixi.lock: default, lint")
+        );
+        assert_eq!(count_packages(&aggregated), 3);
+        // Entries without duplicates are left as they are
+        assert_eq!(aggregated[1].aggregation, None);
+        assert_eq!(aggregated[1].platform.as_deref(), Some("linux-64"));
+    }
+
+    #[test]
+    fn test_found_in_string() {
+        let license_info = LicenseInfo {
+            found_in: BTreeSet::from([
+                Provenance::for_test("lint"),
+                Provenance::for_test("default"),
+                Provenance {
+                    lockfile: Some("other/pixi.lock".to_string()),
+                    ..Provenance::for_test("default")
+                },
+                Provenance {
+                    lockfile: None,
+                    environment: "/opt/conda".to_string(),
+                    platform: None,
+                },
+            ]),
+            ..LicenseInfo::for_test("bzip2", "1.0.8", "MIT")
+        };
+        assert_eq!(
+            license_info.found_in_string().as_deref(),
+            Some("/opt/conda; other/pixi.lock: default; pixi.lock: default, lint")
+        );
+        assert_eq!(
+            LicenseInfo::for_test("bzip2", "1.0.8", "MIT").found_in_string(),
+            None
+        );
+    }
 }
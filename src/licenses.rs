@@ -0,0 +1,326 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::Write,
+};
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+use serde::Serialize;
+use serde_json::json;
+
+use crate::{
+    collect_license_infos,
+    expression_utils::normalize_expression,
+    json_output::{metadata, write_json_document},
+    license_info::{
+        license_state_from_optional_str, CheckResult, IgnoredPackages, LicenseInfo, LicenseInfos,
+        LicenseState,
+    },
+    markdown::format_license_summaries_markdown,
+    template::render_template,
+    CheckOutput, CondaDenyLicensesConfig, OutputFormat,
+};
+
+/// A package name with all versions that have the license
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LicensePackage {
+    pub name: String,
+    pub versions: BTreeSet<String>,
+    pub verdict: CheckResult,
+}
+
+impl LicensePackage {
+    fn pretty_print(&self) -> String {
+        let versions: Vec<&str> = self.versions.iter().map(String::as_str).collect();
+        match versions.as_slice() {
+            [] => self.name.clone(),
+            [version] => format!("{} {version}", self.name),
+            versions => format!("{} ({})", self.name, versions.join(", ")),
+        }
+    }
+}
+
+/// The packages that use a license and the verdict of the policy for it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LicenseSummary {
+    /// The normalized license expression (or license id), `None` for packages without license
+    pub license: Option<String>,
+    /// The worst verdict of the packages with the license
+    pub verdict: CheckResult,
+    /// Number of package names with the license, across versions and platforms
+    pub count: usize,
+    pub packages: Vec<LicensePackage>,
+}
+
+impl LicenseSummary {
+    pub fn license_or_default(&self) -> &str {
+        self.license.as_deref().unwrap_or("no license")
+    }
+
+    pub fn package_names(&self) -> Vec<String> {
+        self.packages
+            .iter()
+            .map(LicensePackage::pretty_print)
+            .collect()
+    }
+}
+
+/// Orders the verdicts from ignored to unsafe, so that a license gets the worst verdict of its packages
+fn severity(verdict: CheckResult) -> u8 {
+    match verdict {
+        CheckResult::Ignored => 0,
+        CheckResult::Safe => 1,
+        CheckResult::Warn => 2,
+        CheckResult::Unsafe => 3,
+    }
+}
+
+fn worst(a: CheckResult, b: CheckResult) -> CheckResult {
+    if severity(b) > severity(a) {
+        b
+    } else {
+        a
+    }
+}
+
+/// Splits a package into one package per license id of its license expression,
+/// so that each id gets its own verdict.
+fn split_by_id(license_info: &LicenseInfo) -> Vec<LicenseInfo> {
+    let LicenseState::Valid(expression) = &license_info.license else {
+        return vec![license_info.clone()];
+    };
+    let ids: BTreeSet<String> = expression
+        .requirements()
+        .map(|requirement| requirement.req.to_string())
+        .collect();
+    ids.into_iter()
+        .map(|id| LicenseInfo {
+            license: license_state_from_optional_str(Some(&id)),
+            ..license_info.clone()
+        })
+        .collect()
+}
+
+fn group_key(license: &LicenseState) -> Option<String> {
+    match license {
+        LicenseState::Valid(expression) => Some(normalize_expression(expression)),
+        LicenseState::Invalid(license) => Some(license.trim().to_string()),
+        LicenseState::NoLicense => None,
+    }
+}
+
+/// Groups the checked packages by license, most common license first.
+pub fn summarize_licenses(check_output: &CheckOutput) -> Vec<LicenseSummary> {
+    let verdicts = [
+        (&check_output.safe_dependencies, CheckResult::Safe),
+        (&check_output.warn_dependencies, CheckResult::Warn),
+        (&check_output.unsafe_dependencies, CheckResult::Unsafe),
+        (&check_output.ignored_dependencies, CheckResult::Ignored),
+    ];
+
+    let mut groups: BTreeMap<Option<String>, BTreeMap<String, LicensePackage>> = BTreeMap::new();
+    for (license_infos, verdict) in verdicts {
+        for license_info in license_infos {
+            let name = license_info
+                .source_identifier
+                .clone()
+                .unwrap_or_else(|| license_info.package_name.clone());
+            let package = groups
+                .entry(group_key(&license_info.license))
+                .or_default()
+                .entry(name.clone())
+                .or_insert_with(|| LicensePackage {
+                    name,
+                    versions: BTreeSet::new(),
+                    verdict,
+                });
+            package.versions.extend(license_info.version.clone());
+            package.verdict = worst(package.verdict, verdict);
+        }
+    }
+
+    let mut summaries: Vec<LicenseSummary> = groups
+        .into_iter()
+        .map(|(license, packages)| {
+            let packages: Vec<LicensePackage> = packages.into_values().collect();
+            LicenseSummary {
+                license,
+                verdict: packages
+                    .iter()
+                    .map(|package| package.verdict)
+                    .fold(CheckResult::Ignored, worst),
+                count: packages.len(),
+                packages,
+            }
+        })
+        .collect();
+    summaries.sort_by(|a, b| {
+        b.count
+            .cmp(&a.count)
+            .then(a.license.is_none().cmp(&b.license.is_none()))
+            .then(a.license.cmp(&b.license))
+    });
+    summaries
+}
+
+fn pretty_print(summary: &LicenseSummary) -> String {
+    let (icon, license) = match summary.verdict {
+        CheckResult::Safe => ("✅", summary.license_or_default().green()),
+        CheckResult::Warn => ("⚠️", summary.license_or_default().yellow()),
+        CheckResult::Unsafe => ("❌", summary.license_or_default().red()),
+        CheckResult::Ignored => ("⏭️", summary.license_or_default().bright_black()),
+    };
+    let packages = if summary.count == 1 {
+        "package"
+    } else {
+        "packages"
+    };
+    format!(
+        "{icon} {license}: {} {packages}\n    {}\n",
+        summary.count,
+        summary.package_names().join(", ").bright_black()
+    )
+}
+
+pub fn licenses<W: Write>(config: CondaDenyLicensesConfig, mut out: W) -> Result<()> {
+    let check_config = &config.check_config;
+    let mut license_infos = collect_license_infos(
+        check_config.lockfile_or_prefix.clone(),
+        &check_config.ignore_packages,
+        IgnoredPackages::Keep,
+    )
+    .with_context(|| "Fetching license information failed.")?;
+    if config.by_id {
+        license_infos = LicenseInfos {
+            license_infos: license_infos
+                .license_infos
+                .iter()
+                .flat_map(split_by_id)
+                .collect(),
+        };
+    }
+    let check_output = license_infos.check(check_config)?;
+    let summaries = summarize_licenses(&check_output);
+
+    match check_config.output_format {
+        OutputFormat::Default => {
+            let mut output = String::new();
+            for summary in &summaries {
+                output.push_str(&pretty_print(summary));
+            }
+            writeln!(out, "{output}")?;
+        }
+        OutputFormat::Json | OutputFormat::JsonPretty => {
+            write_json_document(
+                &mut out,
+                "licenses",
+                &check_config.lockfile_or_prefix,
+                json!({ "licenses": summaries }),
+                check_config.output_format,
+            )?;
+        }
+        OutputFormat::Markdown => {
+            write!(out, "{}", format_license_summaries_markdown(&summaries))?;
+        }
+        OutputFormat::Template => {
+            let count = |verdict: CheckResult| {
+                summaries
+                    .iter()
+                    .filter(|summary| summary.verdict == verdict)
+                    .count()
+            };
+            let context = json!({
+                "metadata": metadata("licenses", &check_config.lockfile_or_prefix)?,
+                "summary": {
+                    "total": summaries.len(),
+                    "safe": count(CheckResult::Safe),
+                    "warn": count(CheckResult::Warn),
+                    "unsafe": count(CheckResult::Unsafe),
+                    "ignored": count(CheckResult::Ignored),
+                },
+                "licenses": summaries,
+            });
+            write!(
+                out,
+                "{}",
+                render_template(check_config.template.as_deref(), context)?
+            )?;
+        }
+        OutputFormat::Csv => {
+            #[derive(Debug, Serialize)]
+            struct LicenseSummaryRow<'a> {
+                license: Option<&'a str>,
+                verdict: CheckResult,
+                count: usize,
+                packages: String,
+            }
+
+            let mut writer = csv::WriterBuilder::new().from_writer(vec![]);
+            for summary in &summaries {
+                let row = LicenseSummaryRow {
+                    license: summary.license.as_deref(),
+                    verdict: summary.verdict,
+                    count: summary.count,
+                    packages: summary.package_names().join(", "),
+                };
+                writer.serialize(&row).with_context(|| {
+                    format!("Failed to serialize the following license summary to CSV: {row:?}")
+                })?;
+            }
+            out.write_all(&writer.into_inner()?)?;
+        }
+        OutputFormat::Sarif | OutputFormat::Junit => {
+            unreachable!("Unsupported output formats are rejected by get_config_options")
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summarize_licenses() {
+        let check_output = CheckOutput {
+            safe_dependencies: vec![
+                LicenseInfo::for_test("numpy", "1.26.4", "BSD-3-Clause"),
+                LicenseInfo::for_test("numpy", "2.0.0", "BSD-3-Clause"),
+                LicenseInfo::for_test("six", "1.16.0", "MIT OR Apache-2.0"),
+            ],
+            unsafe_dependencies: vec![LicenseInfo::for_test("pandas", "2.2.2", "BSD-3-Clause")],
+            ignored_dependencies: vec![LicenseInfo::for_test("pip", "24.0", "Apache-2.0 OR MIT")],
+            ..Default::default()
+        };
+
+        let summaries = summarize_licenses(&check_output);
+        assert_eq!(summaries.len(), 2);
+
+        // Equivalent expressions are grouped together, licenses with the same count by name
+        assert_eq!(summaries[0].license.as_deref(), Some("Apache-2.0 OR MIT"));
+        assert_eq!(summaries[0].verdict, CheckResult::Safe);
+        assert_eq!(summaries[0].count, 2);
+
+        assert_eq!(summaries[1].license.as_deref(), Some("BSD-3-Clause"));
+        assert_eq!(summaries[1].verdict, CheckResult::Unsafe);
+        assert_eq!(
+            summaries[1].package_names(),
+            vec!["numpy (1.26.4, 2.0.0)", "pandas 2.2.2"]
+        );
+    }
+
+    #[test]
+    fn test_split_by_id() {
+        let license_infos =
+            split_by_id(&LicenseInfo::for_test("six", "1.16.0", "MIT OR Apache-2.0"));
+        let licenses: Vec<Option<String>> = license_infos
+            .iter()
+            .map(|license_info| license_info.license.license_string())
+            .collect();
+        assert_eq!(
+            licenses,
+            vec![Some("Apache-2.0".to_string()), Some("MIT".to_string())]
+        );
+    }
+}
@@ -1,14 +1,151 @@
 use std::io::Write;
 
-use crate::{collect_license_infos, CondaDenyListConfig, OutputFormat};
+use crate::{
+    collect_license_infos,
+    json_output::write_json_document,
+    license_info::{aggregate_license_infos, IgnoredPackages, LicenseInfo},
+    markdown::{format_columns_markdown, format_license_infos_markdown},
+    query::{group_license_infos, sort_license_infos, Field},
+    template::{list_context, render_template},
+    CondaDenyListConfig, OutputFormat,
+};
 use anyhow::{Context, Result};
+use colored::Colorize;
+use serde::Serialize;
+
+/// The value of a field in a column, empty if the package has no value
+fn cell(values: &[String]) -> String {
+    values.join(" ")
+}
+
+/// Writes only the fields of `--columns` for each package.
+fn write_columns<W: Write>(
+    license_infos: &[LicenseInfo],
+    columns: &[Field],
+    config: &CondaDenyListConfig,
+    mut out: W,
+) -> Result<()> {
+    let output_format = config.output_format;
+    let header: Vec<String> = columns.iter().map(Field::to_string).collect();
+    let rows: Vec<Vec<Vec<String>>> = license_infos
+        .iter()
+        .map(|license_info| {
+            columns
+                .iter()
+                .map(|column| column.values(license_info, None))
+                .collect()
+        })
+        .collect();
+
+    match output_format {
+        OutputFormat::Default => {
+            let rows: Vec<Vec<String>> = rows
+                .iter()
+                .map(|row| row.iter().map(|values| cell(values)).collect())
+                .collect();
+            let widths: Vec<usize> = (0..columns.len())
+                .map(|i| {
+                    rows.iter()
+                        .map(|row| row[i].chars().count())
+                        .chain([header[i].len()])
+                        .max()
+                        .unwrap_or_default()
+                })
+                .collect();
+            for row in std::iter::once(&header).chain(&rows) {
+                let cells: Vec<String> = row
+                    .iter()
+                    .zip(&widths)
+                    .map(|(cell, width)| format!("{cell:width$}"))
+                    .collect();
+                writeln!(out, "{}", cells.join("  ").trim_end())?;
+            }
+        }
+        OutputFormat::Json | OutputFormat::JsonPretty => {
+            let license_infos: Vec<serde_json::Map<String, serde_json::Value>> = rows
+                .iter()
+                .map(|row| {
+                    columns
+                        .iter()
+                        .zip(row)
+                        .map(|(column, values)| {
+                            let value = match (column, values.as_slice()) {
+                                (Field::Environment | Field::Lockfile, values) => {
+                                    serde_json::json!(values)
+                                }
+                                (_, [value]) => serde_json::json!(value),
+                                _ => serde_json::Value::Null,
+                            };
+                            (column.to_string(), value)
+                        })
+                        .collect()
+                })
+                .collect();
+            write_json_document(
+                &mut out,
+                "list",
+                &config.lockfile_or_prefix,
+                serde_json::json!({ "rows": license_infos }),
+                output_format,
+            )?;
+        }
+        OutputFormat::Csv => {
+            let mut writer = csv::WriterBuilder::new().from_writer(vec![]);
+            writer.write_record(&header)?;
+            for row in &rows {
+                writer.write_record(row.iter().map(|values| cell(values)))?;
+            }
+            out.write_all(&writer.into_inner()?)?;
+        }
+        OutputFormat::Markdown => {
+            let rows = rows
+                .iter()
+                .map(|row| row.iter().map(|values| cell(values)).collect());
+            write!(out, "{}", format_columns_markdown(&header, rows))?;
+        }
+        OutputFormat::Sarif | OutputFormat::Junit | OutputFormat::Template => {
+            unreachable!("Unsupported output formats and --columns with templates are rejected by get_config_options")
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
 
 pub fn list<W: Write>(config: CondaDenyListConfig, mut out: W) -> Result<()> {
-    let license_infos =
-        collect_license_infos(config.lockfile_or_prefix.clone(), &config.ignore_packages)
-            .with_context(|| "Fetching license information failed.")?;
+    let mut license_infos = collect_license_infos(
+        config.lockfile_or_prefix.clone(),
+        &config.ignore_packages,
+        IgnoredPackages::Skip,
+    )
+    .with_context(|| "Fetching license information failed.")?;
+
+    if let Some(filter) = &config.filter {
+        license_infos
+            .license_infos
+            .retain(|license_info| filter.matches(license_info, None));
+    }
+    sort_license_infos(&mut license_infos.license_infos, &config.sort_by, None);
+    if config.aggregate {
+        license_infos.license_infos = aggregate_license_infos(&license_infos.license_infos);
+    }
+
+    if let Some(columns) = &config.columns {
+        return write_columns(&license_infos.license_infos, columns, &config, out);
+    }
 
     match config.output_format {
+        OutputFormat::Default if !config.group_by.is_empty() => {
+            let groups = group_license_infos(&license_infos.license_infos, &config.group_by);
+            let mut output = String::new();
+            for (group, license_infos) in groups {
+                output.push_str(&format!("{}:\n", group.bold()));
+                for license_info in license_infos {
+                    output.push_str(&license_info.pretty_print());
+                }
+                output.push('\n');
+            }
+            write!(out, "{output}")?;
+        }
         OutputFormat::Default => {
             let mut output = String::new();
             for license_info in &license_infos.license_infos {
@@ -16,17 +153,51 @@ pub fn list<W: Write>(config: CondaDenyListConfig, mut out: W) -> Result<()> {
             }
             writeln!(out, "{output}")?;
         }
-        OutputFormat::Json => {
-            serde_json::to_writer(&mut out, &license_infos)?;
+        OutputFormat::Json | OutputFormat::JsonPretty => {
+            write_json_document(
+                &mut out,
+                "list",
+                &config.lockfile_or_prefix,
+                serde_json::json!({ "packages": license_infos.license_infos }),
+                config.output_format,
+            )?;
+        }
+        OutputFormat::Markdown => {
+            write!(out, "{}", format_license_infos_markdown(&license_infos))?;
         }
-        OutputFormat::JsonPretty => {
-            serde_json::to_writer_pretty(&mut out, &license_infos)?;
+        OutputFormat::Sarif | OutputFormat::Junit => {
+            unreachable!("Unsupported output formats are rejected by get_config_options")
+        }
+        OutputFormat::Template => {
+            let context = list_context(&license_infos, &config.lockfile_or_prefix)?;
+            write!(
+                out,
+                "{}",
+                render_template(config.template.as_deref(), context)?
+            )?;
         }
         OutputFormat::Csv => {
+            #[derive(Debug, Serialize)]
+            struct LicenseInfoRow<'a> {
+                package_name: &'a str,
+                version: Option<&'a str>,
+                license: Option<String>,
+                platform: Option<String>,
+                build: Option<String>,
+            }
+
             let mut writer = csv::WriterBuilder::new().from_writer(vec![]);
 
             for license_info in &license_infos.license_infos {
-                writer.serialize(license_info).with_context(|| {
+                // Aggregated platforms and builds are joined
+                let row = LicenseInfoRow {
+                    package_name: &license_info.package_name,
+                    version: license_info.version.as_deref(),
+                    license: license_info.license.license_string(),
+                    platform: license_info.platforms_string(),
+                    build: license_info.builds_string(),
+                };
+                writer.serialize(&row).with_context(|| {
                     format!("Failed to serialize the following license info: {license_info:?}")
                 })?;
             }
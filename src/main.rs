@@ -12,10 +12,16 @@ use clap_complete::{
 use conda_deny::bundle::bundle;
 use conda_deny::check::check;
 use conda_deny::cli::{Cli, CondaDenyCliConfig};
+use conda_deny::diff::diff;
+use conda_deny::explain::explain;
 use conda_deny::get_config_options;
+use conda_deny::licenses::licenses;
 use conda_deny::list::list;
+use conda_deny::report::report;
+use conda_deny::sbom::sbom;
+use conda_deny::why::why;
 use conda_deny::CondaDenyConfig;
-use log::{debug, info};
+use log::{debug, info, LevelFilter};
 
 fn print_completions(shell: clap_complete::Shell, stdout: &mut dyn Write) -> Result<()> {
     let command = Cli::command();
@@ -58,6 +64,9 @@ fn run() -> Result<()> {
         return print_completions(shell, &mut io::stdout());
     }
 
+    // `-v` and above also explain the decision for each license
+    let explain_check = cli.verbose.log_level_filter() > LevelFilter::Error;
+
     let config = get_config_options(cli.config, cli.command)?;
 
     info!("Parsed config: {config:?}");
@@ -65,9 +74,18 @@ fn run() -> Result<()> {
     let stdout = io::stdout();
 
     match config {
-        CondaDenyConfig::Check(check_config) => check(check_config, stdout),
+        CondaDenyConfig::Check(mut check_config) => {
+            check_config.explain = explain_check;
+            check(check_config, stdout)
+        }
         CondaDenyConfig::List(list_config) => list(list_config, stdout),
+        CondaDenyConfig::Licenses(licenses_config) => licenses(licenses_config, stdout),
         CondaDenyConfig::Bundle(bundle_config) => bundle(bundle_config, stdout),
+        CondaDenyConfig::Explain(explain_config) => explain(explain_config, stdout),
+        CondaDenyConfig::Why(why_config) => why(why_config, stdout),
+        CondaDenyConfig::Diff(diff_config) => diff(diff_config, stdout),
+        CondaDenyConfig::Report(report_config) => report(report_config, stdout),
+        CondaDenyConfig::Sbom(sbom_config) => sbom(sbom_config, stdout),
     }
 }
 
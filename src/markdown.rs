@@ -0,0 +1,245 @@
+use crate::{
+    license_info::{
+        count_packages, packages_per_license, CheckResult, LicenseInfo, LicenseInfos, LicenseState,
+    },
+    licenses::LicenseSummary,
+    CheckOutput, UnsafeReason,
+};
+
+/// Escapes text for use in a table cell.
+fn escape(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', " ")
+}
+
+fn table(header: &[&str], rows: impl IntoIterator<Item = Vec<String>>) -> String {
+    let mut output = format!("| {} |\n", header.join(" | "));
+    output.push_str(&format!("|{}\n", " --- |".repeat(header.len())));
+    for row in rows {
+        let cells: Vec<String> = row.iter().map(|cell| escape(cell)).collect();
+        output.push_str(&format!("| {} |\n", cells.join(" | ")));
+    }
+    output
+}
+
+/// A section that is collapsed by default, to keep pull request comments short
+fn collapsible(summary: &str, body: &str) -> String {
+    format!("<details>\n<summary>{summary}</summary>\n\n{body}\n</details>\n\n")
+}
+
+fn package_table<'a>(license_infos: impl IntoIterator<Item = &'a LicenseInfo>) -> String {
+    table(
+        &["Package", "Version", "Platform", "License", "Reason"],
+        license_infos.into_iter().map(|license_info| {
+            vec![
+                license_info
+                    .source_identifier
+                    .clone()
+                    .unwrap_or_else(|| license_info.package_name.clone()),
+                license_info.version.clone().unwrap_or_default(),
+                license_info
+                    .platforms_string()
+                    .unwrap_or_else(|| "source".to_string()),
+                license_info.license.display_string(),
+                license_info
+                    .reason
+                    .map(|reason| reason.to_string())
+                    .unwrap_or_default(),
+            ]
+        }),
+    )
+}
+
+fn license_counts<'a>(license_infos: impl IntoIterator<Item = &'a LicenseInfo>) -> String {
+    table(
+        &["License", "Packages"],
+        packages_per_license(license_infos)
+            .into_iter()
+            .map(|(license, count)| vec![format!("`{license}`"), count.to_string()]),
+    )
+}
+
+/// Formats the check output as Markdown, e.g. for pull request comments, with the same
+/// summary as the terminal output and the unsafe packages in a collapsible table.
+pub fn format_check_output_markdown(check_output: &CheckOutput) -> String {
+    let mut output = String::new();
+    if check_output.unsafe_dependencies.is_empty() {
+        output.push_str("✅ No unsafe licenses found!\n\n");
+    } else {
+        output.push_str("❌ Unsafe licenses found!\n\n");
+    }
+
+    // Like in the terminal output, the safe and unsafe counts are always shown
+    let counts = [
+        (
+            "✅",
+            count_packages(&check_output.safe_dependencies),
+            "safe licenses",
+            true,
+        ),
+        (
+            "⚠️",
+            count_packages(&check_output.warn_dependencies),
+            "licenses with warnings",
+            false,
+        ),
+        (
+            "❌",
+            count_packages(&check_output.unsafe_dependencies),
+            "unsafe licenses",
+            true,
+        ),
+        (
+            "⏭️",
+            count_packages(&check_output.ignored_dependencies),
+            "ignored licenses",
+            false,
+        ),
+        (
+            "⏭️",
+            count_packages(&check_output.outside_closure_dependencies),
+            "packages outside the runtime closure",
+            false,
+        ),
+        (
+            "📋",
+            count_packages(&check_output.baselined_dependencies),
+            "unsafe licenses known from the baseline",
+            false,
+        ),
+        (
+            "🔄",
+            check_output.license_changes.len(),
+            "license changes",
+            false,
+        ),
+        (
+            "🧩",
+            check_output.inconsistent_licenses.len(),
+            "packages with inconsistent licenses",
+            false,
+        ),
+    ];
+    let counts: Vec<String> = counts
+        .into_iter()
+        .filter(|(_, count, _, always)| *always || *count > 0)
+        .map(|(icon, count, description, _)| format!("- {icon} {count} {description}"))
+        .collect();
+    output.push_str(&counts.join("\n"));
+    output.push_str("\n\n");
+
+    if !check_output.unsafe_dependencies.is_empty() {
+        output.push_str(&collapsible(
+            &format!(
+                "❌ {} unsafe packages",
+                count_packages(&check_output.unsafe_dependencies)
+            ),
+            &package_table(&check_output.unsafe_dependencies),
+        ));
+    }
+    if !check_output.warn_dependencies.is_empty() {
+        output.push_str(&collapsible(
+            &format!(
+                "⚠️ {} packages with warnings",
+                count_packages(&check_output.warn_dependencies)
+            ),
+            &package_table(&check_output.warn_dependencies),
+        ));
+    }
+
+    output.push_str("### Licenses\n\n");
+    output.push_str(&license_counts(check_output.checked_dependencies()));
+    output
+}
+
+/// Formats the license information as Markdown with the packages that have a non-SPDX
+/// or missing license in a collapsible table.
+pub fn format_license_infos_markdown(license_infos: &LicenseInfos) -> String {
+    let mut output = String::from("## conda-deny list\n\n");
+    let license_infos = &license_infos.license_infos;
+    let invalid: Vec<LicenseInfo> = license_infos
+        .iter()
+        .filter_map(|license_info| {
+            let reason = match license_info.license {
+                LicenseState::Valid(_) => return None,
+                LicenseState::Invalid(_) => UnsafeReason::NonSpdx,
+                LicenseState::NoLicense => UnsafeReason::Missing,
+            };
+            let mut license_info = license_info.clone();
+            license_info.reason = Some(reason);
+            Some(license_info)
+        })
+        .collect();
+
+    output.push_str(&format!(
+        "- 📦 {} packages\n- ❌ {} packages with a non-SPDX or missing license\n\n",
+        count_packages(license_infos),
+        count_packages(&invalid)
+    ));
+    if !invalid.is_empty() {
+        output.push_str(&collapsible(
+            &format!(
+                "❌ {} packages with a non-SPDX or missing license",
+                count_packages(&invalid)
+            ),
+            &package_table(&invalid),
+        ));
+    }
+    output.push_str(&collapsible(
+        &format!("📦 {} packages", count_packages(license_infos)),
+        &package_table(license_infos),
+    ));
+
+    output.push_str("### Licenses\n\n");
+    output.push_str(&license_counts(license_infos));
+    output
+}
+
+/// Formats the `--columns` of `list` as a Markdown table.
+pub fn format_columns_markdown(
+    header: &[String],
+    rows: impl IntoIterator<Item = Vec<String>>,
+) -> String {
+    let header: Vec<&str> = header.iter().map(String::as_str).collect();
+    format!("## conda-deny list\n\n{}", table(&header, rows))
+}
+
+/// Formats the license summaries as Markdown with one table row per license.
+pub fn format_license_summaries_markdown(summaries: &[LicenseSummary]) -> String {
+    let mut output = String::from("## conda-deny licenses\n\n");
+    let unsafe_licenses = summaries
+        .iter()
+        .filter(|summary| summary.verdict == CheckResult::Unsafe)
+        .count();
+    output.push_str(&format!(
+        "- 📜 {} licenses\n- ❌ {unsafe_licenses} unsafe licenses\n\n",
+        summaries.len()
+    ));
+    output.push_str(&table(
+        &["License", "Verdict", "Packages", "Package names"],
+        summaries.iter().map(|summary| {
+            vec![
+                format!("`{}`", summary.license_or_default()),
+                summary.verdict.to_string(),
+                summary.count.to_string(),
+                summary.package_names().join(", "),
+            ]
+        }),
+    ));
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table() {
+        assert_eq!(
+            table(
+                &["License", "Packages"],
+                [vec!["MIT | Apache-2.0".to_string(), "2".to_string()]]
+            ),
+            "| License | Packages |\n| --- | --- |\n| MIT \\| Apache-2.0 | 2 |\n"
+        );
+    }
+}
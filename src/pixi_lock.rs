@@ -6,71 +6,157 @@ use log::warn;
 use rattler_conda_types::Platform;
 use rattler_lock::{CondaPackageData, LockFile, LockedPackage, PypiPackageData};
 
-use crate::license_allowlist::{
-    is_package_ignored, is_package_ignored_by_name_only, IgnorePackage,
+use crate::{
+    dependency_graph::{DependencyGraph, EnvironmentGraph, RuntimeClosure},
+    license_allowlist::{is_package_ignored, is_package_ignored_by_name_only, IgnorePackage},
+    license_info::Provenance,
+    pixi_manifest::PixiManifest,
 };
 
-pub fn get_conda_packages_for_pixi_lock(
-    pixi_lock_path: &Path,
+/// The conda packages of one environment on one platform of a lockfile
+pub type LockedPackages = (Provenance, Vec<CondaPackageData>);
+
+pub fn read_pixi_lock(pixi_lock_path: &Path) -> Result<LockFile> {
+    LockFile::from_path(pixi_lock_path)
+        .with_context(|| format!("Failed to read pixi.lock file: {pixi_lock_path:?}"))
+}
+
+/// Collects the conda packages of every selected environment and platform of a lockfile.
+///
+/// `lockfile` names the lockfile in the provenance of the packages.
+pub fn get_locked_conda_packages(
+    lock_file: &LockFile,
+    lockfile: &str,
     environment_spec: &Option<Vec<String>>,
     platform_spec: &Option<Vec<Platform>>,
     ignore_pypi: bool,
     ignore_packages: &[IgnorePackage],
-) -> Result<Vec<CondaPackageData>> {
-    let lock_file = LockFile::from_path(pixi_lock_path)
-        .with_context(|| format!("Failed to read pixi.lock file: {pixi_lock_path:?}"))?;
+) -> Result<Vec<LockedPackages>> {
     let environment_spec = environment_spec
         .clone()
-        .unwrap_or_else(|| _get_environment_names(&lock_file));
+        .unwrap_or_else(|| _get_environment_names(lock_file));
     let mut package_records = Vec::new();
 
     for environment_name in environment_spec {
-        package_records.extend(collect_conda_packages_for_environment(
-            &lock_file,
-            &environment_name,
-            platform_spec,
-            ignore_pypi,
-            ignore_packages,
-        )?);
+        let environment = lock_file.environment(&environment_name).ok_or_else(|| {
+            anyhow::anyhow!("Environment not found in lock file: {}", environment_name)
+        })?;
+
+        for platform in environment.platforms() {
+            if !platform_matches(platform.subdir(), platform_spec) {
+                continue;
+            }
+
+            let Some(packages) = environment.packages(platform) else {
+                continue;
+            };
+
+            let mut platform_records = Vec::new();
+            for package in packages {
+                match package {
+                    LockedPackage::Conda(conda_package) => {
+                        platform_records.push(conda_package.to_owned());
+                    }
+                    LockedPackage::Pypi(package_data) => {
+                        ignore_or_reject_pypi_package(package_data, ignore_pypi, ignore_packages)?;
+                    }
+                }
+            }
+            let provenance = Provenance {
+                lockfile: Some(lockfile.to_string()),
+                environment: environment_name.clone(),
+                platform: Some(platform.subdir().to_string()),
+            };
+            package_records.push((provenance, platform_records));
+        }
     }
 
     Ok(package_records)
 }
 
-fn collect_conda_packages_for_environment(
-    lock_file: &LockFile,
-    environment_name: &str,
+/// Builds the dependency graph of the conda packages for every environment and platform.
+///
+/// Direct dependencies are taken from the pixi manifest next to the lockfile, if there is one.
+pub fn get_dependency_graphs_for_pixi_lock(
+    pixi_lock_path: &Path,
+    environment_spec: &Option<Vec<String>>,
     platform_spec: &Option<Vec<Platform>>,
-    ignore_pypi: bool,
-    ignore_packages: &[IgnorePackage],
-) -> Result<Vec<CondaPackageData>> {
-    let environment = lock_file.environment(environment_name).ok_or_else(|| {
-        anyhow::anyhow!("Environment not found in lock file: {}", environment_name)
-    })?;
-    let mut package_records = Vec::new();
-
-    for platform in environment.platforms() {
-        if !platform_matches(platform.subdir(), platform_spec) {
-            continue;
-        }
+) -> Result<Vec<EnvironmentGraph>> {
+    let lock_file = read_pixi_lock(pixi_lock_path)?;
+    let manifest = PixiManifest::for_lockfile(pixi_lock_path)?;
+    let environment_spec = environment_spec
+        .clone()
+        .unwrap_or_else(|| _get_environment_names(&lock_file));
+    let mut graphs = Vec::new();
 
-        let Some(packages) = environment.packages(platform) else {
-            continue;
-        };
+    for environment_name in environment_spec {
+        let environment = lock_file.environment(&environment_name).ok_or_else(|| {
+            anyhow::anyhow!("Environment not found in lock file: {}", environment_name)
+        })?;
 
-        for package in packages {
-            match package {
-                LockedPackage::Conda(conda_package) => {
-                    package_records.push(conda_package.to_owned());
-                }
-                LockedPackage::Pypi(package_data) => {
-                    ignore_or_reject_pypi_package(package_data, ignore_pypi, ignore_packages)?;
-                }
+        for platform in environment.platforms() {
+            let subdir = platform.subdir();
+            if !platform_matches(subdir, platform_spec) {
+                continue;
             }
+            let Some(packages) = environment.conda_packages(platform) else {
+                continue;
+            };
+
+            let direct_dependencies = manifest
+                .as_ref()
+                .map(|manifest| manifest.direct_dependencies(&environment_name, subdir));
+            let graph = DependencyGraph::new(
+                packages.map(|package| {
+                    let depends = package
+                        .record()
+                        .map(|record| record.depends.as_slice())
+                        .unwrap_or_default();
+                    (package.name().as_source().to_string(), depends)
+                }),
+                direct_dependencies,
+            );
+            graphs.push(EnvironmentGraph {
+                lockfile: Some(pixi_lock_path.display().to_string()),
+                environment: environment_name.clone(),
+                platform: Some(subdir),
+                graph,
+            });
         }
     }
 
-    Ok(package_records)
+    Ok(graphs)
+}
+
+/// Computes the runtime closure of the direct dependencies of a pixi feature (or environment)
+/// for every selected environment and platform of the lockfile.
+pub fn get_runtime_closures_for_pixi_lock(
+    pixi_lock_path: &Path,
+    environment_spec: &Option<Vec<String>>,
+    platform_spec: &Option<Vec<Platform>>,
+    of: &str,
+) -> Result<Vec<RuntimeClosure>> {
+    let manifest = PixiManifest::for_lockfile(pixi_lock_path)?.ok_or_else(|| {
+        anyhow::anyhow!(
+            "No pixi manifest found next to {pixi_lock_path:?}, which is needed to compute the runtime closure"
+        )
+    })?;
+
+    get_dependency_graphs_for_pixi_lock(pixi_lock_path, environment_spec, platform_spec)?
+        .into_iter()
+        .map(|environment_graph| {
+            let platform = environment_graph
+                .platform
+                .expect("Lockfile graphs always have a platform");
+            let roots = manifest.direct_dependencies_of(of, platform)?;
+            Ok(RuntimeClosure {
+                packages: environment_graph.graph.closure(&roots),
+                lockfile: environment_graph.lockfile,
+                environment: environment_graph.environment,
+                platform: Some(platform),
+            })
+        })
+        .collect()
 }
 
 fn ignore_or_reject_pypi_package(
@@ -136,41 +222,72 @@ mod tests {
 
     use super::*;
 
-    #[test]
-    fn test_get_packages_for_pixi_lock() {
-        let path = Path::new("tests/default_pixi.lock");
-        let package_records = get_conda_packages_for_pixi_lock(path, &None, &None, false, &[]);
-        assert_eq!(package_records.unwrap().len(), 758);
-
-        let package_records = get_conda_packages_for_pixi_lock(
+    fn count_packages(
+        path: &str,
+        environment_spec: Option<Vec<String>>,
+        platform_spec: Option<Vec<Platform>>,
+    ) -> usize {
+        let lock_file = read_pixi_lock(Path::new(path)).unwrap();
+        get_locked_conda_packages(
+            &lock_file,
             path,
-            &Some(vec!["lint".to_string()]),
-            &None,
+            &environment_spec,
+            &platform_spec,
             false,
             &[],
-        );
-        assert_eq!(package_records.unwrap().len(), 219);
+        )
+        .unwrap()
+        .iter()
+        .map(|(_, packages)| packages.len())
+        .sum()
+    }
 
-        let package_records = get_conda_packages_for_pixi_lock(
-            path,
-            &Some(vec!["lint".to_string()]),
-            &Some(vec![Platform::Linux64]),
-            false,
-            &[],
+    #[test]
+    fn test_get_packages_for_pixi_lock() {
+        let path = "tests/default_pixi.lock";
+        assert_eq!(count_packages(path, None, None), 758);
+        assert_eq!(
+            count_packages(path, Some(vec!["lint".to_string()]), None),
+            219
+        );
+        assert_eq!(
+            count_packages(
+                path,
+                Some(vec!["lint".to_string()]),
+                Some(vec![Platform::Linux64])
+            ),
+            48
         );
-        assert_eq!(package_records.unwrap().len(), 48);
 
-        let path = Path::new("tests/pixi-build/pixi.lock");
-        let package_records = get_conda_packages_for_pixi_lock(path, &None, &None, false, &[]);
-        assert_eq!(package_records.unwrap().len(), 89);
+        let path = "tests/pixi-build/pixi.lock";
+        assert_eq!(count_packages(path, None, None), 89);
+        assert_eq!(
+            count_packages(path, None, Some(vec![Platform::Linux64])),
+            22
+        );
+    }
 
-        let package_records = get_conda_packages_for_pixi_lock(
-            path,
-            &None,
+    #[test]
+    fn test_get_locked_conda_packages_provenance() {
+        let lock_file = read_pixi_lock(Path::new("tests/default_pixi.lock")).unwrap();
+        let locked_packages = get_locked_conda_packages(
+            &lock_file,
+            "pixi.lock",
+            &Some(vec!["lint".to_string()]),
             &Some(vec![Platform::Linux64]),
             false,
             &[],
+        )
+        .unwrap();
+
+        assert_eq!(locked_packages.len(), 1);
+        assert_eq!(
+            locked_packages[0].0,
+            Provenance {
+                lockfile: Some("pixi.lock".to_string()),
+                environment: "lint".to_string(),
+                platform: Some("linux-64".to_string()),
+            }
         );
-        assert_eq!(package_records.unwrap().len(), 22);
     }
 }
@@ -0,0 +1,227 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+};
+
+use anyhow::{Context, Result};
+use rattler_conda_types::Platform;
+use serde::Deserialize;
+
+const DEFAULT_FEATURE_NAME: &str = "default";
+
+/// The parts of a pixi manifest (`pixi.toml` or `[tool.pixi]` in `pyproject.toml`)
+/// that determine the direct dependencies of an environment
+#[derive(Debug, Clone, Deserialize)]
+pub struct PixiManifest {
+    #[serde(flatten)]
+    default_feature: FeatureManifest,
+    #[serde(default)]
+    feature: BTreeMap<String, FeatureManifest>,
+    #[serde(default)]
+    environments: BTreeMap<String, EnvironmentManifest>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+struct FeatureManifest {
+    #[serde(default)]
+    dependencies: BTreeMap<String, toml::Value>,
+    #[serde(default)]
+    target: BTreeMap<String, TargetManifest>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+struct TargetManifest {
+    #[serde(default)]
+    dependencies: BTreeMap<String, toml::Value>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum EnvironmentManifest {
+    Features(Vec<String>),
+    Table {
+        #[serde(default)]
+        features: Vec<String>,
+        #[serde(default, rename = "no-default-feature")]
+        no_default_feature: bool,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+struct PyProject {
+    tool: Option<PyProjectTool>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PyProjectTool {
+    pixi: Option<PixiManifest>,
+}
+
+impl PixiManifest {
+    /// Reads a pixi manifest, returning `None` for a `pyproject.toml` without `[tool.pixi]`.
+    pub fn from_path(path: &Path) -> Result<Option<Self>> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read pixi manifest: {path:?}"))?;
+
+        if path
+            .file_name()
+            .is_some_and(|name| name == "pyproject.toml")
+        {
+            let pyproject: PyProject = toml::from_str(&content)
+                .with_context(|| format!("Failed to parse pixi manifest: {path:?}"))?;
+            Ok(pyproject.tool.and_then(|tool| tool.pixi))
+        } else {
+            toml::from_str(&content)
+                .map(Some)
+                .with_context(|| format!("Failed to parse pixi manifest: {path:?}"))
+        }
+    }
+
+    /// Reads the `pixi.toml` or `pyproject.toml` next to a lockfile, if there is one.
+    pub fn for_lockfile(lockfile: &Path) -> Result<Option<Self>> {
+        let directory = lockfile.parent().unwrap_or(Path::new("."));
+        for manifest_name in ["pixi.toml", "pyproject.toml"] {
+            let manifest_path = directory.join(manifest_name);
+            if manifest_path.is_file() {
+                return Self::from_path(&manifest_path);
+            }
+        }
+        Ok(None)
+    }
+
+    fn features_of_environment(&self, environment: &str) -> Vec<&FeatureManifest> {
+        let (feature_names, no_default_feature) = match self.environments.get(environment) {
+            Some(EnvironmentManifest::Features(features)) => (features.as_slice(), false),
+            Some(EnvironmentManifest::Table {
+                features,
+                no_default_feature,
+            }) => (features.as_slice(), *no_default_feature),
+            None => (&[] as &[String], false),
+        };
+
+        let mut features = Vec::new();
+        if !no_default_feature {
+            features.push(&self.default_feature);
+        }
+        features.extend(
+            feature_names
+                .iter()
+                .filter(|name| name.as_str() != DEFAULT_FEATURE_NAME)
+                .filter_map(|name| self.feature.get(name)),
+        );
+        features
+    }
+
+    /// Returns the names of the conda packages that the environment depends on directly.
+    pub fn direct_dependencies(&self, environment: &str, platform: Platform) -> BTreeSet<String> {
+        self.features_of_environment(environment)
+            .into_iter()
+            .flat_map(|feature| feature.dependencies(platform))
+            .collect()
+    }
+
+    /// Returns the direct dependencies of a feature, or of an environment if there is no
+    /// feature with that name.
+    pub fn direct_dependencies_of(
+        &self,
+        name: &str,
+        platform: Platform,
+    ) -> Result<BTreeSet<String>> {
+        if name == DEFAULT_FEATURE_NAME {
+            return Ok(self.default_feature.dependencies(platform).collect());
+        }
+        if let Some(feature) = self.feature.get(name) {
+            return Ok(feature.dependencies(platform).collect());
+        }
+        if self.environments.contains_key(name) {
+            return Ok(self.direct_dependencies(name, platform));
+        }
+        Err(anyhow::anyhow!(
+            "No feature or environment named {name} in the pixi manifest"
+        ))
+    }
+}
+
+impl FeatureManifest {
+    fn dependencies(&self, platform: Platform) -> impl Iterator<Item = String> + '_ {
+        self.dependencies.keys().cloned().chain(
+            self.target
+                .iter()
+                .filter(move |(selector, _)| target_matches(selector, platform))
+                .flat_map(|(_, target)| target.dependencies.keys().cloned()),
+        )
+    }
+}
+
+fn target_matches(selector: &str, platform: Platform) -> bool {
+    match selector {
+        "unix" => platform.is_unix(),
+        "win" => platform.is_windows(),
+        "linux" => platform.is_linux(),
+        "osx" => platform.is_osx(),
+        _ => selector == platform.as_str(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_direct_dependencies() {
+        let manifest = PixiManifest::from_path(Path::new("tests/test_default_use_case/pixi.toml"))
+            .unwrap()
+            .unwrap();
+
+        let default = manifest.direct_dependencies("default", Platform::Linux64);
+        assert!(default.contains("openssl"));
+        assert!(default.contains("compilers"));
+        assert!(default.contains("pre-commit"));
+        assert!(default.contains("conda"));
+
+        let default_osx = manifest.direct_dependencies("default", Platform::OsxArm64);
+        assert!(!default_osx.contains("compilers"));
+
+        let lint = manifest.direct_dependencies("lint", Platform::Linux64);
+        assert!(lint.contains("pre-commit"));
+        assert!(!lint.contains("openssl"));
+    }
+
+    #[test]
+    fn test_direct_dependencies_of_feature() {
+        let manifest = PixiManifest::from_path(Path::new("tests/test_default_use_case/pixi.toml"))
+            .unwrap()
+            .unwrap();
+
+        let lint = manifest
+            .direct_dependencies_of("lint", Platform::Linux64)
+            .unwrap();
+        assert_eq!(
+            lint,
+            manifest.direct_dependencies("lint", Platform::Linux64)
+        );
+
+        let default = manifest
+            .direct_dependencies_of("default", Platform::Linux64)
+            .unwrap();
+        assert!(default.contains("openssl"));
+        assert!(default.contains("compilers"));
+
+        assert!(manifest
+            .direct_dependencies_of("does-not-exist", Platform::Linux64)
+            .is_err());
+    }
+
+    #[test]
+    fn test_manifest_for_lockfile_in_pyproject() {
+        let manifest = PixiManifest::for_lockfile(Path::new(
+            "tests/test_default_use_case_pyproject/pixi.lock",
+        ))
+        .unwrap();
+        assert!(manifest.is_none());
+
+        let manifest =
+            PixiManifest::for_lockfile(Path::new("tests/test_default_use_case/pixi.lock")).unwrap();
+        assert!(manifest.is_some());
+    }
+}
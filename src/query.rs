@@ -0,0 +1,569 @@
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    str::FromStr,
+};
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use glob::Pattern;
+use rattler_conda_types::Version;
+
+use crate::{
+    license_info::{aggregate_license_infos, LicenseInfo},
+    CheckOutput,
+};
+
+/// A field of a package that can be filtered, sorted by or shown as a column
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Field {
+    Name,
+    Version,
+    Build,
+    Platform,
+    License,
+    /// Whether the license is a valid SPDX expression: `valid`, `invalid` or `missing`
+    State,
+    Policy,
+    Reason,
+    Environment,
+    /// The path of the lockfile the package was found in
+    Lockfile,
+    /// The identifier of a source package
+    Source,
+    DependencyType,
+    /// Where `check` put the package: `safe`, `warn`, `unsafe`, `ignored`, `baselined` or `outside-closure`
+    Verdict,
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = self
+            .to_possible_value()
+            .expect("no field is skipped as a value");
+        write!(f, "{}", value.get_name())
+    }
+}
+
+impl Field {
+    /// The values of the field for a package, with the verdict of `check` if known.
+    ///
+    /// Only `environment` and `lockfile` can have several values.
+    pub fn values(self, license_info: &LicenseInfo, verdict: Option<&str>) -> Vec<String> {
+        let value = match self {
+            Field::Name => Some(license_info.package_name.clone()),
+            Field::Version => license_info.version.clone(),
+            Field::Build => license_info.builds_string(),
+            Field::Platform => license_info.platforms_string(),
+            Field::License => license_info.license.license_string(),
+            Field::State => Some(license_info.license.state().to_string()),
+            Field::Policy => license_info.policy.clone(),
+            Field::Reason => license_info.reason.map(|reason| reason.to_string()),
+            Field::Environment => return license_info.environments().into_iter().collect(),
+            Field::Lockfile => {
+                let lockfiles: BTreeSet<&String> = license_info
+                    .found_in
+                    .iter()
+                    .filter_map(|provenance| provenance.lockfile.as_ref())
+                    .collect();
+                return lockfiles.into_iter().cloned().collect();
+            }
+            Field::Source => license_info.source_identifier.clone(),
+            Field::DependencyType => license_info.dependency_type.map(|dependency_type| {
+                serde_json::to_value(dependency_type)
+                    .ok()
+                    .and_then(|value| value.as_str().map(str::to_string))
+                    .unwrap_or_default()
+            }),
+            Field::Verdict => verdict.map(str::to_string),
+        };
+        value.into_iter().collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operator {
+    /// Matches a glob pattern
+    Matches(Pattern),
+    NotMatches(Pattern),
+    /// Contains the text, ignoring case
+    Contains(String),
+    NotContains(String),
+}
+
+/// A `--filter` expression, e.g. `license~GPL and not platform=win-*`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    Condition { field: Field, operator: Operator },
+    Not(Box<Filter>),
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+}
+
+impl Filter {
+    /// Whether the package matches the filter, with the verdict of `check` if known.
+    pub fn matches(&self, license_info: &LicenseInfo, verdict: Option<&str>) -> bool {
+        match self {
+            Filter::Condition { field, operator } => {
+                let values = field.values(license_info, verdict);
+                let contains = |text: &str| {
+                    let text = text.to_lowercase();
+                    values
+                        .iter()
+                        .any(|value| value.to_lowercase().contains(&text))
+                };
+                match operator {
+                    Operator::Matches(pattern) => values.iter().any(|value| pattern.matches(value)),
+                    Operator::NotMatches(pattern) => {
+                        !values.iter().any(|value| pattern.matches(value))
+                    }
+                    Operator::Contains(text) => contains(text),
+                    Operator::NotContains(text) => !contains(text),
+                }
+            }
+            Filter::Not(filter) => !filter.matches(license_info, verdict),
+            Filter::And(left, right) => {
+                left.matches(license_info, verdict) && right.matches(license_info, verdict)
+            }
+            Filter::Or(left, right) => {
+                left.matches(license_info, verdict) || right.matches(license_info, verdict)
+            }
+        }
+    }
+
+    /// Whether the filter uses the field, e.g. to reject `verdict` outside of `check`.
+    pub fn uses(&self, field: Field) -> bool {
+        match self {
+            Filter::Condition { field: used, .. } => *used == field,
+            Filter::Not(filter) => filter.uses(field),
+            Filter::And(left, right) | Filter::Or(left, right) => {
+                left.uses(field) || right.uses(field)
+            }
+        }
+    }
+
+    /// Combines the filters of several `--filter` options, which all have to match.
+    pub fn all(filters: Vec<Filter>) -> Option<Filter> {
+        filters
+            .into_iter()
+            .reduce(|left, right| Filter::And(Box::new(left), Box::new(right)))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    /// A keyword or condition, `quoted` if any part of it was in quotes
+    Word {
+        text: String,
+        quoted: bool,
+    },
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::Open);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::Close);
+            }
+            _ => {
+                let mut text = String::new();
+                let mut quoted = false;
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == '(' || c == ')' {
+                        break;
+                    }
+                    chars.next();
+                    if c == '"' || c == '\'' {
+                        quoted = true;
+                        loop {
+                            match chars.next() {
+                                Some(end) if end == c => break,
+                                Some(inner) => text.push(inner),
+                                None => anyhow::bail!("Unterminated quote in filter: {input}"),
+                            }
+                        }
+                    } else {
+                        text.push(c);
+                    }
+                }
+                tokens.push(Token::Word { text, quoted });
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+fn is_keyword(token: Option<&Token>, keyword: &str) -> bool {
+    matches!(token, Some(Token::Word { text, quoted: false }) if text.eq_ignore_ascii_case(keyword))
+}
+
+fn parse_condition(text: &str) -> Result<Filter> {
+    let position = text
+        .find(['=', '~'])
+        .with_context(|| format!("Expected a condition like `name=value`, got `{text}`"))?;
+    let negated = text[..position].ends_with('!');
+    let field = &text[..position - usize::from(negated)];
+    let value = &text[position + 1..];
+
+    let field = Field::from_str(field.trim(), true).map_err(|_| {
+        let fields: Vec<String> = Field::value_variants()
+            .iter()
+            .map(Field::to_string)
+            .collect();
+        anyhow::anyhow!(
+            "Unknown field `{field}` in filter, expected one of: {}",
+            fields.join(", ")
+        )
+    })?;
+    let operator = if text[position..].starts_with('~') {
+        let value = value.to_string();
+        if negated {
+            Operator::NotContains(value)
+        } else {
+            Operator::Contains(value)
+        }
+    } else {
+        let pattern =
+            Pattern::new(value).with_context(|| format!("Invalid pattern `{value}` in filter"))?;
+        if negated {
+            Operator::NotMatches(pattern)
+        } else {
+            Operator::Matches(pattern)
+        }
+    };
+    Ok(Filter::Condition { field, operator })
+}
+
+/// Recursive descent parser, `not` binds stronger than `and`, which binds stronger than `or`.
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn or(&mut self) -> Result<Filter> {
+        let mut filter = self.and()?;
+        while is_keyword(self.peek(), "or") {
+            self.next();
+            filter = Filter::Or(Box::new(filter), Box::new(self.and()?));
+        }
+        Ok(filter)
+    }
+
+    fn and(&mut self) -> Result<Filter> {
+        let mut filter = self.not()?;
+        while is_keyword(self.peek(), "and") {
+            self.next();
+            filter = Filter::And(Box::new(filter), Box::new(self.not()?));
+        }
+        Ok(filter)
+    }
+
+    fn not(&mut self) -> Result<Filter> {
+        if is_keyword(self.peek(), "not") {
+            self.next();
+            return Ok(Filter::Not(Box::new(self.not()?)));
+        }
+        match self.next() {
+            Some(Token::Open) => {
+                let filter = self.or()?;
+                anyhow::ensure!(
+                    self.next() == Some(Token::Close),
+                    "Missing closing parenthesis in filter"
+                );
+                Ok(filter)
+            }
+            Some(Token::Word { text, .. }) => parse_condition(&text),
+            Some(Token::Close) => anyhow::bail!("Unexpected closing parenthesis in filter"),
+            None => anyhow::bail!("Unexpected end of filter"),
+        }
+    }
+}
+
+impl FromStr for Filter {
+    type Err = anyhow::Error;
+
+    fn from_str(input: &str) -> Result<Self> {
+        let mut parser = Parser {
+            tokens: tokenize(input)?,
+            position: 0,
+        };
+        let filter = parser.or()?;
+        if let Some(token) = parser.peek() {
+            anyhow::bail!("Unexpected {token:?} in filter, expected `and` or `or`");
+        }
+        Ok(filter)
+    }
+}
+
+fn compare_values(field: Field, a: &[String], b: &[String]) -> Ordering {
+    if field == Field::Version
+        && let ([a], [b]) = (a, b)
+        && let (Ok(a), Ok(b)) = (Version::from_str(a), Version::from_str(b))
+    {
+        return a.cmp(&b);
+    }
+    a.cmp(b)
+}
+
+/// Sorts the packages by the fields, the first field first. Packages without a value come first.
+pub fn sort_license_infos(
+    license_infos: &mut [LicenseInfo],
+    sort_by: &[Field],
+    verdict: Option<&str>,
+) {
+    if sort_by.is_empty() {
+        return;
+    }
+    license_infos.sort_by(|a, b| {
+        sort_by
+            .iter()
+            .map(|field| {
+                compare_values(*field, &field.values(a, verdict), &field.values(b, verdict))
+            })
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal)
+    });
+}
+
+/// The packages of the check output with the verdict each of them got
+fn check_output_buckets(check_output: &mut CheckOutput) -> [(&mut Vec<LicenseInfo>, &str); 6] {
+    [
+        (&mut check_output.safe_dependencies, "safe"),
+        (&mut check_output.warn_dependencies, "warn"),
+        (&mut check_output.unsafe_dependencies, "unsafe"),
+        (&mut check_output.ignored_dependencies, "ignored"),
+        (&mut check_output.baselined_dependencies, "baselined"),
+        (
+            &mut check_output.outside_closure_dependencies,
+            "outside-closure",
+        ),
+    ]
+}
+
+/// Keeps only the packages of the check output that match the filter.
+pub fn filter_check_output(check_output: &mut CheckOutput, filter: &Filter) {
+    for (license_infos, verdict) in check_output_buckets(check_output) {
+        license_infos.retain(|license_info| filter.matches(license_info, Some(verdict)));
+    }
+}
+
+pub fn sort_check_output(check_output: &mut CheckOutput, sort_by: &[Field]) {
+    for (license_infos, verdict) in check_output_buckets(check_output) {
+        sort_license_infos(license_infos, sort_by, Some(verdict));
+    }
+}
+
+/// Merges the entries of each verdict across platforms, see [`aggregate_license_infos`].
+pub fn aggregate_check_output(check_output: &mut CheckOutput) {
+    for (license_infos, _) in check_output_buckets(check_output) {
+        *license_infos = aggregate_license_infos(license_infos);
+    }
+}
+
+/// Where a package was found, to group the output by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum GroupBy {
+    Lockfile,
+    Environment,
+    Platform,
+}
+
+/// The groups of a package, e.g. `pixi.lock › default` when grouping by lockfile and environment.
+fn group_names(license_info: &LicenseInfo, group_by: &[GroupBy]) -> BTreeSet<String> {
+    license_info
+        .found_in
+        .iter()
+        .map(|provenance| {
+            let parts: Vec<&str> = group_by
+                .iter()
+                .map(|group| match group {
+                    GroupBy::Lockfile => provenance.lockfile.as_deref().unwrap_or("conda prefix"),
+                    GroupBy::Environment => &provenance.environment,
+                    GroupBy::Platform => provenance.platform.as_deref().unwrap_or("unknown"),
+                })
+                .collect();
+            parts.join(" › ")
+        })
+        .collect()
+}
+
+/// Splits the packages into groups by where they were found, sorted by group name.
+///
+/// A package that was found in several groups is part of each of them.
+pub fn group_license_infos<'a>(
+    license_infos: &'a [LicenseInfo],
+    group_by: &[GroupBy],
+) -> BTreeMap<String, Vec<&'a LicenseInfo>> {
+    let mut groups: BTreeMap<String, Vec<&LicenseInfo>> = BTreeMap::new();
+    for license_info in license_infos {
+        for group in group_names(license_info, group_by) {
+            groups.entry(group).or_default().push(license_info);
+        }
+    }
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+    use crate::license_info::Provenance;
+
+    fn license_info(name: &str, version: &str, license: &str) -> LicenseInfo {
+        LicenseInfo {
+            found_in: BTreeSet::from([
+                Provenance::for_test("default"),
+                Provenance::for_test("lint"),
+            ]),
+            ..LicenseInfo::for_test(name, version, license)
+        }
+    }
+
+    #[test]
+    fn test_filter_matches() {
+        let readline = license_info("readline", "8.2", "GPL-3.0-only");
+        let libgcc = license_info("libgcc", "14.1.0", "GPL-3.0-only WITH GCC-exception-3.1");
+        let xz = license_info("xz", "5.2.6", "LGPL-2.1 and GPL-2.0");
+
+        let matching = |filter: &str| -> Vec<&str> {
+            let filter: Filter = filter.parse().unwrap();
+            [&readline, &libgcc, &xz]
+                .into_iter()
+                .filter(|license_info| filter.matches(license_info, Some("unsafe")))
+                .map(|license_info| license_info.package_name.as_str())
+                .collect()
+        };
+
+        assert_eq!(matching("license~gpl"), vec!["readline", "libgcc", "xz"]);
+        assert_eq!(matching("name=lib*"), vec!["libgcc"]);
+        assert_eq!(matching("state=invalid"), vec!["xz"]);
+        assert_eq!(matching("license='GPL-3.0-only'"), vec!["readline"]);
+        assert_eq!(matching("name!=lib* and license!~lgpl"), vec!["readline"]);
+        assert_eq!(
+            matching("name=xz or (license~GCC and not version=8.*)"),
+            vec!["libgcc", "xz"]
+        );
+        assert_eq!(matching("environment=lint and verdict=unsafe").len(), 3);
+        assert!(matching("platform=osx-*").is_empty());
+    }
+
+    #[test]
+    fn test_filter_precedence() {
+        let filter: Filter = "name=a or name=b and not name=c".parse().unwrap();
+        let condition = |value: &str| Filter::Condition {
+            field: Field::Name,
+            operator: Operator::Matches(Pattern::new(value).unwrap()),
+        };
+        assert_eq!(
+            filter,
+            Filter::Or(
+                Box::new(condition("a")),
+                Box::new(Filter::And(
+                    Box::new(condition("b")),
+                    Box::new(Filter::Not(Box::new(condition("c"))))
+                ))
+            )
+        );
+    }
+
+    #[test]
+    fn test_filter_errors() {
+        for (filter, error) in [
+            ("", "Unexpected end of filter"),
+            ("name", "Expected a condition like `name=value`, got `name`"),
+            ("colour=red", "Unknown field `colour` in filter"),
+            ("(name=a", "Missing closing parenthesis in filter"),
+            ("name=a name=b", "expected `and` or `or`"),
+            ("license='MIT", "Unterminated quote in filter"),
+        ] {
+            let message = filter.parse::<Filter>().unwrap_err().to_string();
+            assert!(message.contains(error), "{filter}: {message}");
+        }
+    }
+
+    #[test]
+    fn test_sort_license_infos() {
+        let mut license_infos = vec![
+            license_info("numpy", "1.10.0", "BSD-3-Clause"),
+            license_info("numpy", "1.9.0", "BSD-3-Clause"),
+            license_info("attrs", "24.2.0", "MIT"),
+        ];
+
+        sort_license_infos(&mut license_infos, &[Field::License, Field::Version], None);
+        let versions: Vec<_> = license_infos
+            .iter()
+            .map(|license_info| license_info.version.as_deref().unwrap())
+            .collect();
+        assert_eq!(versions, vec!["1.9.0", "1.10.0", "24.2.0"]);
+    }
+
+    #[test]
+    fn test_group_license_infos() {
+        let provenance = |lockfile: &str, environment: &str, platform: &str| Provenance {
+            lockfile: Some(lockfile.to_string()),
+            environment: environment.to_string(),
+            platform: Some(platform.to_string()),
+        };
+        let mut pip = license_info("pip", "24.0", "MIT");
+        pip.found_in = BTreeSet::from([
+            provenance("app/pixi.lock", "default", "linux-64"),
+            provenance("app/pixi.lock", "default", "osx-arm64"),
+            provenance("lib/pixi.lock", "lint", "linux-64"),
+        ]);
+        let mut attrs = license_info("attrs", "24.2.0", "MIT");
+        attrs.found_in = BTreeSet::from([provenance("lib/pixi.lock", "lint", "linux-64")]);
+        let license_infos = [pip, attrs];
+
+        let names = |group_by: &[GroupBy]| -> Vec<(String, Vec<&str>)> {
+            group_license_infos(&license_infos, group_by)
+                .into_iter()
+                .map(|(group, license_infos)| {
+                    let names = license_infos
+                        .iter()
+                        .map(|license_info| license_info.package_name.as_str())
+                        .collect();
+                    (group, names)
+                })
+                .collect()
+        };
+        assert_eq!(
+            names(&[GroupBy::Lockfile]),
+            vec![
+                ("app/pixi.lock".to_string(), vec!["pip"]),
+                ("lib/pixi.lock".to_string(), vec!["pip", "attrs"]),
+            ]
+        );
+        assert_eq!(
+            names(&[GroupBy::Lockfile, GroupBy::Environment, GroupBy::Platform])[1],
+            (
+                "app/pixi.lock › default › osx-arm64".to_string(),
+                vec!["pip"]
+            )
+        );
+    }
+}
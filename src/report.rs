@@ -0,0 +1,362 @@
+use std::{
+    collections::BTreeMap,
+    io::Write,
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+
+use crate::{
+    bundle::{bundled_license_files, existing_bundle_directory},
+    check::{check_exit_status, check_output},
+    junit::escape,
+    license_info::{packages_per_license, LicenseInfo},
+    CheckOutput, CondaDenyReportConfig, LockfileOrPrefix,
+};
+
+/// The verdicts in the order of the package table, unsafe packages first
+const VERDICTS: [&str; 6] = [
+    "unsafe",
+    "warn",
+    "safe",
+    "ignored",
+    "baselined",
+    "outside-closure",
+];
+
+const STYLE: &str = r#"
+body { font-family: system-ui, sans-serif; margin: 2em; color: #1f2328; }
+h1 { margin-bottom: 0.2em; }
+.verdict { padding: 0.8em 1em; border-radius: 6px; font-weight: bold; margin: 1em 0; }
+.verdict.passed { background: #dafbe1; }
+.verdict.failed { background: #ffebe9; }
+table { border-collapse: collapse; margin: 1em 0; }
+th, td { border: 1px solid #d0d7de; padding: 0.3em 0.6em; text-align: left; vertical-align: top; }
+th { background: #f6f8fa; }
+#packages th { cursor: pointer; }
+.bar { background: #54aeff; height: 1em; }
+tr.unsafe td.verdict-cell { color: #cf222e; font-weight: bold; }
+tr.warn td.verdict-cell { color: #9a6700; font-weight: bold; }
+tr.safe td.verdict-cell { color: #1a7f37; }
+.filters { margin: 1em 0; }
+.filters input, .filters select { margin-right: 1em; padding: 0.2em; }
+"#;
+
+const SCRIPT: &str = r#"
+const table = document.getElementById("packages");
+const rows = () => Array.from(table.tBodies[0].rows);
+function filter() {
+  const text = document.getElementById("filter").value.toLowerCase();
+  const verdict = document.getElementById("verdict").value;
+  for (const row of rows()) {
+    const matches = row.textContent.toLowerCase().includes(text)
+      && (verdict === "" || row.dataset.verdict === verdict);
+    row.style.display = matches ? "" : "none";
+  }
+}
+document.getElementById("filter").addEventListener("input", filter);
+document.getElementById("verdict").addEventListener("change", filter);
+table.tHead.querySelectorAll("th").forEach((header, column) => {
+  let ascending = true;
+  header.addEventListener("click", () => {
+    const sorted = rows().sort((a, b) => {
+      const x = a.cells[column].textContent;
+      const y = b.cells[column].textContent;
+      return (ascending ? 1 : -1) * x.localeCompare(y, undefined, { numeric: true });
+    });
+    ascending = !ascending;
+    sorted.forEach((row) => table.tBodies[0].appendChild(row));
+  });
+});
+"#;
+
+/// Returns `path` relative to `base`, so that links keep working when the report is moved
+/// together with the bundle directory.
+fn relative_path(base: &Path, path: &Path) -> PathBuf {
+    let (Ok(base), Ok(path)) = (base.canonicalize(), path.canonicalize()) else {
+        return path.to_path_buf();
+    };
+    let base: Vec<Component> = base.components().collect();
+    let path: Vec<Component> = path.components().collect();
+    let common = base.iter().zip(&path).take_while(|(a, b)| a == b).count();
+    let mut relative = PathBuf::new();
+    for _ in common..base.len() {
+        relative.push("..");
+    }
+    for component in &path[common..] {
+        relative.push(component);
+    }
+    relative
+}
+
+/// Links to the license texts that `bundle` collected for a package
+struct LicenseTexts<'a> {
+    bundle_directory: &'a Path,
+    report_directory: &'a Path,
+}
+
+impl LicenseTexts<'_> {
+    fn links(&self, license_info: &LicenseInfo) -> String {
+        let (Some(version), Some(build)) = (&license_info.version, &license_info.build) else {
+            return String::new();
+        };
+        bundled_license_files(
+            self.bundle_directory,
+            &license_info.package_name,
+            version,
+            build,
+        )
+        .iter()
+        .map(|(name, file)| {
+            let href = relative_path(self.report_directory, file)
+                .display()
+                .to_string()
+                .replace('\\', "/");
+            format!("<a href=\"{}\">{}</a>", escape(&href), escape(name))
+        })
+        .collect::<Vec<_>>()
+        .join("<br>")
+    }
+}
+
+fn rows(check_output: &CheckOutput) -> Vec<(&LicenseInfo, &'static str)> {
+    let buckets = [
+        &check_output.unsafe_dependencies,
+        &check_output.warn_dependencies,
+        &check_output.safe_dependencies,
+        &check_output.ignored_dependencies,
+        &check_output.baselined_dependencies,
+        &check_output.outside_closure_dependencies,
+    ];
+    buckets
+        .into_iter()
+        .zip(VERDICTS)
+        .flat_map(|(license_infos, verdict)| {
+            license_infos
+                .iter()
+                .map(move |license_info| (license_info, verdict))
+        })
+        .collect()
+}
+
+/// A table with the number of packages per verdict for each environment or platform
+fn breakdown<'a>(
+    title: &str,
+    rows: &[(&'a LicenseInfo, &'static str)],
+    keys: impl Fn(&'a LicenseInfo) -> Vec<String>,
+) -> String {
+    let mut counts: BTreeMap<String, BTreeMap<&str, usize>> = BTreeMap::new();
+    for (license_info, verdict) in rows {
+        for key in keys(license_info) {
+            *counts.entry(key).or_default().entry(verdict).or_default() += 1;
+        }
+    }
+    let mut html = format!("<table>\n<tr><th>{}</th>", escape(title));
+    for verdict in VERDICTS {
+        html.push_str(&format!("<th>{verdict}</th>"));
+    }
+    html.push_str("</tr>\n");
+    for (key, counts) in counts {
+        html.push_str(&format!("<tr><td>{}</td>", escape(&key)));
+        for verdict in VERDICTS {
+            html.push_str(&format!(
+                "<td>{}</td>",
+                counts.get(verdict).copied().unwrap_or_default()
+            ));
+        }
+        html.push_str("</tr>\n");
+    }
+    html.push_str("</table>\n");
+    html
+}
+
+fn histogram(check_output: &CheckOutput) -> String {
+    let counts = packages_per_license(
+        check_output
+            .checked_dependencies()
+            .chain(&check_output.outside_closure_dependencies),
+    );
+    let max = counts.first().map_or(1, |(_, count)| *count);
+    let mut html = String::from("<table>\n<tr><th>License</th><th>Packages</th><th></th></tr>\n");
+    for (license, count) in counts {
+        html.push_str(&format!(
+            "<tr><td>{}</td><td>{count}</td><td style=\"width: 20em\"><div class=\"bar\" style=\"width: {}%\"></div></td></tr>\n",
+            escape(&license),
+            count * 100 / max
+        ));
+    }
+    html.push_str("</table>\n");
+    html
+}
+
+fn package_table(
+    rows: &[(&LicenseInfo, &'static str)],
+    license_texts: Option<&LicenseTexts>,
+) -> String {
+    let mut html = String::from(
+        "<table id=\"packages\">\n<thead><tr><th>Package</th><th>Version</th><th>Platform</th><th>Build</th><th>Environments</th><th>License</th><th>Verdict</th><th>Reason</th><th>Policy</th>",
+    );
+    if license_texts.is_some() {
+        html.push_str("<th>License texts</th>");
+    }
+    html.push_str("</tr></thead>\n<tbody>\n");
+    for (license_info, verdict) in rows {
+        let cells = [
+            license_info
+                .source_identifier
+                .clone()
+                .unwrap_or_else(|| license_info.package_name.clone()),
+            license_info.version.clone().unwrap_or_default(),
+            license_info
+                .platform
+                .clone()
+                .unwrap_or_else(|| "source".to_string()),
+            license_info.build.clone().unwrap_or_default(),
+            license_info
+                .environments()
+                .into_iter()
+                .collect::<Vec<_>>()
+                .join(", "),
+            license_info.license.display_string(),
+        ];
+        html.push_str(&format!(
+            "<tr class=\"{verdict}\" data-verdict=\"{verdict}\">"
+        ));
+        for cell in cells {
+            html.push_str(&format!("<td>{}</td>", escape(&cell)));
+        }
+        html.push_str(&format!(
+            "<td class=\"verdict-cell\">{verdict}</td><td>{}</td><td>{}</td>",
+            license_info
+                .reason
+                .map(|reason| reason.to_string())
+                .unwrap_or_default(),
+            escape(license_info.policy.as_deref().unwrap_or_default())
+        ));
+        if let Some(license_texts) = license_texts {
+            html.push_str(&format!("<td>{}</td>", license_texts.links(license_info)));
+        }
+        html.push_str("</tr>\n");
+    }
+    html.push_str("</tbody>\n</table>\n");
+    html
+}
+
+fn source_description(lockfile_or_prefix: &LockfileOrPrefix) -> String {
+    let (kind, paths) = match lockfile_or_prefix {
+        LockfileOrPrefix::Lockfile(spec) => ("Lockfiles", &spec.lockfiles),
+        LockfileOrPrefix::Prefix(prefixes) => ("Conda prefixes", prefixes),
+    };
+    let paths: Vec<String> = paths
+        .iter()
+        .map(|path| format!("<code>{}</code>", escape(&path.display().to_string())))
+        .collect();
+    format!("{kind}: {}", paths.join(", "))
+}
+
+fn render_html(
+    config: &CondaDenyReportConfig,
+    check_output: &CheckOutput,
+    license_texts: Option<&LicenseTexts>,
+) -> String {
+    let rows = rows(check_output);
+    let verdict = match check_exit_status(check_output, &config.check_config) {
+        Ok(()) => "<div class=\"verdict passed\">✅ The license check passed</div>".to_string(),
+        Err(error) => format!(
+            "<div class=\"verdict failed\">❌ The license check failed: {}</div>",
+            escape(&error.to_string())
+        ),
+    };
+    let counts: Vec<String> = VERDICTS
+        .iter()
+        .map(|verdict| {
+            let count = rows.iter().filter(|(_, v)| v == verdict).count();
+            format!("{count} {verdict}")
+        })
+        .collect();
+
+    let mut html = String::from("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>conda-deny compliance report</title>\n");
+    html.push_str(&format!("<style>{STYLE}</style>\n</head>\n<body>\n"));
+    html.push_str("<h1>conda-deny compliance report</h1>\n");
+    html.push_str(&format!(
+        "<p>{}</p>\n",
+        source_description(&config.check_config.lockfile_or_prefix)
+    ));
+    html.push_str(&verdict);
+    html.push_str(&format!("\n<p>{}</p>\n", counts.join(" · ")));
+
+    html.push_str("<h2>Licenses</h2>\n");
+    html.push_str(&histogram(check_output));
+    html.push_str("<h2>Environments</h2>\n");
+    html.push_str(&breakdown("Environment", &rows, |license_info| {
+        license_info.environments().into_iter().collect()
+    }));
+    html.push_str("<h2>Platforms</h2>\n");
+    html.push_str(&breakdown("Platform", &rows, |license_info| {
+        vec![license_info
+            .platform
+            .clone()
+            .unwrap_or_else(|| "source".to_string())]
+    }));
+
+    html.push_str("<h2>Packages</h2>\n<div class=\"filters\">\n<input id=\"filter\" type=\"search\" placeholder=\"Filter packages\">\n<select id=\"verdict\"><option value=\"\">All verdicts</option>");
+    for verdict in VERDICTS {
+        html.push_str(&format!("<option>{verdict}</option>"));
+    }
+    html.push_str("</select>\n</div>\n");
+    html.push_str(&package_table(&rows, license_texts));
+    html.push_str(&format!("<script>{SCRIPT}</script>\n</body>\n</html>\n"));
+    html
+}
+
+/// Writes a self-contained HTML compliance report, with links to the license texts if
+/// `bundle` has been run.
+pub fn report<W: Write>(config: CondaDenyReportConfig, mut out: W) -> Result<()> {
+    let check_output = check_output(&config.check_config)?;
+
+    let bundle_directory = existing_bundle_directory(config.bundle_directory.as_deref())?;
+    let report_directory = match config.html.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let license_texts = bundle_directory
+        .as_deref()
+        .map(|bundle_directory| LicenseTexts {
+            bundle_directory,
+            report_directory: &report_directory,
+        });
+
+    let html = render_html(&config, &check_output, license_texts.as_ref());
+    std::fs::write(&config.html, html)
+        .with_context(|| format!("Failed to write the report: {:?}", config.html))?;
+    writeln!(
+        out,
+        "📄 Wrote the compliance report to {}",
+        config.html.display()
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rows() {
+        let check_output = CheckOutput {
+            safe_dependencies: vec![LicenseInfo::for_test("a", "1.0", "MIT")],
+            unsafe_dependencies: vec![LicenseInfo::for_test("b", "1.0", "GPL-3.0-only")],
+            outside_closure_dependencies: vec![LicenseInfo::for_test("c", "1.0", "MIT")],
+            ..Default::default()
+        };
+
+        let rows: Vec<(&str, &str)> = rows(&check_output)
+            .into_iter()
+            .map(|(license_info, verdict)| (license_info.package_name.as_str(), verdict))
+            .collect();
+        assert_eq!(
+            rows,
+            vec![("b", "unsafe"), ("a", "safe"), ("c", "outside-closure")]
+        );
+    }
+}
@@ -0,0 +1,355 @@
+use std::{collections::HashMap, path::Path};
+
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+
+use crate::{
+    license_allowlist::join_ignore_rules, license_info::LicenseInfo, CheckOutput,
+    CondaDenyCheckConfig, LockfileOrPrefix, ReasonAction, UnsafeReason,
+};
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+/// Rule for packages that match `warn-licenses`, which have no unsafe reason
+const WARN_LICENSE_RULE: &str = "warn-license";
+const LICENSE_CHANGE_RULE: &str = "license-change";
+const INCONSISTENT_LICENSE_RULE: &str = "inconsistent-license";
+/// Rule for packages that match ignore-packages and have no unsafe reason
+const IGNORED_PACKAGE_RULE: &str = "ignored-package";
+
+fn rules() -> Vec<Value> {
+    let rule = |id: String, name: &str, description: &str| {
+        json!({
+            "id": id,
+            "name": name,
+            "shortDescription": { "text": description },
+        })
+    };
+    vec![
+        rule(
+            UnsafeReason::Disallowed.to_string(),
+            "DisallowedLicense",
+            "The license is not allowed by the policy",
+        ),
+        rule(
+            UnsafeReason::Denied.to_string(),
+            "DeniedLicense",
+            "The license can only be satisfied with a denied license",
+        ),
+        rule(
+            UnsafeReason::NonSpdx.to_string(),
+            "NonSpdxLicense",
+            "The license is not a valid SPDX expression",
+        ),
+        rule(
+            UnsafeReason::Missing.to_string(),
+            "MissingLicense",
+            "The package has no license information",
+        ),
+        rule(
+            WARN_LICENSE_RULE.to_string(),
+            "WarnLicense",
+            "The license requires attention",
+        ),
+        rule(
+            IGNORED_PACKAGE_RULE.to_string(),
+            "IgnoredPackage",
+            "The package is ignored by ignore-packages",
+        ),
+        rule(
+            LICENSE_CHANGE_RULE.to_string(),
+            "LicenseChange",
+            "The license of the package changed",
+        ),
+        rule(
+            INCONSISTENT_LICENSE_RULE.to_string(),
+            "InconsistentLicense",
+            "The license differs between platforms or builds of the same version",
+        ),
+    ]
+}
+
+/// Where packages are declared, so that findings can point at the lockfile line of a package
+struct PackageLocations {
+    /// Lockfile and line of each package, by file name without extension and subdir
+    lockfile_lines: HashMap<(String, String), (String, usize)>,
+    /// Fallback for packages without a url, e.g. source packages
+    default_uri: Option<String>,
+    prefixes: Vec<String>,
+}
+
+fn uri_of(path: &Path) -> String {
+    path.display().to_string().replace('\\', "/")
+}
+
+impl PackageLocations {
+    fn new(lockfile_or_prefix: &LockfileOrPrefix) -> Result<Self> {
+        let mut locations = PackageLocations {
+            lockfile_lines: HashMap::new(),
+            default_uri: None,
+            prefixes: Vec::new(),
+        };
+        match lockfile_or_prefix {
+            LockfileOrPrefix::Lockfile(spec) => {
+                for lockfile in &spec.lockfiles {
+                    let content = std::fs::read_to_string(lockfile)
+                        .with_context(|| format!("Failed to read lockfile: {lockfile:?}"))?;
+                    locations.add_lockfile(&uri_of(lockfile), &content);
+                }
+                locations.default_uri = spec.lockfiles.first().map(|path| uri_of(path));
+            }
+            LockfileOrPrefix::Prefix(prefixes) => {
+                locations.prefixes = prefixes.iter().map(|prefix| uri_of(prefix)).collect();
+            }
+        }
+        Ok(locations)
+    }
+
+    /// Records the first line of each entry in the `packages` section of a lockfile.
+    fn add_lockfile(&mut self, uri: &str, content: &str) {
+        let mut in_packages = false;
+        let mut entry_line = 0;
+        for (index, line) in content.lines().enumerate() {
+            if !line.starts_with(' ') && !line.starts_with('-') {
+                in_packages = line == "packages:";
+                continue;
+            }
+            if !in_packages {
+                continue;
+            }
+            if line.starts_with("- ") {
+                entry_line = index + 1;
+            }
+            // `- conda: <url>` since lockfile version 6, `url: <url>` before
+            let Some(url) = line
+                .strip_prefix("- conda: ")
+                .or_else(|| line.trim_start().strip_prefix("url: "))
+            else {
+                continue;
+            };
+            let mut parts = url.trim().rsplit('/');
+            let (Some(file_name), Some(subdir)) = (parts.next(), parts.next()) else {
+                continue;
+            };
+            let stem = file_name
+                .strip_suffix(".conda")
+                .or_else(|| file_name.strip_suffix(".tar.bz2"))
+                .unwrap_or(file_name);
+            self.lockfile_lines
+                .entry((stem.to_string(), subdir.to_string()))
+                .or_insert_with(|| (uri.to_string(), entry_line));
+        }
+    }
+
+    fn location(&self, license_info: &LicenseInfo) -> Option<Value> {
+        let file_stem = match (&license_info.version, &license_info.build) {
+            (Some(version), Some(build)) => {
+                Some(format!("{}-{version}-{build}", license_info.package_name))
+            }
+            _ => None,
+        };
+        let lockfile_line = file_stem.as_ref().and_then(|stem| {
+            self.lockfile_lines.get(&(
+                stem.clone(),
+                license_info.platform.clone().unwrap_or_default(),
+            ))
+        });
+
+        let (uri, line) = match (lockfile_line, self.prefixes.first(), file_stem) {
+            (Some((uri, line)), _, _) => (uri.clone(), Some(*line)),
+            (None, Some(prefix), Some(stem)) => (format!("{prefix}/conda-meta/{stem}.json"), None),
+            _ => (self.default_uri.clone()?, None),
+        };
+        let mut physical_location = json!({ "artifactLocation": { "uri": uri } });
+        if let Some(line) = line {
+            physical_location["region"] = json!({ "startLine": line });
+        }
+        Some(json!({ "physicalLocation": physical_location }))
+    }
+}
+
+fn level(action: ReasonAction) -> &'static str {
+    match action {
+        ReasonAction::Fail => "error",
+        ReasonAction::Warn => "warning",
+        ReasonAction::Ignore => "note",
+    }
+}
+
+fn package_result(license_info: &LicenseInfo, level: &str, locations: &PackageLocations) -> Value {
+    let rule_id = match license_info.reason {
+        Some(reason) => reason.to_string(),
+        None if !license_info.ignored_by.is_empty() => IGNORED_PACKAGE_RULE.to_string(),
+        None => WARN_LICENSE_RULE.to_string(),
+    };
+    let license = license_info.license.display_string();
+    let mut result = json!({
+        "ruleId": rule_id,
+        "level": level,
+        "message": {
+            "text": format!(
+                "{} {} ({}) has license {license} ({rule_id})",
+                license_info.package_name,
+                license_info.version.as_deref().unwrap_or("unknown"),
+                license_info.platform.as_deref().unwrap_or("unknown"),
+            ),
+        },
+        "properties": {
+            "package": license_info.package_name,
+            "version": license_info.version,
+            "platform": license_info.platform,
+            "build": license_info.build,
+            "license": license_info.license.license_string(),
+            "policy": license_info.policy,
+        },
+    });
+    if let Some(location) = locations.location(license_info) {
+        result["locations"] = json!([location]);
+    }
+    result
+}
+
+fn suppressed(mut result: Value, justification: &str) -> Value {
+    result["suppressions"] = json!([{ "kind": "external", "justification": justification }]);
+    result
+}
+
+/// Builds a SARIF 2.1.0 log of the check findings, e.g. for code scanning.
+///
+/// Ignored and baselined packages are reported as suppressed results.
+pub fn sarif_log(check_output: &CheckOutput, config: &CondaDenyCheckConfig) -> Result<Value> {
+    let locations = PackageLocations::new(&config.lockfile_or_prefix)?;
+    let mut results = Vec::new();
+
+    for license_info in &check_output.unsafe_dependencies {
+        results.push(package_result(license_info, "error", &locations));
+    }
+    for license_info in &check_output.warn_dependencies {
+        results.push(package_result(license_info, "warning", &locations));
+    }
+    for license_info in &check_output.ignored_dependencies {
+        let justification = if license_info.ignored_by.is_empty() {
+            "Ignored by the configuration".to_string()
+        } else {
+            format!(
+                "Ignored by ignore-packages: {}",
+                join_ignore_rules(&license_info.ignored_by)
+            )
+        };
+        results.push(suppressed(
+            package_result(license_info, "note", &locations),
+            &justification,
+        ));
+    }
+    for license_info in &check_output.baselined_dependencies {
+        let mut result = suppressed(
+            package_result(license_info, "error", &locations),
+            "Known from the baseline",
+        );
+        result["baselineState"] = json!("unchanged");
+        results.push(result);
+    }
+
+    let location_of = |package_name: &str, platform: Option<&str>, version: Option<&str>| {
+        check_output
+            .checked_dependencies()
+            .find(|license_info| {
+                license_info.package_name == package_name
+                    && license_info.platform.as_deref() == platform
+                    && (version.is_none() || license_info.version.as_deref() == version)
+            })
+            .and_then(|license_info| locations.location(license_info))
+    };
+    for change in &check_output.license_changes {
+        let mut result = json!({
+            "ruleId": LICENSE_CHANGE_RULE,
+            "level": level(config.license_changes),
+            "message": {
+                "text": format!(
+                    "{} ({}) changed its license from {} to {}",
+                    change.package_name,
+                    change.platform.as_deref().unwrap_or("unknown"),
+                    change.old_license.as_deref().unwrap_or("no license"),
+                    change.new_license.as_deref().unwrap_or("no license"),
+                ),
+            },
+        });
+        if let Some(location) = location_of(
+            &change.package_name,
+            change.platform.as_deref(),
+            change.new_version.as_deref(),
+        ) {
+            result["locations"] = json!([location]);
+        }
+        results.push(result);
+    }
+    for inconsistency in &check_output.inconsistent_licenses {
+        let licenses = inconsistency
+            .licenses
+            .iter()
+            .map(|variant| variant.license.as_deref().unwrap_or("no license"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let locations: Vec<Value> = inconsistency
+            .licenses
+            .iter()
+            .flat_map(|variant| &variant.builds)
+            .filter_map(|build| {
+                location_of(
+                    &inconsistency.package_name,
+                    build.platform.as_deref(),
+                    Some(&inconsistency.version),
+                )
+            })
+            .collect();
+        results.push(json!({
+            "ruleId": INCONSISTENT_LICENSE_RULE,
+            "level": level(config.inconsistent_licenses),
+            "message": {
+                "text": format!(
+                    "{} {} has different licenses on different platforms or builds: {licenses}",
+                    inconsistency.package_name, inconsistency.version,
+                ),
+            },
+            "locations": locations,
+        }));
+    }
+
+    Ok(json!({
+        "$schema": SARIF_SCHEMA,
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": "https://github.com/quantco/conda-deny",
+                    "rules": rules(),
+                },
+            },
+            "results": results,
+        }],
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lockfile_lines() {
+        let path = Path::new("tests/test_inconsistent_licenses/pixi.lock");
+        let mut locations = PackageLocations {
+            lockfile_lines: HashMap::new(),
+            default_uri: None,
+            prefixes: Vec::new(),
+        };
+        locations.add_lockfile(&uri_of(path), &std::fs::read_to_string(path).unwrap());
+
+        assert_eq!(locations.lockfile_lines.len(), 2);
+        assert_eq!(
+            locations.lockfile_lines[&("k9s-0.40.5-h36c15f3_1".to_string(), "win-64".to_string())],
+            (uri_of(path), 19)
+        );
+    }
+}
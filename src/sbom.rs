@@ -0,0 +1,357 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::Write,
+};
+
+use anyhow::{Context, Result};
+use rattler_conda_types::{PackageRecord, PrefixRecord};
+use rattler_lock::{CondaPackageData, SourceIdentifier};
+use reqwest::Url;
+
+use crate::{
+    bundle::existing_bundle_directory,
+    cyclonedx::cyclonedx_bom,
+    dependency_graph::dependency_name,
+    license_allowlist::IgnorePackage,
+    license_info::{license_state_from_optional_str, LicenseState},
+    pixi_lock::{get_locked_conda_packages, read_pixi_lock},
+    spdx_document::{spdx_document, spdx_tag_value},
+    CondaDenySbomConfig, LockfileOrPrefix, SbomFormat,
+};
+
+/// A package of the SBOM with what is needed to identify and verify it
+#[derive(Debug, Clone)]
+pub(crate) struct SbomPackage {
+    pub name: String,
+    /// Not known for source packages
+    pub version: Option<String>,
+    pub build: Option<String>,
+    pub subdir: Option<String>,
+    /// Where the package was downloaded from
+    pub url: Option<Url>,
+    pub license: LicenseState,
+    pub md5: Option<String>,
+    pub sha256: Option<String>,
+    /// Only set for source packages, which are built from a local path or a git repository
+    pub source_identifier: Option<String>,
+    /// Pixi environments (or conda prefixes) the package was found in
+    pub environments: BTreeSet<String>,
+    /// References of the packages this package depends on
+    pub dependencies: BTreeSet<String>,
+}
+
+/// Characters that do not need to be percent-encoded in a package URL
+fn is_unreserved(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "-._~".contains(c)
+}
+
+fn percent_encode(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for c in text.chars() {
+        if is_unreserved(c) {
+            encoded.push(c);
+        } else {
+            let mut buffer = [0; 4];
+            for byte in c.encode_utf8(&mut buffer).bytes() {
+                encoded.push_str(&format!("%{byte:02X}"));
+            }
+        }
+    }
+    encoded
+}
+
+impl SbomPackage {
+    fn from_package_record(record: &PackageRecord, url: Option<Url>) -> Self {
+        SbomPackage {
+            name: record.name.as_source().to_string(),
+            version: Some(record.version.to_string()),
+            build: Some(record.build.clone()),
+            subdir: Some(record.subdir.clone()),
+            url,
+            license: license_state_from_optional_str(record.license.as_deref()),
+            md5: record.md5.map(|md5| format!("{md5:x}")),
+            sha256: record.sha256.map(|sha256| format!("{sha256:x}")),
+            source_identifier: None,
+            environments: BTreeSet::new(),
+            dependencies: BTreeSet::new(),
+        }
+    }
+
+    fn from_conda_package(package: &CondaPackageData) -> Self {
+        let mut sbom_package = match package.record() {
+            Some(record) => Self::from_package_record(record, package.location().as_url().cloned()),
+            None => SbomPackage {
+                name: package.name().as_source().to_string(),
+                version: None,
+                build: None,
+                subdir: None,
+                url: None,
+                license: license_state_from_optional_str(
+                    package
+                        .as_source()
+                        .and_then(|source| source.metadata.as_partial())
+                        .and_then(|metadata| metadata.license.as_deref()),
+                ),
+                md5: None,
+                sha256: None,
+                source_identifier: None,
+                environments: BTreeSet::new(),
+                dependencies: BTreeSet::new(),
+            },
+        };
+        if let Some(source) = package.as_source() {
+            sbom_package.url = None;
+            sbom_package.source_identifier =
+                Some(SourceIdentifier::from_source_data(source).to_string());
+        }
+        sbom_package
+    }
+
+    /// Base URL of the channel, e.g. `https://conda.anaconda.org/conda-forge`
+    fn channel_url(&self) -> Option<Url> {
+        let mut channel_url = self.url.clone()?;
+        channel_url
+            .path_segments_mut()
+            .ok()?
+            .pop_if_empty()
+            .pop()
+            .pop();
+        Some(channel_url)
+    }
+
+    /// The package URL (purl) of a binary package, see https://github.com/package-url/purl-spec
+    pub fn purl(&self) -> Option<String> {
+        if self.source_identifier.is_some() {
+            return None;
+        }
+        let mut purl = format!("pkg:conda/{}", percent_encode(&self.name));
+        if let Some(version) = &self.version {
+            purl.push_str(&format!("@{}", percent_encode(version)));
+        }
+
+        // Qualifiers are sorted by key
+        let channel_url = self.channel_url();
+        let channel = channel_url
+            .as_ref()
+            .and_then(|url| url.path_segments()?.next_back().map(str::to_string))
+            .filter(|channel| !channel.is_empty());
+        let repository_url = channel_url
+            .as_ref()
+            .filter(|url| url.host_str() != Some("conda.anaconda.org"))
+            .map(|url| url.as_str().trim_end_matches('/').to_string());
+        let package_type = self.url.as_ref().and_then(|url| {
+            if url.path().ends_with(".conda") {
+                Some("conda")
+            } else if url.path().ends_with(".tar.bz2") {
+                Some("tar.bz2")
+            } else {
+                None
+            }
+        });
+        let qualifiers: Vec<String> = [
+            ("build", self.build.as_deref()),
+            ("channel", channel.as_deref()),
+            ("repository_url", repository_url.as_deref()),
+            ("subdir", self.subdir.as_deref()),
+            ("type", package_type),
+        ]
+        .into_iter()
+        .filter_map(|(key, value)| Some(format!("{key}={}", percent_encode(value?))))
+        .collect();
+        if !qualifiers.is_empty() {
+            purl.push('?');
+            purl.push_str(&qualifiers.join("&"));
+        }
+        Some(purl)
+    }
+
+    /// Unique reference of the package within the SBOM
+    pub fn reference(&self) -> String {
+        match &self.source_identifier {
+            Some(source_identifier) => format!("source:{source_identifier}"),
+            None => self.purl().unwrap_or_else(|| self.name.clone()),
+        }
+    }
+}
+
+/// Adds the packages of one environment on one platform (or one conda prefix), resolving the
+/// dependencies of each package to the packages of the same environment.
+fn add_environment(
+    sbom_packages: &mut BTreeMap<String, SbomPackage>,
+    environment: &str,
+    packages: Vec<(SbomPackage, &[String])>,
+) {
+    let references: BTreeMap<String, String> = packages
+        .iter()
+        .map(|(package, _)| (package.name.clone(), package.reference()))
+        .collect();
+
+    for (package, depends) in packages {
+        let dependencies = depends
+            .iter()
+            .filter_map(|spec| references.get(dependency_name(spec)).cloned());
+        let sbom_package = sbom_packages.entry(package.reference()).or_insert(package);
+        sbom_package.environments.insert(environment.to_string());
+        sbom_package.dependencies.extend(dependencies);
+    }
+}
+
+/// Collects all packages with their dependencies, sorted by reference.
+///
+/// Like `bundle`, the SBOM includes ignored conda packages, as it describes what is shipped.
+/// `ignore_packages` is only used to leave out pypi packages.
+pub(crate) fn collect_sbom_packages(
+    lockfile_or_prefix: &LockfileOrPrefix,
+    ignore_packages: &[IgnorePackage],
+) -> Result<Vec<SbomPackage>> {
+    let mut sbom_packages = BTreeMap::new();
+    match lockfile_or_prefix {
+        LockfileOrPrefix::Lockfile(lockfile_spec) => {
+            for lockfile in &lockfile_spec.lockfiles {
+                let environments = get_locked_conda_packages(
+                    &read_pixi_lock(lockfile)?,
+                    &lockfile.display().to_string(),
+                    &lockfile_spec.environments,
+                    &lockfile_spec.platforms,
+                    lockfile_spec.ignore_pypi,
+                    ignore_packages,
+                )
+                .with_context(|| {
+                    format!(
+                        "Failed to get package records from lockfile: {}",
+                        lockfile.display()
+                    )
+                })?;
+                for (provenance, packages) in &environments {
+                    let packages = packages
+                        .iter()
+                        .map(|package| {
+                            (SbomPackage::from_conda_package(package), package.depends())
+                        })
+                        .collect();
+                    add_environment(&mut sbom_packages, &provenance.environment, packages);
+                }
+            }
+        }
+        LockfileOrPrefix::Prefix(prefixes) => {
+            for prefix in prefixes {
+                let meta_path = prefix.join("conda-meta");
+                anyhow::ensure!(
+                    meta_path.exists(),
+                    "The conda prefix {:?} is invalid: {:?} directory is missing",
+                    prefix,
+                    meta_path
+                );
+                let records = PrefixRecord::collect_from_prefix::<PrefixRecord>(prefix)
+                    .with_context(|| {
+                        format!("Failed to collect prefix records from {}", prefix.display())
+                    })?;
+                let packages = records
+                    .iter()
+                    .map(|record| {
+                        let package_record = &record.repodata_record.package_record;
+                        (
+                            SbomPackage::from_package_record(
+                                package_record,
+                                Some(record.repodata_record.url.clone()),
+                            ),
+                            package_record.depends.as_slice(),
+                        )
+                    })
+                    .collect();
+                add_environment(&mut sbom_packages, &prefix.display().to_string(), packages);
+            }
+        }
+    }
+    Ok(sbom_packages.into_values().collect())
+}
+
+/// Name of the SBOM document, after the lockfiles or conda prefixes it describes
+fn document_name(lockfile_or_prefix: &LockfileOrPrefix) -> String {
+    let paths: Vec<String> = match lockfile_or_prefix {
+        LockfileOrPrefix::Lockfile(lockfile_spec) => lockfile_spec
+            .lockfiles
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect(),
+        LockfileOrPrefix::Prefix(prefixes) => prefixes
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect(),
+    };
+    paths.join(", ")
+}
+
+pub fn sbom<W: Write>(config: CondaDenySbomConfig, mut out: W) -> Result<()> {
+    let packages = collect_sbom_packages(&config.lockfile_or_prefix, &config.ignore_packages)
+        .with_context(|| "Collecting the packages for the SBOM failed.")?;
+
+    let document = match config.format {
+        SbomFormat::CyclonedxJson => serde_json::to_string_pretty(&cyclonedx_bom(&packages))?,
+        SbomFormat::SpdxJson | SbomFormat::SpdxTv => {
+            let bundle_directory = existing_bundle_directory(config.bundle_directory.as_deref())?;
+            let document = spdx_document(
+                &packages,
+                &document_name(&config.lockfile_or_prefix),
+                bundle_directory.as_deref(),
+            )?;
+            if config.format == SbomFormat::SpdxJson {
+                serde_json::to_string_pretty(&document)?
+            } else {
+                spdx_tag_value(&document)
+            }
+        }
+    };
+    let document = document.trim_end();
+
+    match &config.output {
+        Some(path) => {
+            std::fs::write(path, format!("{document}\n"))
+                .with_context(|| format!("Failed to write the SBOM: {path:?}"))?;
+            writeln!(out, "📦 Wrote the SBOM to {}", path.display())?;
+        }
+        None => writeln!(out, "{document}")?,
+    }
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sbom_package(url: &str) -> SbomPackage {
+        SbomPackage {
+            name: "python".to_string(),
+            version: Some("3.12.7".to_string()),
+            build: Some("h5d932e8_0_cpython".to_string()),
+            subdir: Some("linux-64".to_string()),
+            url: Some(Url::parse(url).unwrap()),
+            license: LicenseState::NoLicense,
+            md5: None,
+            sha256: None,
+            source_identifier: None,
+            environments: BTreeSet::new(),
+            dependencies: BTreeSet::new(),
+        }
+    }
+
+    #[test]
+    fn test_purl() {
+        let package = sbom_package(
+            "https://conda.anaconda.org/conda-forge/linux-64/python-3.12.7-h5d932e8_0_cpython.conda",
+        );
+        assert_eq!(
+            package.purl().unwrap(),
+            "pkg:conda/python@3.12.7?build=h5d932e8_0_cpython&channel=conda-forge&subdir=linux-64&type=conda"
+        );
+
+        let package = sbom_package(
+            "https://repo.prefix.dev/my-channel/linux-64/python-3.12.7-h5d932e8_0_cpython.tar.bz2",
+        );
+        assert_eq!(
+            package.purl().unwrap(),
+            "pkg:conda/python@3.12.7?build=h5d932e8_0_cpython&channel=my-channel&repository_url=https%3A%2F%2Frepo.prefix.dev%2Fmy-channel&subdir=linux-64&type=tar.bz2"
+        );
+    }
+}
@@ -0,0 +1,491 @@
+use std::{
+    collections::{btree_map::Entry, BTreeMap, BTreeSet},
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+use spdx::{Expression, LicenseItem};
+
+use crate::{
+    bundle::bundled_license_files, expression_utils::normalize_expression,
+    license_info::LicenseState, sbom::SbomPackage,
+};
+
+const NOASSERTION: &str = "NOASSERTION";
+
+/// Replaces the characters that are not allowed in SPDX identifiers.
+fn spdx_id_part(text: &str) -> String {
+    text.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect()
+}
+
+/// Formats seconds since the Unix epoch as an ISO 8601 UTC timestamp.
+pub(crate) fn format_timestamp(seconds: u64) -> String {
+    let days = (seconds / 86400) as i64;
+    let time = seconds % 86400;
+
+    // Converts days since the epoch to a civil date,
+    // see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+/// Creation time of the document, `SOURCE_DATE_EPOCH` can be set for reproducible documents.
+pub(crate) fn created() -> Result<u64> {
+    match std::env::var("SOURCE_DATE_EPOCH") {
+        Ok(epoch) => epoch
+            .trim()
+            .parse()
+            .with_context(|| format!("Invalid SOURCE_DATE_EPOCH: {epoch}")),
+        Err(_) => Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs()),
+    }
+}
+
+/// The identifier of each package, unique within the document
+fn package_ids(packages: &[SbomPackage]) -> Vec<String> {
+    let mut seen = BTreeSet::new();
+    packages
+        .iter()
+        .map(|package| {
+            let parts = [
+                Some(&package.name),
+                package.version.as_ref(),
+                package.build.as_ref(),
+                package.subdir.as_ref(),
+            ];
+            let id = parts
+                .into_iter()
+                .flatten()
+                .map(|part| spdx_id_part(part))
+                .collect::<Vec<_>>()
+                .join("-");
+            let mut unique_id = id.clone();
+            let mut counter = 1;
+            while !seen.insert(unique_id.clone()) {
+                counter += 1;
+                unique_id = format!("{id}-{counter}");
+            }
+            unique_id
+        })
+        .collect()
+}
+
+/// Text of a license that is not on the SPDX license list: the license texts collected by
+/// `bundle` if there are any, the license as declared in the package metadata otherwise.
+fn extracted_text(
+    package: &SbomPackage,
+    license: &str,
+    bundle_directory: Option<&Path>,
+) -> Result<String> {
+    let license_files = match (bundle_directory, &package.version, &package.build) {
+        (Some(bundle_directory), Some(version), Some(build)) => {
+            bundled_license_files(bundle_directory, &package.name, version, build)
+        }
+        _ => Vec::new(),
+    };
+    if license_files.is_empty() {
+        return Ok(license.to_string());
+    }
+
+    let mut texts = Vec::new();
+    for (name, file) in license_files {
+        let text = std::fs::read(&file)
+            .with_context(|| format!("Failed to read license file: {file:?}"))?;
+        texts.push(format!("{name}:\n\n{}", String::from_utf8_lossy(&text)));
+    }
+    Ok(texts.join("\n\n"))
+}
+
+/// The `LicenseRef-*` ids of an expression that have to be defined in the document itself.
+///
+/// References to other documents (`DocumentRef-*:LicenseRef-*`) are defined there.
+fn license_refs(expression: &Expression) -> BTreeSet<String> {
+    expression
+        .requirements()
+        .filter_map(|requirement| match &requirement.req.license {
+            LicenseItem::Other(license_ref) if license_ref.doc_ref.is_none() => {
+                Some(format!("LicenseRef-{}", license_ref.lic_ref))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Builds an SPDX 2.3 document with a package per conda package and the dependencies between
+/// them as relationships.
+///
+/// The declared license is the license of the package metadata, the concluded license is the
+/// normalized SPDX expression that conda-deny checks. Licenses that are not valid SPDX
+/// expressions are declared as `LicenseRef-*` with the texts collected by `bundle`, and so are
+/// the `LicenseRef-*` ids in valid expressions.
+pub(crate) fn spdx_document(
+    packages: &[SbomPackage],
+    name: &str,
+    bundle_directory: Option<&Path>,
+) -> Result<Value> {
+    let ids = package_ids(packages);
+    let created = created()?;
+
+    let mut spdx_packages = Vec::new();
+    let mut extracted_licenses = Vec::new();
+    // Each `LicenseRef-*` id of a valid expression is defined once, with the packages using it
+    let mut license_ref_packages: BTreeMap<String, (String, BTreeSet<&str>)> = BTreeMap::new();
+    for (package, id) in packages.iter().zip(&ids) {
+        let (declared, concluded) = match &package.license {
+            LicenseState::Valid(expression) => {
+                for license_ref in license_refs(expression) {
+                    let (_, names) = match license_ref_packages.entry(license_ref) {
+                        Entry::Occupied(entry) => entry.into_mut(),
+                        Entry::Vacant(entry) => {
+                            let text =
+                                extracted_text(package, expression.as_ref(), bundle_directory)?;
+                            entry.insert((text, BTreeSet::new()))
+                        }
+                    };
+                    names.insert(&package.name);
+                }
+                (expression.to_string(), normalize_expression(expression))
+            }
+            LicenseState::Invalid(license) => {
+                let license_id = format!("LicenseRef-{id}");
+                extracted_licenses.push(json!({
+                    "licenseId": license_id,
+                    "name": license,
+                    "extractedText": extracted_text(package, license, bundle_directory)?,
+                    "comment": format!(
+                        "License of {} as declared in the package metadata",
+                        package.name
+                    ),
+                }));
+                (license_id, NOASSERTION.to_string())
+            }
+            LicenseState::NoLicense => (NOASSERTION.to_string(), NOASSERTION.to_string()),
+        };
+
+        let download_location = package
+            .url
+            .as_ref()
+            .map_or(NOASSERTION.to_string(), |url| url.to_string());
+        let mut spdx_package = json!({
+            "SPDXID": format!("SPDXRef-Package-{id}"),
+            "name": package.name,
+            "downloadLocation": download_location,
+            "filesAnalyzed": false,
+            "licenseDeclared": declared,
+            "licenseConcluded": concluded,
+            "copyrightText": NOASSERTION,
+        });
+        if let Some(version) = &package.version {
+            spdx_package["versionInfo"] = json!(version);
+        }
+        let checksums: Vec<Value> = [("SHA256", &package.sha256), ("MD5", &package.md5)]
+            .into_iter()
+            .filter_map(|(algorithm, value)| {
+                Some(json!({ "algorithm": algorithm, "checksumValue": value.as_ref()? }))
+            })
+            .collect();
+        if !checksums.is_empty() {
+            spdx_package["checksums"] = json!(checksums);
+        }
+        if let Some(purl) = package.purl() {
+            spdx_package["externalRefs"] = json!([{
+                "referenceCategory": "PACKAGE-MANAGER",
+                "referenceType": "purl",
+                "referenceLocator": purl,
+            }]);
+        }
+        let mut comment = Vec::new();
+        if let Some(build) = &package.build {
+            comment.push(format!("build: {build}"));
+        }
+        if let Some(subdir) = &package.subdir {
+            comment.push(format!("subdir: {subdir}"));
+        }
+        if let Some(source_identifier) = &package.source_identifier {
+            comment.push(format!("source: {source_identifier}"));
+        }
+        if !package.environments.is_empty() {
+            let environments: Vec<&str> = package.environments.iter().map(String::as_str).collect();
+            comment.push(format!("environments: {}", environments.join(", ")));
+        }
+        spdx_package["comment"] = json!(comment.join("\n"));
+        spdx_packages.push(spdx_package);
+    }
+
+    for (license_id, (text, names)) in license_ref_packages {
+        extracted_licenses.push(json!({
+            "name": license_id.trim_start_matches("LicenseRef-"),
+            "licenseId": license_id,
+            "extractedText": text,
+            "comment": format!(
+                "Referenced by the license of {}",
+                names.into_iter().collect::<Vec<_>>().join(", ")
+            ),
+        }));
+    }
+
+    // The document describes the packages that no other package depends on
+    let depended_on: BTreeSet<&String> = packages
+        .iter()
+        .flat_map(|package| &package.dependencies)
+        .collect();
+    let mut relationships = Vec::new();
+    for (package, id) in packages.iter().zip(&ids) {
+        if !depended_on.contains(&package.reference()) {
+            relationships.push(json!({
+                "spdxElementId": "SPDXRef-DOCUMENT",
+                "relationshipType": "DESCRIBES",
+                "relatedSpdxElement": format!("SPDXRef-Package-{id}"),
+            }));
+        }
+    }
+    let ids_by_reference: BTreeMap<String, &String> = packages
+        .iter()
+        .map(SbomPackage::reference)
+        .zip(&ids)
+        .collect();
+    for (package, id) in packages.iter().zip(&ids) {
+        for dependency_id in package
+            .dependencies
+            .iter()
+            .filter_map(|dependency| ids_by_reference.get(dependency))
+        {
+            relationships.push(json!({
+                "spdxElementId": format!("SPDXRef-Package-{id}"),
+                "relationshipType": "DEPENDS_ON",
+                "relatedSpdxElement": format!("SPDXRef-Package-{dependency_id}"),
+            }));
+        }
+    }
+
+    let mut document = json!({
+        "spdxVersion": "SPDX-2.3",
+        "dataLicense": "CC0-1.0",
+        "SPDXID": "SPDXRef-DOCUMENT",
+        "name": name,
+        "documentNamespace": format!(
+            "https://spdx.org/spdxdocs/{}-{created}",
+            spdx_id_part(name)
+        ),
+        "creationInfo": {
+            "creators": [
+                format!("Tool: {}-{}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))
+            ],
+            "created": format_timestamp(created),
+        },
+        "packages": spdx_packages,
+        "relationships": relationships,
+    });
+    if !extracted_licenses.is_empty() {
+        document["hasExtractedLicensingInfos"] = json!(extracted_licenses);
+    }
+    Ok(document)
+}
+
+/// Adds a tag, wrapping multi-line values in `<text>` tags.
+fn push_tag(output: &mut String, name: &str, value: &str) {
+    if value.contains('\n') {
+        output.push_str(&format!("{name}: <text>{value}</text>\n"));
+    } else {
+        output.push_str(&format!("{name}: {value}\n"));
+    }
+}
+
+fn str_of(value: &Value) -> &str {
+    value.as_str().unwrap_or_default()
+}
+
+fn array_of(value: &Value) -> impl Iterator<Item = &Value> {
+    value.as_array().into_iter().flatten()
+}
+
+/// Formats an SPDX document built by [`spdx_document`] in the tag-value format.
+pub(crate) fn spdx_tag_value(document: &Value) -> String {
+    let mut output = String::new();
+    push_tag(&mut output, "SPDXVersion", str_of(&document["spdxVersion"]));
+    push_tag(&mut output, "DataLicense", str_of(&document["dataLicense"]));
+    push_tag(&mut output, "SPDXID", str_of(&document["SPDXID"]));
+    push_tag(&mut output, "DocumentName", str_of(&document["name"]));
+    push_tag(
+        &mut output,
+        "DocumentNamespace",
+        str_of(&document["documentNamespace"]),
+    );
+    for creator in array_of(&document["creationInfo"]["creators"]) {
+        push_tag(&mut output, "Creator", str_of(creator));
+    }
+    push_tag(
+        &mut output,
+        "Created",
+        str_of(&document["creationInfo"]["created"]),
+    );
+
+    for package in array_of(&document["packages"]) {
+        output.push('\n');
+        push_tag(&mut output, "PackageName", str_of(&package["name"]));
+        push_tag(&mut output, "SPDXID", str_of(&package["SPDXID"]));
+        if let Some(version) = package.get("versionInfo") {
+            push_tag(&mut output, "PackageVersion", str_of(version));
+        }
+        push_tag(
+            &mut output,
+            "PackageDownloadLocation",
+            str_of(&package["downloadLocation"]),
+        );
+        push_tag(&mut output, "FilesAnalyzed", "false");
+        for checksum in array_of(&package["checksums"]) {
+            let checksum = format!(
+                "{}: {}",
+                str_of(&checksum["algorithm"]),
+                str_of(&checksum["checksumValue"])
+            );
+            push_tag(&mut output, "PackageChecksum", &checksum);
+        }
+        push_tag(
+            &mut output,
+            "PackageLicenseConcluded",
+            str_of(&package["licenseConcluded"]),
+        );
+        push_tag(
+            &mut output,
+            "PackageLicenseDeclared",
+            str_of(&package["licenseDeclared"]),
+        );
+        push_tag(
+            &mut output,
+            "PackageCopyrightText",
+            str_of(&package["copyrightText"]),
+        );
+        for reference in array_of(&package["externalRefs"]) {
+            let reference = format!(
+                "{} {} {}",
+                str_of(&reference["referenceCategory"]),
+                str_of(&reference["referenceType"]),
+                str_of(&reference["referenceLocator"])
+            );
+            push_tag(&mut output, "ExternalRef", &reference);
+        }
+        push_tag(&mut output, "PackageComment", str_of(&package["comment"]));
+    }
+
+    output.push('\n');
+    for relationship in array_of(&document["relationships"]) {
+        let relationship = format!(
+            "{} {} {}",
+            str_of(&relationship["spdxElementId"]),
+            str_of(&relationship["relationshipType"]),
+            str_of(&relationship["relatedSpdxElement"])
+        );
+        push_tag(&mut output, "Relationship", &relationship);
+    }
+
+    for license in array_of(&document["hasExtractedLicensingInfos"]) {
+        output.push('\n');
+        push_tag(&mut output, "LicenseID", str_of(&license["licenseId"]));
+        // License texts often span several lines, so they are always wrapped
+        output.push_str(&format!(
+            "ExtractedText: <text>{}</text>\n",
+            str_of(&license["extractedText"])
+        ));
+        push_tag(&mut output, "LicenseName", str_of(&license["name"]));
+        push_tag(&mut output, "LicenseComment", str_of(&license["comment"]));
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::license_info::license_state_from_optional_str;
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_timestamp(1740174046), "2025-02-21T21:40:46Z");
+        assert_eq!(format_timestamp(951782400), "2000-02-29T00:00:00Z");
+    }
+
+    #[test]
+    fn test_spdx_document_defines_license_refs() {
+        let package = |name: &str, license: &str| SbomPackage {
+            name: name.to_string(),
+            version: Some("1.0".to_string()),
+            build: Some("h0_0".to_string()),
+            subdir: Some("win-64".to_string()),
+            url: None,
+            license: license_state_from_optional_str(Some(license)),
+            md5: None,
+            sha256: None,
+            source_identifier: None,
+            environments: BTreeSet::new(),
+            dependencies: BTreeSet::new(),
+        };
+        let packages = [
+            package("vc14_runtime", "LicenseRef-MicrosoftVisualCpp2015-2022Runtime"),
+            package(
+                "ucrt",
+                "LicenseRef-MicrosoftWindowsSDK10 AND LicenseRef-MicrosoftVisualCpp2015-2022Runtime",
+            ),
+            package("external", "DocumentRef-other:LicenseRef-Foo"),
+            package("zlib", "Zlib"),
+        ];
+
+        let document = spdx_document(&packages, "test", None).unwrap();
+        let extracted: Vec<(&str, &str)> = array_of(&document["hasExtractedLicensingInfos"])
+            .map(|license| (str_of(&license["licenseId"]), str_of(&license["comment"])))
+            .collect();
+        assert_eq!(
+            extracted,
+            vec![
+                (
+                    "LicenseRef-MicrosoftVisualCpp2015-2022Runtime",
+                    "Referenced by the license of ucrt, vc14_runtime"
+                ),
+                (
+                    "LicenseRef-MicrosoftWindowsSDK10",
+                    "Referenced by the license of ucrt"
+                ),
+            ]
+        );
+        assert_eq!(
+            document["hasExtractedLicensingInfos"][1]["extractedText"],
+            "LicenseRef-MicrosoftWindowsSDK10 AND LicenseRef-MicrosoftVisualCpp2015-2022Runtime"
+        );
+    }
+
+    #[test]
+    fn test_spdx_id_part() {
+        assert_eq!(
+            spdx_id_part("python_abi-3.12_5_cp312"),
+            "python-abi-3.12-5-cp312"
+        );
+    }
+}
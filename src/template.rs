@@ -0,0 +1,182 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+};
+
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+use tera::Tera;
+
+use crate::{
+    json_output::metadata,
+    license_info::{count_packages, packages_per_license, LicenseInfo, LicenseInfos, LicenseState},
+    CheckOutput, CondaDenyCheckConfig, LockfileOrPrefix,
+};
+
+/// A package in the template context.
+fn entry(license_info: &LicenseInfo) -> Value {
+    json!({
+        "name": license_info.package_name,
+        "version": license_info.version,
+        "build": license_info.build,
+        "platform": license_info.platform,
+        "platforms": license_info.aggregation.as_ref().map(|aggregation| &aggregation.platforms),
+        "builds": license_info.aggregation.as_ref().map(|aggregation| &aggregation.builds),
+        "source": license_info.source_identifier,
+        "license": license_info.license.license_string(),
+        "license_state": license_info.license.state(),
+        "reason": license_info.reason.map(|reason| reason.to_string()),
+        "policy": license_info.policy,
+        "environments": license_info.environments(),
+        "pulled_in_by": license_info.pulled_in_by,
+        "dependency_type": license_info.dependency_type,
+        "found_in": license_info.found_in,
+    })
+}
+
+fn entries<'a>(license_infos: impl IntoIterator<Item = &'a LicenseInfo>) -> Vec<Value> {
+    license_infos.into_iter().map(entry).collect()
+}
+
+/// The licenses with the number of packages and the package names, most common first.
+fn license_groups(license_infos: &[&LicenseInfo]) -> Vec<Value> {
+    let mut names: BTreeMap<String, BTreeSet<&str>> = BTreeMap::new();
+    for license_info in license_infos {
+        let license = license_info.license.display_string();
+        names
+            .entry(license)
+            .or_default()
+            .insert(&license_info.package_name);
+    }
+    packages_per_license(license_infos.iter().copied())
+        .into_iter()
+        .map(|(license, count)| {
+            json!({
+                "license": license,
+                "count": count,
+                "packages": names.remove(&license).unwrap_or_default(),
+            })
+        })
+        .collect()
+}
+
+/// The context of a template rendered by `check`, see the README for a description.
+pub(crate) fn check_context(
+    check_output: &CheckOutput,
+    config: &CondaDenyCheckConfig,
+    failure: Option<&anyhow::Error>,
+) -> Result<Value> {
+    let failure = failure.map(|error| error.to_string());
+    let checked: Vec<&LicenseInfo> = check_output.checked_dependencies().collect();
+    Ok(json!({
+        "metadata": metadata("check", &config.lockfile_or_prefix)?,
+        "summary": {
+            "passed": failure.is_none(),
+            "failure": failure,
+            "total": count_packages(checked.iter().copied()),
+            "safe": count_packages(&check_output.safe_dependencies),
+            "warn": count_packages(&check_output.warn_dependencies),
+            "unsafe": count_packages(&check_output.unsafe_dependencies),
+            "ignored": count_packages(&check_output.ignored_dependencies),
+            "baselined": count_packages(&check_output.baselined_dependencies),
+            "outside_closure": count_packages(&check_output.outside_closure_dependencies),
+            "license_changes": check_output.license_changes.len(),
+            "inconsistent_licenses": check_output.inconsistent_licenses.len(),
+        },
+        "safe": entries(&check_output.safe_dependencies),
+        "warn": entries(&check_output.warn_dependencies),
+        "unsafe": entries(&check_output.unsafe_dependencies),
+        "ignored": entries(&check_output.ignored_dependencies),
+        "baselined": entries(&check_output.baselined_dependencies),
+        "outside_closure": entries(&check_output.outside_closure_dependencies),
+        "licenses": license_groups(&checked),
+        "license_changes": check_output.license_changes,
+        "inconsistent_licenses": check_output.inconsistent_licenses,
+    }))
+}
+
+/// The context of a template rendered by `list`, see the README for a description.
+pub(crate) fn list_context(
+    license_infos: &LicenseInfos,
+    lockfile_or_prefix: &LockfileOrPrefix,
+) -> Result<Value> {
+    let license_infos: Vec<&LicenseInfo> = license_infos.license_infos.iter().collect();
+    let count = |state: fn(&LicenseState) -> bool| {
+        license_infos
+            .iter()
+            .filter(|license_info| state(&license_info.license))
+            .map(|license_info| license_info.package_count())
+            .sum::<usize>()
+    };
+    Ok(json!({
+        "metadata": metadata("list", lockfile_or_prefix)?,
+        "summary": {
+            "total": count_packages(license_infos.iter().copied()),
+            "valid": count(|license| matches!(license, LicenseState::Valid(_))),
+            "invalid": count(|license| matches!(license, LicenseState::Invalid(_))),
+            "missing": count(|license| matches!(license, LicenseState::NoLicense)),
+        },
+        "packages": entries(license_infos.iter().copied()),
+        "licenses": license_groups(&license_infos),
+    }))
+}
+
+/// Renders the Tera template at the given path with the context.
+pub(crate) fn render_template(template: Option<&Path>, context: Value) -> Result<String> {
+    let path = template.ok_or_else(|| {
+        anyhow::anyhow!("The template output format requires a template, pass it with --template")
+    })?;
+    let source = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read the template: {path:?}"))?;
+    let context = tera::Context::from_value(context)?;
+    Tera::one_off(&source, &context, false)
+        .with_context(|| format!("Failed to render the template: {path:?}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spdx::Expression;
+
+    fn license_info(name: &str, license: LicenseState) -> LicenseInfo {
+        LicenseInfo {
+            license,
+            ..LicenseInfo::for_test(name, "1.0.0", "")
+        }
+    }
+
+    #[test]
+    fn test_license_groups() {
+        let mit = LicenseState::Valid(Expression::parse("MIT").unwrap());
+        let license_infos = [
+            license_info("b", mit.clone()),
+            license_info("a", mit),
+            license_info("c", LicenseState::NoLicense),
+        ];
+        let license_infos: Vec<&LicenseInfo> = license_infos.iter().collect();
+        assert_eq!(
+            license_groups(&license_infos),
+            vec![
+                json!({ "license": "MIT", "count": 2, "packages": ["a", "b"] }),
+                json!({ "license": "no license", "count": 1, "packages": ["c"] }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_render_template() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("summary.tera");
+        std::fs::write(
+            &path,
+            "{% for group in licenses %}{{ group.license }}: {{ group.count }}\n{% endfor %}",
+        )
+        .unwrap();
+        let context = json!({ "licenses": [{ "license": "MIT", "count": 2 }] });
+        assert_eq!(
+            render_template(Some(&path), context.clone()).unwrap(),
+            "MIT: 2\n"
+        );
+        assert!(render_template(None, context).is_err());
+    }
+}
@@ -0,0 +1,121 @@
+use std::io::Write;
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+use rattler_conda_types::Platform;
+use serde::Serialize;
+use serde_json::json;
+
+use crate::{
+    collect_dependency_graphs, json_output::write_json_document, CondaDenyWhyConfig, OutputFormat,
+};
+
+/// The dependency chains that pull in a package in one environment on one platform
+#[derive(Debug, Clone, Serialize)]
+struct DependencyChains {
+    environment: String,
+    platform: Option<Platform>,
+    /// Shortest chains from the direct dependencies to the package
+    paths: Vec<Vec<String>>,
+}
+
+pub fn why<W: Write>(config: CondaDenyWhyConfig, mut out: W) -> Result<()> {
+    let graphs = collect_dependency_graphs(&config.lockfile_or_prefix)
+        .with_context(|| "Building the dependency graph failed.")?;
+
+    let chains: Vec<DependencyChains> = graphs
+        .into_iter()
+        .filter(|environment_graph| environment_graph.graph.contains(&config.package_name))
+        .map(|environment_graph| DependencyChains {
+            paths: environment_graph
+                .graph
+                .paths_from_direct_dependencies(&config.package_name),
+            environment: environment_graph.environment,
+            platform: environment_graph.platform,
+        })
+        .collect();
+
+    if chains.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Package {} was not found",
+            config.package_name
+        ));
+    }
+
+    match config.output_format {
+        OutputFormat::Default => {
+            let mut output = String::new();
+            for chain in &chains {
+                match chain.platform {
+                    Some(platform) => output.push_str(&format!(
+                        "{} ({}):\n",
+                        chain.environment.bold(),
+                        platform.to_string().bright_purple()
+                    )),
+                    None => output.push_str(&format!("{}:\n", chain.environment.bold())),
+                }
+                if chain.paths.is_empty() {
+                    output.push_str(&format!(
+                        "  {}\n",
+                        "Not pulled in by any direct dependency".bright_black()
+                    ));
+                }
+                for path in &chain.paths {
+                    output.push_str(&format!("  {}\n", format_path(path)));
+                }
+            }
+            write!(out, "{output}")?;
+        }
+        OutputFormat::Json | OutputFormat::JsonPretty => {
+            write_json_document(
+                &mut out,
+                "why",
+                &config.lockfile_or_prefix,
+                json!({ "package_name": config.package_name, "chains": chains }),
+                config.output_format,
+            )?;
+        }
+        OutputFormat::Markdown
+        | OutputFormat::Sarif
+        | OutputFormat::Junit
+        | OutputFormat::Template => {
+            unreachable!("Unsupported output formats are rejected by get_config_options")
+        }
+        OutputFormat::Csv => {
+            #[derive(Debug, Clone, Serialize)]
+            struct DependencyChainRow {
+                environment: String,
+                platform: Option<Platform>,
+                path: String,
+            }
+
+            let mut writer = csv::WriterBuilder::new().from_writer(vec![]);
+            for chain in chains {
+                for path in chain.paths {
+                    let row = DependencyChainRow {
+                        environment: chain.environment.clone(),
+                        platform: chain.platform,
+                        path: path.join(" -> "),
+                    };
+                    writer.serialize(&row).with_context(|| {
+                        format!("Failed to serialize the following dependency chain: {row:?}")
+                    })?;
+                }
+            }
+
+            out.write_all(&writer.into_inner()?)?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+fn format_path(path: &[String]) -> String {
+    let (package, chain) = path.split_last().expect("Paths are never empty");
+    chain
+        .iter()
+        .map(|name| name.blue().to_string())
+        .chain(std::iter::once(package.yellow().to_string()))
+        .collect::<Vec<_>>()
+        .join(" → ")
+}
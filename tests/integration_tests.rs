@@ -2,11 +2,18 @@
 use assert_cmd::prelude::*;
 use conda_deny::bundle::bundle;
 use conda_deny::cli::CondaDenyCliConfig;
+use conda_deny::diff::diff;
+use conda_deny::explain::explain;
+use conda_deny::licenses::licenses;
+use conda_deny::query::{Field, Filter, GroupBy};
+use conda_deny::report::report;
+use conda_deny::sbom::sbom;
+use conda_deny::why::why;
 use conda_deny::{
     check::check, get_config_options, list::list, CondaDenyCheckConfig, CondaDenyConfig,
     CondaDenyListConfig,
 };
-use conda_deny::{CondaDenyBundleConfig, OutputFormat};
+use conda_deny::{CondaDenyBundleConfig, FailOn, OutputFormat, ReasonAction, SbomFormat};
 use rattler_conda_types::Platform;
 use rstest::{fixture, rstest};
 use std::io::Write;
@@ -38,6 +45,19 @@ packages:
   license: MIT
 "#;
 
+/// The packages of a check or diff JSON document with the given result.
+fn packages_with_result<'a>(
+    output: &'a serde_json::Value,
+    result: &str,
+) -> Vec<&'a serde_json::Value> {
+    output["packages"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|package| package["result"] == result)
+        .collect()
+}
+
 #[fixture]
 #[once]
 fn colored_control() {
@@ -62,6 +82,12 @@ fn list_config(
         environment,
         ignore_pypi,
         output,
+        template: None,
+        filter: None,
+        sort_by: None,
+        columns: None,
+        aggregate: None,
+        group_by: None,
     };
 
     let config = get_config_options(config, cli).unwrap();
@@ -81,6 +107,7 @@ fn bundle_config(
     #[default(None)] environment: Option<Vec<String>>,
     #[default(None)] ignore_pypi: Option<bool>,
     #[default(None)] directory: Option<PathBuf>,
+    #[default(None)] notice_file: Option<PathBuf>,
 ) -> CondaDenyBundleConfig {
     let cli = CondaDenyCliConfig::Bundle {
         lockfile,
@@ -89,6 +116,7 @@ fn bundle_config(
         environment,
         ignore_pypi,
         directory,
+        notice_file,
     };
 
     let config = get_config_options(config, cli).unwrap();
@@ -116,8 +144,20 @@ fn check_config(
         platform,
         environment,
         osi,
+        policy: None,
+        fail_on: None,
+        pulled_in_by: false,
+        runtime_closure: None,
+        baseline: None,
+        write_baseline: None,
+        license_history: None,
         ignore_pypi,
         output,
+        template: None,
+        filter: None,
+        sort_by: None,
+        aggregate: None,
+        group_by: None,
     };
 
     let config = get_config_options(config, cli);
@@ -522,6 +562,97 @@ ignore-packages = [{ package = "beautifulsoup4" }]"#;
     assert_eq!(out, b"");
 }
 
+#[rstest]
+fn test_pypi_ignore_explain_and_sbom(mut out: Vec<u8>) {
+    let mut temp_config_file = NamedTempFile::new().unwrap();
+    let pypi_packages = [
+        "beautifulsoup4",
+        "certifi",
+        "charset-normalizer",
+        "conda-forge-metadata",
+        "deprecated",
+        "idna",
+        "requests",
+        "ruamel-yaml",
+        "ruamel-yaml-clib",
+        "soupsieve",
+        "typing-extensions",
+        "urllib3",
+        "wrapt",
+    ];
+    let ignore_packages: Vec<String> = pypi_packages
+        .iter()
+        .chain(&["python"])
+        .map(|package| format!(r#"{{ package = "{package}" }}"#))
+        .collect();
+    let file_content = format!(
+        r#"[tool.conda-deny]
+license-allowlist = "tests/default_license_allowlist.toml"
+ignore-packages = [{}]"#,
+        ignore_packages.join(", ")
+    );
+    temp_config_file
+        .as_file_mut()
+        .write_all(file_content.as_bytes())
+        .unwrap();
+    let lockfile = Some(vec![
+        "tests/test_pypi_ignore/lockfile_with_pypi_packages.lock".into(),
+    ]);
+
+    let cli = CondaDenyCliConfig::Explain {
+        package: "python".to_string(),
+        lockfile: lockfile.clone(),
+        prefix: None,
+        platform: None,
+        environment: None,
+        osi: None,
+        policy: None,
+        ignore_pypi: Some(false),
+        output: Some(OutputFormat::Json),
+    };
+    let CondaDenyConfig::Explain(explain_config) =
+        get_config_options(Some(temp_config_file.path().to_path_buf()), cli).unwrap()
+    else {
+        panic!()
+    };
+    explain(explain_config, &mut out).unwrap();
+    let output: serde_json::Value = serde_json::from_slice(&out).unwrap();
+    let explanations = output["explanations"].as_array().unwrap();
+    assert_eq!(explanations.len(), 1);
+    assert_eq!(explanations[0]["result"], "ignored");
+    assert_eq!(
+        explanations[0]["ignored_by"],
+        serde_json::json!([{"package": "python"}])
+    );
+
+    let cli = CondaDenyCliConfig::Sbom {
+        lockfile,
+        prefix: None,
+        platform: None,
+        environment: None,
+        ignore_pypi: Some(false),
+        format: SbomFormat::CyclonedxJson,
+        output: None,
+        bundle_directory: None,
+    };
+    let CondaDenyConfig::Sbom(sbom_config) =
+        get_config_options(Some(temp_config_file.path().to_path_buf()), cli).unwrap()
+    else {
+        panic!()
+    };
+    let mut out = Vec::new();
+    sbom(sbom_config, &mut out).unwrap();
+    let bom: serde_json::Value = serde_json::from_slice(&out).unwrap();
+    let components = bom["components"].as_array().unwrap();
+    // Ignored conda packages are still shipped
+    assert!(components
+        .iter()
+        .any(|component| component["name"] == "python"));
+    assert!(!components
+        .iter()
+        .any(|component| component["name"] == "certifi"));
+}
+
 #[rstest]
 #[ignore = "https://github.com/prefix-dev/pixi/issues/6043"]
 fn test_pixi_build_list(
@@ -560,12 +691,14 @@ fn test_list_source_package_variants_with_license(mut out: Vec<u8>, _colored_con
         None,
     );
     let result = list(list_config, &mut out);
-    let stripped_output = String::from_utf8(strip_ansi_escapes::strip(out)).unwrap();
+    let stripped_output = String::from_utf8(strip_ansi_escapes::strip(out))
+        .unwrap()
+        .replace(&temp_lockfile.path().display().to_string(), "pixi.lock");
 
     assert!(result.is_ok(), "{result:?}");
     insta::assert_snapshot!(stripped_output, @r"
-my-package[6652ddb3] @ . (source): no license
-my-package[949d3bf9] @ . (source): MIT
+my-package[6652ddb3] @ . (source) [pixi.lock: default]: no license
+my-package[949d3bf9] @ . (source) [pixi.lock: default]: MIT
 ");
 }
 
@@ -597,14 +730,16 @@ safe-licenses = ["MIT"]
         None,
     );
     let result = check(check_config, &mut out);
-    let stripped_output = String::from_utf8(strip_ansi_escapes::strip(out)).unwrap();
+    let stripped_output = String::from_utf8(strip_ansi_escapes::strip(out))
+        .unwrap()
+        .replace(&temp_lockfile.path().display().to_string(), "pixi.lock");
 
     assert!(result.is_err());
     insta::assert_snapshot!(stripped_output, @r"
 
 ❌ The following dependencies are unsafe:
 
-my-package[6652ddb3] @ . (source): no license
+my-package[6652ddb3] @ . (source) [pixi.lock: default]: no license
 
 ❌ Unsafe licenses found! ❌
 There were 1 safe licenses and 1 unsafe licenses.
@@ -630,10 +765,12 @@ fn test_bundle_prefix() {
         None,
         // DIRECTORY
         Some(temp_dir.path().join(Path::new("test_bundle"))),
+        // NOTICE FILE
+        None,
     );
 
     bundle(bundle_config.clone(), &mut out).unwrap();
-    let bundle_dir = bundle_config.directory.unwrap();
+    let bundle_dir = bundle_config.directory.clone().unwrap();
 
     let mut entries = Vec::new();
 
@@ -676,10 +813,12 @@ fn test_bundle_lockfile() {
         None,
         // DIRECTORY
         Some(temp_dir.path().join(Path::new("test_bundle"))),
+        // NOTICE FILE
+        Some(temp_dir.path().join(Path::new("THIRD_PARTY_NOTICES.md"))),
     );
 
     bundle(bundle_config.clone(), &mut out).unwrap();
-    let bundle_dir = bundle_config.directory.unwrap();
+    let bundle_dir = bundle_config.directory.clone().unwrap();
 
     let mut entries = Vec::new();
 
@@ -701,6 +840,12 @@ fn test_bundle_lockfile() {
 
     let output = String::from_utf8(out).unwrap();
     assert!(output.contains("License files written to:"));
+    assert!(output.contains("Notice file written to:"));
+
+    let notice = std::fs::read_to_string(bundle_config.notice_file.as_ref().unwrap()).unwrap();
+    assert!(notice.starts_with("# Third-party notices"));
+    assert!(notice.contains("- Package: numpy 2.3.2, License: "));
+    assert!(notice.contains("Homepage: "));
 }
 
 #[rstest]
@@ -812,3 +957,1891 @@ fn test_check_checks_source_package_with_record(
     assert!(output.contains("my-source-pkg"));
     assert!(output.contains("GPL-3.0-only"));
 }
+
+#[rstest]
+#[case(None)]
+#[case(Some("lenient"))]
+fn test_policies_check(#[case] policy: Option<&str>, mut out: Vec<u8>, _colored_control: ()) {
+    let mut temp_pixi_toml = NamedTempFile::new().unwrap();
+    let file_content = r#"[tool.conda-deny]
+license-allowlist = "tests/default_license_allowlist.toml"
+lockfile = "tests/default_pixi.lock"
+platform = "linux-64"
+environment = ["default", "lint"]
+
+[tool.conda-deny.policies.lenient]
+osi = true
+environment = "lint""#;
+    temp_pixi_toml
+        .as_file_mut()
+        .write_all(file_content.as_bytes())
+        .unwrap();
+
+    let cli = CondaDenyCliConfig::Check {
+        lockfile: None,
+        prefix: None,
+        platform: None,
+        environment: None,
+        osi: None,
+        policy: policy.map(str::to_string),
+        fail_on: None,
+        pulled_in_by: false,
+        runtime_closure: None,
+        baseline: None,
+        write_baseline: None,
+        license_history: None,
+        ignore_pypi: None,
+        output: None,
+        template: None,
+        filter: None,
+        sort_by: None,
+        aggregate: None,
+        group_by: None,
+    };
+    let CondaDenyConfig::Check(check_config) =
+        get_config_options(Some(temp_pixi_toml.path().to_path_buf()), cli).unwrap()
+    else {
+        panic!();
+    };
+
+    let result = check(check_config, &mut out);
+    let output = String::from_utf8(out).unwrap();
+
+    assert!(result.is_err());
+    insta::assert_snapshot!(
+        format!("policies_check_{}", policy.unwrap_or("mapped")),
+        output
+    );
+}
+
+#[test]
+fn test_unknown_policy() {
+    let mut temp_pixi_toml = NamedTempFile::new().unwrap();
+    let file_content = r#"[tool.conda-deny]
+safe-licenses = ["MIT"]"#;
+    temp_pixi_toml
+        .as_file_mut()
+        .write_all(file_content.as_bytes())
+        .unwrap();
+
+    let cli = CondaDenyCliConfig::Check {
+        lockfile: Some(vec!["tests/default_pixi.lock".into()]),
+        prefix: None,
+        platform: None,
+        environment: None,
+        osi: None,
+        policy: Some("strict".to_string()),
+        fail_on: None,
+        pulled_in_by: false,
+        runtime_closure: None,
+        baseline: None,
+        write_baseline: None,
+        license_history: None,
+        ignore_pypi: None,
+        output: None,
+        template: None,
+        filter: None,
+        sort_by: None,
+        aggregate: None,
+        group_by: None,
+    };
+    let result = get_config_options(Some(temp_pixi_toml.path().to_path_buf()), cli);
+
+    let error = format!("{:?}", result.unwrap_err());
+    assert!(error.contains("Policy 'strict' is not configured"));
+}
+
+#[rstest]
+#[case(vec![FailOn::Disallowed], true, false)]
+#[case(vec![FailOn::Missing], false, true)]
+#[case(vec![FailOn::Warn], false, false)]
+fn test_warn_licenses_check(
+    #[case] fail_on: Vec<FailOn>,
+    #[case] succeeds: bool,
+    #[case] missing_is_unsafe: bool,
+    mut out: Vec<u8>,
+    _colored_control: (),
+) {
+    let mut temp_lockfile = NamedTempFile::new().unwrap();
+    temp_lockfile
+        .as_file_mut()
+        .write_all(SOURCE_PACKAGE_VARIANTS_LOCKFILE.as_bytes())
+        .unwrap();
+
+    let mut temp_config_file = NamedTempFile::new().unwrap();
+    let config_content = r#"[tool.conda-deny]
+safe-licenses = ["Apache-2.0"]
+warn-licenses = ["MIT"]
+"#;
+    temp_config_file
+        .as_file_mut()
+        .write_all(config_content.as_bytes())
+        .unwrap();
+
+    let cli = CondaDenyCliConfig::Check {
+        lockfile: Some(vec![temp_lockfile.path().display().to_string()]),
+        prefix: None,
+        platform: None,
+        environment: None,
+        osi: None,
+        policy: None,
+        fail_on: Some(fail_on),
+        pulled_in_by: false,
+        runtime_closure: None,
+        baseline: None,
+        write_baseline: None,
+        license_history: None,
+        ignore_pypi: None,
+        output: None,
+        template: None,
+        filter: None,
+        sort_by: None,
+        aggregate: None,
+        group_by: None,
+    };
+    let CondaDenyConfig::Check(check_config) =
+        get_config_options(Some(temp_config_file.path().to_path_buf()), cli).unwrap()
+    else {
+        panic!();
+    };
+
+    let result = check(check_config, &mut out);
+    let stripped_output = String::from_utf8(strip_ansi_escapes::strip(out))
+        .unwrap()
+        .replace(&temp_lockfile.path().display().to_string(), "pixi.lock");
+
+    assert_eq!(result.is_ok(), succeeds, "{result:?}");
+    if missing_is_unsafe {
+        insta::assert_snapshot!(stripped_output, @r"
+
+⚠️ The following dependencies require attention:
+
+my-package[949d3bf9] @ . (source) [pixi.lock: default]: MIT
+
+❌ The following dependencies are unsafe:
+
+my-package[6652ddb3] @ . (source) [pixi.lock: default]: no license
+
+❌ Unsafe licenses found! ❌
+There were 0 safe licenses, 1 licenses with warnings and 1 unsafe licenses.
+    ");
+    } else {
+        // Reasons that are not in --fail-on are reported as warnings
+        insta::allow_duplicates! {
+            insta::assert_snapshot!(stripped_output, @r"
+
+⚠️ The following dependencies require attention:
+
+my-package[6652ddb3] @ . (source) [pixi.lock: default]: no license
+my-package[949d3bf9] @ . (source) [pixi.lock: default]: MIT
+
+✅ No unsafe licenses found! ✅
+There were 0 safe licenses, 2 licenses with warnings and 0 unsafe licenses.
+            ");
+        }
+    }
+}
+
+#[rstest]
+fn test_check_json_reasons(mut out: Vec<u8>) {
+    let mut temp_lockfile = NamedTempFile::new().unwrap();
+    temp_lockfile
+        .as_file_mut()
+        .write_all(SOURCE_PACKAGE_VARIANTS_LOCKFILE.as_bytes())
+        .unwrap();
+
+    let mut temp_config_file = NamedTempFile::new().unwrap();
+    let config_content = r#"[tool.conda-deny]
+safe-licenses = ["MIT"]
+deny-licenses = ["MIT"]
+
+[tool.conda-deny.reason-actions]
+missing = "ignore"
+"#;
+    temp_config_file
+        .as_file_mut()
+        .write_all(config_content.as_bytes())
+        .unwrap();
+
+    let check_config = check_config(
+        Some(temp_config_file.path().to_path_buf()),
+        Some(vec![temp_lockfile.path().display().to_string()]),
+        None,
+        None,
+        None,
+        None,
+        None,
+        Some(OutputFormat::JsonPretty),
+    );
+    let result = check(check_config, &mut out);
+    let output: serde_json::Value = serde_json::from_slice(&out).unwrap();
+
+    assert!(result.is_err());
+    assert_eq!(
+        packages_with_result(&output, "unsafe")[0]["reason"],
+        "denied"
+    );
+    assert_eq!(
+        packages_with_result(&output, "ignored")[0]["reason"],
+        "missing"
+    );
+    assert!(packages_with_result(&output, "safe").is_empty());
+}
+
+#[rstest]
+fn test_check_json_explanation(mut out: Vec<u8>) {
+    let mut temp_config_file = NamedTempFile::new().unwrap();
+    let config_content = r#"[tool.conda-deny]
+license-allowlist = "tests/default_license_allowlist.toml"
+safe-licenses = ["MIT"]
+"#;
+    temp_config_file
+        .as_file_mut()
+        .write_all(config_content.as_bytes())
+        .unwrap();
+
+    let check_config = check_config(
+        Some(temp_config_file.path().to_path_buf()),
+        Some(vec!["tests/default_pixi.lock".into()]),
+        None,
+        None,
+        None,
+        None,
+        None,
+        Some(OutputFormat::JsonPretty),
+    );
+    let _ = check(check_config, &mut out);
+    let output: serde_json::Value = serde_json::from_slice(&out).unwrap();
+
+    let requirements: Vec<&serde_json::Value> = packages_with_result(&output, "safe")
+        .into_iter()
+        .flat_map(|info| info["explanation"]["requirements"].as_array().unwrap())
+        .collect();
+    let matched_by = |requirement: &str| {
+        requirements
+            .iter()
+            .find(|evaluation| evaluation["requirement"] == requirement)
+            .map(|evaluation| evaluation["matched"].clone())
+            .unwrap()
+    };
+
+    assert_eq!(
+        matched_by("MIT"),
+        serde_json::json!({"entry": "MIT", "source": {"type": "safe-licenses"}})
+    );
+    assert_eq!(
+        matched_by("Apache-2.0"),
+        serde_json::json!({
+            "entry": "Apache-2.0",
+            "source": {"type": "license-allowlist", "location": "tests/default_license_allowlist.toml"}
+        })
+    );
+
+    let unsafe_requirement = packages_with_result(&output, "unsafe")
+        .into_iter()
+        .flat_map(|info| info["explanation"]["requirements"].as_array().unwrap())
+        .find(|evaluation| evaluation["result"] == "not-allowed")
+        .unwrap();
+    assert!(unsafe_requirement.get("matched").is_none());
+}
+
+#[rstest]
+#[case("archspec", "safe", serde_json::json!([]))]
+#[case("_libgcc_mutex==0.1", "ignored", serde_json::json!([{"package": "_libgcc_mutex"}]))]
+fn test_explain(
+    #[case] package: &str,
+    #[case] result: &str,
+    #[case] ignored_by: serde_json::Value,
+    mut out: Vec<u8>,
+) {
+    let mut temp_config_file = NamedTempFile::new().unwrap();
+    let config_content = r#"[tool.conda-deny]
+license-allowlist = "tests/default_license_allowlist.toml"
+"#;
+    temp_config_file
+        .as_file_mut()
+        .write_all(config_content.as_bytes())
+        .unwrap();
+
+    let cli = CondaDenyCliConfig::Explain {
+        package: package.to_string(),
+        lockfile: Some(vec!["tests/default_pixi.lock".into()]),
+        prefix: None,
+        platform: Some(vec![Platform::Linux64]),
+        environment: Some(vec!["default".into()]),
+        osi: None,
+        policy: None,
+        ignore_pypi: None,
+        output: Some(OutputFormat::JsonPretty),
+    };
+    let CondaDenyConfig::Explain(explain_config) =
+        get_config_options(Some(temp_config_file.path().to_path_buf()), cli).unwrap()
+    else {
+        panic!()
+    };
+
+    explain(explain_config, &mut out).unwrap();
+    let output: serde_json::Value = serde_json::from_slice(&out).unwrap();
+
+    let explanations = output["explanations"].as_array().unwrap();
+    assert!(!explanations.is_empty());
+    for explanation in explanations {
+        assert_eq!(explanation["result"], result);
+        assert_eq!(explanation["ignored_by"], ignored_by);
+        assert_eq!(explanation["policy"], "default");
+    }
+}
+
+#[rstest]
+fn test_explain_unknown_package(mut out: Vec<u8>) {
+    let cli = CondaDenyCliConfig::Explain {
+        package: "not-a-package==1.0".to_string(),
+        lockfile: Some(vec!["tests/default_pixi.lock".into()]),
+        prefix: None,
+        platform: None,
+        environment: None,
+        osi: Some(true),
+        policy: None,
+        ignore_pypi: None,
+        output: None,
+    };
+    let CondaDenyConfig::Explain(explain_config) = get_config_options(None, cli).unwrap() else {
+        panic!()
+    };
+
+    let result = explain(explain_config, &mut out);
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        "Package not-a-package==1.0 was not found"
+    );
+}
+
+#[rstest]
+fn test_why(mut out: Vec<u8>) {
+    let cli = CondaDenyCliConfig::Why {
+        package: "openssl".to_string(),
+        lockfile: Some(vec!["tests/test_default_use_case/pixi.lock".into()]),
+        prefix: None,
+        platform: Some(vec![Platform::Linux64]),
+        environment: Some(vec!["default".into()]),
+        output: Some(OutputFormat::Json),
+    };
+    let CondaDenyConfig::Why(why_config) = get_config_options(None, cli).unwrap() else {
+        panic!()
+    };
+
+    why(why_config, &mut out).unwrap();
+    let output: serde_json::Value = serde_json::from_slice(&out).unwrap();
+
+    assert_eq!(output["chains"][0]["environment"], "default");
+    assert_eq!(output["chains"][0]["platform"], "linux-64");
+    // openssl is a direct dependency in the manifest, so the shortest chain is the package itself
+    assert_eq!(
+        output["chains"][0]["paths"][0],
+        serde_json::json!(["openssl"])
+    );
+    assert_eq!(
+        output["chains"][0]["paths"][1],
+        serde_json::json!(["taplo", "openssl"])
+    );
+}
+
+#[rstest]
+fn test_check_pulled_in_by(mut out: Vec<u8>) {
+    let cli = CondaDenyCliConfig::Check {
+        lockfile: Some(vec!["tests/test_default_use_case/pixi.lock".into()]),
+        prefix: None,
+        platform: Some(vec![Platform::Linux64]),
+        environment: Some(vec!["lint".into()]),
+        osi: Some(true),
+        policy: None,
+        fail_on: None,
+        pulled_in_by: true,
+        runtime_closure: None,
+        baseline: None,
+        write_baseline: None,
+        license_history: None,
+        ignore_pypi: None,
+        output: Some(OutputFormat::Json),
+        template: None,
+        filter: None,
+        sort_by: None,
+        aggregate: None,
+        group_by: None,
+    };
+    let CondaDenyConfig::Check(check_config) = get_config_options(None, cli).unwrap() else {
+        panic!()
+    };
+
+    let _ = check(check_config, &mut out);
+    let output: serde_json::Value = serde_json::from_slice(&out).unwrap();
+
+    let bzip2 = output["packages"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|info| info["package_name"] == "bzip2")
+        .unwrap();
+    assert_eq!(
+        bzip2["pulled_in_by"],
+        serde_json::json!(["pre-commit", "pre-commit-hooks"])
+    );
+}
+
+#[rstest]
+fn test_check_runtime_closure(mut out: Vec<u8>) {
+    let cli = CondaDenyCliConfig::Check {
+        lockfile: Some(vec!["tests/test_default_use_case/pixi.lock".into()]),
+        prefix: None,
+        platform: Some(vec![Platform::Linux64]),
+        environment: Some(vec!["default".into()]),
+        osi: Some(true),
+        policy: None,
+        fail_on: None,
+        pulled_in_by: false,
+        runtime_closure: Some("lint".into()),
+        baseline: None,
+        write_baseline: None,
+        license_history: None,
+        ignore_pypi: None,
+        output: Some(OutputFormat::Json),
+        template: None,
+        filter: None,
+        sort_by: None,
+        aggregate: None,
+        group_by: None,
+    };
+    let CondaDenyConfig::Check(check_config) = get_config_options(None, cli).unwrap() else {
+        panic!()
+    };
+
+    let _ = check(check_config, &mut out);
+    let output: serde_json::Value = serde_json::from_slice(&out).unwrap();
+
+    let package_names = |results: &[&str]| -> Vec<String> {
+        results
+            .iter()
+            .flat_map(|result| packages_with_result(&output, result))
+            .map(|info| info["package_name"].as_str().unwrap().to_string())
+            .collect()
+    };
+    let checked = package_names(&["safe", "warn", "unsafe", "ignored"]);
+    let outside_closure = package_names(&["outside-closure"]);
+
+    // pre-commit is a direct dependency of the lint feature, rust only of the default feature
+    assert!(checked.contains(&"pre-commit".to_string()));
+    assert!(checked.contains(&"python".to_string()));
+    assert!(!checked.contains(&"rust".to_string()));
+    assert!(outside_closure.contains(&"rust".to_string()));
+    assert!(!outside_closure.contains(&"pre-commit".to_string()));
+}
+
+#[rstest]
+fn test_prefix_dependency_type(mut out: Vec<u8>) {
+    let cli = CondaDenyCliConfig::List {
+        lockfile: None,
+        prefix: Some(vec!["tests/test_conda_prefixes/test-env".into()]),
+        platform: None,
+        environment: None,
+        ignore_pypi: None,
+        output: Some(OutputFormat::Json),
+        template: None,
+        filter: None,
+        sort_by: None,
+        columns: None,
+        aggregate: None,
+        group_by: None,
+    };
+    let CondaDenyConfig::List(list_config) = get_config_options(None, cli).unwrap() else {
+        panic!()
+    };
+
+    list(list_config, &mut out).unwrap();
+    let output: serde_json::Value = serde_json::from_slice(&out).unwrap();
+
+    let dependency_type = |package_name: &str| {
+        output["packages"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|info| info["package_name"] == package_name)
+            .unwrap()["dependency_type"]
+            .clone()
+    };
+    assert_eq!(dependency_type("numpy"), "direct");
+    assert_eq!(dependency_type("ipython"), "direct");
+    assert_eq!(dependency_type("openssl"), "transitive");
+}
+
+#[rstest]
+fn test_why_prefix(mut out: Vec<u8>) {
+    let cli = CondaDenyCliConfig::Why {
+        package: "six".to_string(),
+        lockfile: None,
+        prefix: Some(vec!["tests/test_conda_prefixes/test-env".into()]),
+        platform: None,
+        environment: None,
+        output: Some(OutputFormat::Json),
+    };
+    let CondaDenyConfig::Why(why_config) = get_config_options(None, cli).unwrap() else {
+        panic!()
+    };
+
+    why(why_config, &mut out).unwrap();
+    let output: serde_json::Value = serde_json::from_slice(&out).unwrap();
+
+    let paths = output["chains"][0]["paths"].as_array().unwrap();
+    assert!(!paths.is_empty());
+    // Chains start at packages requested in conda-meta/history
+    for path in paths {
+        let direct = path[0].as_str().unwrap();
+        assert!(["ipython", "multipledispatch", "numpy", "pandas"].contains(&direct));
+        assert_eq!(path.as_array().unwrap().last().unwrap(), "six");
+    }
+}
+
+fn diff_cli(old: &str, new: &str, output: OutputFormat) -> CondaDenyCliConfig {
+    CondaDenyCliConfig::Diff {
+        old: old.to_string(),
+        new: new.to_string(),
+        platform: Some(vec![Platform::Linux64]),
+        environment: Some(vec!["default".into()]),
+        osi: Some(true),
+        policy: None,
+        fail_on: None,
+        ignore_pypi: None,
+        output: Some(output),
+    }
+}
+
+#[rstest]
+#[case("tests/default_pixi.lock")]
+#[case("git:HEAD:tests/default_pixi.lock")]
+fn test_diff(#[case] old: &str, mut out: Vec<u8>) {
+    let cli = diff_cli(
+        old,
+        "tests/test_default_use_case/pixi.lock",
+        OutputFormat::Json,
+    );
+    let CondaDenyConfig::Diff(diff_config) = get_config_options(None, cli).unwrap() else {
+        panic!()
+    };
+
+    diff(diff_config, &mut out).unwrap();
+    let output: serde_json::Value = serde_json::from_slice(&out).unwrap();
+
+    assert_eq!(output["old"], old);
+    assert_eq!(
+        output["changes"],
+        serde_json::json!([{
+            "change": "added",
+            "package_name": "k9s",
+            "platform": "linux-64",
+            "old_version": null,
+            "new_version": "0.40.5",
+            "old_license": null,
+            "new_license": "Apache-2.0",
+            "license_changed": false,
+            "result": "safe",
+            "reason": null,
+            "policy": null,
+        }])
+    );
+    let checked: Vec<&serde_json::Value> = packages_with_result(&output, "safe")
+        .into_iter()
+        .map(|package| &package["package_name"])
+        .collect();
+    assert_eq!(checked, vec!["k9s"]);
+    assert_eq!(output["license_changes"], serde_json::json!([]));
+    assert_eq!(output["inconsistent_licenses"], serde_json::json!([]));
+}
+
+#[rstest]
+fn test_diff_markdown(mut out: Vec<u8>) {
+    let cli = diff_cli(
+        "tests/test_default_use_case/pixi.lock",
+        "tests/default_pixi.lock",
+        OutputFormat::Markdown,
+    );
+    let CondaDenyConfig::Diff(diff_config) = get_config_options(None, cli).unwrap() else {
+        panic!()
+    };
+
+    diff(diff_config, &mut out).unwrap();
+    let output = String::from_utf8(out).unwrap();
+
+    assert!(output.contains("1 removed"));
+    assert!(output.contains("| removed | k9s | linux-64 | 0.40.5 | Apache-2.0 |  |"));
+}
+
+fn baseline_cli(baseline: Option<PathBuf>, write_baseline: Option<PathBuf>) -> CondaDenyCliConfig {
+    CondaDenyCliConfig::Check {
+        lockfile: Some(vec!["tests/test_default_use_case/pixi.lock".into()]),
+        prefix: None,
+        platform: Some(vec![Platform::Linux64]),
+        environment: Some(vec!["default".into()]),
+        osi: Some(true),
+        policy: None,
+        fail_on: None,
+        pulled_in_by: false,
+        runtime_closure: None,
+        baseline,
+        write_baseline,
+        license_history: None,
+        ignore_pypi: None,
+        output: Some(OutputFormat::Json),
+        template: None,
+        filter: None,
+        sort_by: None,
+        aggregate: None,
+        group_by: None,
+    }
+}
+
+#[rstest]
+fn test_check_baseline() {
+    let baseline_dir = tempfile::tempdir().unwrap();
+    let baseline_path = baseline_dir.path().join("conda-deny-baseline.json");
+
+    // Without a baseline, the check fails
+    let CondaDenyConfig::Check(check_config) =
+        get_config_options(None, baseline_cli(None, None)).unwrap()
+    else {
+        panic!()
+    };
+    assert!(check(check_config, &mut Vec::new()).is_err());
+
+    // Writing the baseline accepts the current findings
+    let CondaDenyConfig::Check(check_config) =
+        get_config_options(None, baseline_cli(None, Some(baseline_path.clone()))).unwrap()
+    else {
+        panic!()
+    };
+    check(check_config, &mut Vec::new()).unwrap();
+
+    let mut baseline: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&baseline_path).unwrap()).unwrap();
+    let entries = baseline["entries"].as_array_mut().unwrap();
+    assert!(!entries.is_empty());
+    entries.push(serde_json::json!({
+        "package": "not-installed",
+        "version": "==1.0",
+        "license": "Proprietary",
+    }));
+    std::fs::write(&baseline_path, baseline.to_string()).unwrap();
+
+    let CondaDenyConfig::Check(check_config) =
+        get_config_options(None, baseline_cli(Some(baseline_path), None)).unwrap()
+    else {
+        panic!()
+    };
+    let mut out = Vec::new();
+    check(check_config, &mut out).unwrap();
+    let output: serde_json::Value = serde_json::from_slice(&out).unwrap();
+
+    assert!(packages_with_result(&output, "unsafe").is_empty());
+    assert!(!packages_with_result(&output, "baselined").is_empty());
+    assert_eq!(
+        output["fixed_baseline"],
+        serde_json::json!([{
+            "package": "not-installed",
+            "version": "==1.0",
+            "license": "Proprietary",
+        }])
+    );
+}
+
+#[test]
+fn test_check_license_history() {
+    let mut history = NamedTempFile::new().unwrap();
+    let recorded = serde_json::json!({
+        "license_infos": [{
+            "package_name": "k9s",
+            "version": "0.40.0",
+            "platform": "linux-64",
+            "license": {"Valid": "MIT"},
+        }]
+    });
+    write!(history, "{recorded}").unwrap();
+
+    let cli = CondaDenyCliConfig::Check {
+        lockfile: Some(vec!["tests/test_default_use_case/pixi.lock".into()]),
+        prefix: None,
+        platform: Some(vec![Platform::Linux64]),
+        environment: Some(vec!["default".into()]),
+        osi: Some(true),
+        policy: None,
+        fail_on: None,
+        pulled_in_by: false,
+        runtime_closure: None,
+        baseline: None,
+        write_baseline: None,
+        license_history: Some(history.path().to_path_buf()),
+        ignore_pypi: None,
+        output: Some(OutputFormat::Json),
+        template: None,
+        filter: None,
+        sort_by: None,
+        aggregate: None,
+        group_by: None,
+    };
+    let CondaDenyConfig::Check(mut check_config) = get_config_options(None, cli).unwrap() else {
+        panic!()
+    };
+    check_config.license_changes = ReasonAction::Fail;
+
+    let mut out = Vec::new();
+    let result = check(check_config, &mut out);
+    let output: serde_json::Value = serde_json::from_slice(&out).unwrap();
+
+    assert_eq!(
+        output["license_changes"],
+        serde_json::json!([{
+            "package_name": "k9s",
+            "platform": "linux-64",
+            "old_version": "0.40.0",
+            "new_version": "0.40.5",
+            "old_license": "MIT",
+            "new_license": "Apache-2.0",
+        }])
+    );
+    assert!(result.unwrap_err().to_string().contains("license changes"));
+}
+
+#[rstest]
+#[case("warn", true)]
+#[case("fail", false)]
+fn test_check_inconsistent_licenses(
+    #[case] action: &str,
+    #[case] passes: bool,
+    mut out: Vec<u8>,
+    _colored_control: (),
+) {
+    let mut temp_config_file = NamedTempFile::new().unwrap();
+    let file_content = format!(
+        r#"[tool.conda-deny]
+lockfile = "tests/test_inconsistent_licenses/pixi.lock"
+safe-licenses = ["MIT", "Apache-2.0"]
+inconsistent-licenses = "{action}""#
+    );
+    temp_config_file
+        .as_file_mut()
+        .write_all(file_content.as_bytes())
+        .unwrap();
+
+    let check_config = check_config(
+        Some(temp_config_file.path().to_path_buf()),
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+    );
+    let result = check(check_config, &mut out);
+    let output = String::from_utf8(strip_ansi_escapes::strip(out)).unwrap();
+
+    assert_eq!(result.is_ok(), passes, "{result:?}");
+    assert!(output.contains(
+        "k9s 0.40.5:\n    Apache-2.0: linux-64 he91c749_1\n    MIT: win-64 h36c15f3_1\n"
+    ));
+    assert!(output.contains("1 packages with inconsistent licenses"));
+}
+
+#[rstest]
+fn test_check_sarif(mut out: Vec<u8>) {
+    let lockfile = "tests/test_default_use_case/pixi.lock";
+    let cli = CondaDenyCliConfig::Check {
+        lockfile: Some(vec![lockfile.into()]),
+        prefix: None,
+        platform: Some(vec![Platform::Linux64]),
+        environment: Some(vec!["default".into()]),
+        osi: Some(true),
+        policy: None,
+        fail_on: None,
+        pulled_in_by: false,
+        runtime_closure: None,
+        baseline: None,
+        write_baseline: None,
+        license_history: None,
+        ignore_pypi: None,
+        output: Some(OutputFormat::Sarif),
+        template: None,
+        filter: None,
+        sort_by: None,
+        aggregate: None,
+        group_by: None,
+    };
+    let CondaDenyConfig::Check(check_config) = get_config_options(None, cli).unwrap() else {
+        panic!()
+    };
+
+    assert!(check(check_config, &mut out).is_err());
+    let sarif: serde_json::Value = serde_json::from_slice(&out).unwrap();
+    assert_eq!(sarif["version"], "2.1.0");
+
+    let run = &sarif["runs"][0];
+    assert_eq!(run["tool"]["driver"]["name"], "conda-deny");
+    let results = run["results"].as_array().unwrap();
+    assert!(!results.is_empty());
+
+    let lockfile_lines: Vec<String> = std::fs::read_to_string(lockfile)
+        .unwrap()
+        .lines()
+        .map(str::to_string)
+        .collect();
+    for result in results {
+        assert_eq!(result["level"], "error");
+        assert!(["disallowed", "non-spdx"].contains(&result["ruleId"].as_str().unwrap()));
+
+        let location = &result["locations"][0]["physicalLocation"];
+        assert_eq!(location["artifactLocation"]["uri"], lockfile);
+        let line = location["region"]["startLine"].as_u64().unwrap() as usize;
+        let package = result["properties"]["package"].as_str().unwrap();
+        assert!(
+            lockfile_lines[line - 1].contains(&format!("/{package}-")),
+            "{package} is not declared in line {line}"
+        );
+    }
+}
+
+#[rstest]
+fn test_check_junit(mut out: Vec<u8>) {
+    let mut temp_config_file = NamedTempFile::new().unwrap();
+    let file_content = r#"[tool.conda-deny]
+lockfile = "tests/test_default_use_case/pixi.lock"
+platform = "linux-64"
+environment = "default"
+osi = true
+
+[tool.conda-deny.reason-actions]
+non-spdx = "ignore""#;
+    temp_config_file
+        .as_file_mut()
+        .write_all(file_content.as_bytes())
+        .unwrap();
+
+    let check_config = check_config(
+        Some(temp_config_file.path().to_path_buf()),
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        Some(OutputFormat::Junit),
+    );
+    assert!(check(check_config, &mut out).is_err());
+    let output = String::from_utf8(out).unwrap();
+
+    assert!(output.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites"));
+    assert!(output
+        .contains("<testsuite name=\"tests/test_default_use_case/pixi.lock:default:linux-64\""));
+    assert!(output.contains(
+        "name=\"_libgcc_mutex 0.1 (conda_forge)\">\n      <skipped message=\"ignored (non-spdx: None)\"/>"
+    ));
+    assert!(output.contains("<failure type=\"disallowed\" message=\"disallowed: bzip2-1.0.6\">"));
+
+    let failures = output.matches("<failure ").count();
+    assert!(output.contains(&format!("failures=\"{failures}\"")));
+}
+
+#[rstest]
+fn test_check_sarif_and_junit_ignore_packages() {
+    let mut temp_config_file = NamedTempFile::new().unwrap();
+    let file_content = r#"[tool.conda-deny]
+lockfile = "tests/test_default_use_case/pixi.lock"
+platform = "linux-64"
+environment = "default"
+osi = true
+ignore-packages = [{ package = "bzip2" }]"#;
+    temp_config_file
+        .as_file_mut()
+        .write_all(file_content.as_bytes())
+        .unwrap();
+
+    let output_of = |output_format| {
+        let check_config = check_config(
+            Some(temp_config_file.path().to_path_buf()),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            Some(output_format),
+        );
+        let mut out = Vec::new();
+        assert!(check(check_config, &mut out).is_err());
+        String::from_utf8(out).unwrap()
+    };
+
+    let sarif: serde_json::Value = serde_json::from_str(&output_of(OutputFormat::Sarif)).unwrap();
+    let bzip2 = sarif["runs"][0]["results"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|result| result["properties"]["package"] == "bzip2")
+        .unwrap();
+    assert_eq!(bzip2["level"], "note");
+    assert_eq!(
+        bzip2["suppressions"],
+        serde_json::json!([{
+            "kind": "external",
+            "justification": "Ignored by ignore-packages: bzip2",
+        }])
+    );
+
+    let junit = output_of(OutputFormat::Junit);
+    assert!(junit.contains(
+        "name=\"bzip2 1.0.8 (hd590300_5)\">\n      <skipped message=\"ignored by ignore-packages: bzip2 (disallowed: bzip2-1.0.6)\"/>"
+    ));
+}
+
+#[rstest]
+fn test_check_junit_multiple_lockfiles(mut out: Vec<u8>) {
+    let check_config = check_config(
+        None,
+        Some(vec![
+            "tests/test_default_use_case/pixi.lock".into(),
+            "tests/test_inconsistent_licenses/pixi.lock".into(),
+        ]),
+        None,
+        Some(vec![Platform::Linux64]),
+        None,
+        Some(true),
+        None,
+        Some(OutputFormat::Junit),
+    );
+    assert!(check(check_config, &mut out).is_err());
+    let output = String::from_utf8(out).unwrap();
+
+    for lockfile in [
+        "tests/test_default_use_case/pixi.lock",
+        "tests/test_inconsistent_licenses/pixi.lock",
+    ] {
+        assert!(output.contains(&format!("<testsuite name=\"{lockfile}:default:linux-64\"")));
+    }
+    assert!(!output.contains("<testsuite name=\"default:"));
+}
+
+#[rstest]
+#[case("check")]
+#[case("list")]
+fn test_markdown_output(#[case] subcommand: &str, mut out: Vec<u8>) {
+    let mut temp_config_file = NamedTempFile::new().unwrap();
+    let file_content = r#"[tool.conda-deny]
+lockfile = "tests/test_inconsistent_licenses/pixi.lock"
+safe-licenses = ["Apache-2.0"]"#;
+    temp_config_file
+        .as_file_mut()
+        .write_all(file_content.as_bytes())
+        .unwrap();
+    let config = Some(temp_config_file.path().to_path_buf());
+
+    if subcommand == "check" {
+        let check_config = check_config(
+            config,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            Some(OutputFormat::Markdown),
+        );
+        assert!(check(check_config, &mut out).is_err());
+    } else {
+        let list_config = list_config(
+            config,
+            None,
+            None,
+            None,
+            None,
+            None,
+            Some(OutputFormat::Markdown),
+        );
+        list(list_config, &mut out).unwrap();
+    }
+    let output = String::from_utf8(out).unwrap();
+    insta::assert_snapshot!(format!("{subcommand}_markdown"), output);
+}
+
+#[rstest]
+fn test_report_html(mut out: Vec<u8>) {
+    let report_dir = tempfile::tempdir().unwrap();
+    let bundle_directory = report_dir.path().join("bundle");
+    let license_directory = bundle_directory.join("k9s-0.40.5-he91c749_1");
+    std::fs::create_dir_all(&license_directory).unwrap();
+    std::fs::write(license_directory.join("LICENSE"), "Apache License").unwrap();
+    let html_path = report_dir.path().join("report.html");
+
+    let cli = CondaDenyCliConfig::Report {
+        lockfile: Some(vec!["tests/test_inconsistent_licenses/pixi.lock".into()]),
+        prefix: None,
+        platform: None,
+        environment: None,
+        osi: Some(true),
+        policy: None,
+        ignore_pypi: None,
+        html: html_path.clone(),
+        bundle_directory: Some(bundle_directory),
+    };
+    let CondaDenyConfig::Report(report_config) = get_config_options(None, cli).unwrap() else {
+        panic!()
+    };
+    report(report_config, &mut out).unwrap();
+
+    let html = std::fs::read_to_string(&html_path).unwrap();
+    assert!(html.starts_with("<!DOCTYPE html>"));
+    assert!(html.contains("✅ The license check passed"));
+    // Packages, histogram and breakdowns
+    assert!(html.contains(
+        "<tr class=\"safe\" data-verdict=\"safe\"><td>k9s</td><td>0.40.5</td><td>linux-64</td>"
+    ));
+    assert!(html.contains("<tr><td>Apache-2.0</td><td>1</td>"));
+    assert!(html.contains("<tr><td>win-64</td><td>0</td><td>0</td><td>1</td>"));
+    assert!(html.contains("<tr><td>default</td><td>0</td><td>0</td><td>2</td>"));
+    // Links to the bundled license texts are relative to the report
+    assert!(html.contains("<a href=\"bundle/k9s-0.40.5-he91c749_1/LICENSE\">LICENSE</a>"));
+}
+
+#[rstest]
+fn test_sbom_cyclonedx_prefix(mut out: Vec<u8>) {
+    let cli = CondaDenyCliConfig::Sbom {
+        lockfile: None,
+        prefix: Some(vec!["tests/test_conda_prefixes/test-env".into()]),
+        platform: None,
+        environment: None,
+        ignore_pypi: None,
+        format: SbomFormat::CyclonedxJson,
+        output: None,
+        bundle_directory: None,
+    };
+    let CondaDenyConfig::Sbom(sbom_config) = get_config_options(None, cli).unwrap() else {
+        panic!()
+    };
+    sbom(sbom_config, &mut out).unwrap();
+
+    let bom: serde_json::Value = serde_json::from_slice(&out).unwrap();
+    assert_eq!(bom["bomFormat"], "CycloneDX");
+    assert_eq!(bom["specVersion"], "1.5");
+    let components = bom["components"].as_array().unwrap();
+    assert_eq!(components.len(), 49);
+
+    let ipython_purl =
+        "pkg:conda/ipython@8.28.0?build=pyh707e725_0&channel=conda-forge&subdir=noarch&type=conda";
+    let ipython = components
+        .iter()
+        .find(|component| component["name"] == "ipython")
+        .unwrap();
+    assert_eq!(ipython["purl"], ipython_purl);
+    assert_eq!(ipython["bom-ref"], ipython_purl);
+    assert_eq!(ipython["licenses"][0]["expression"], "BSD-3-Clause");
+    assert_eq!(ipython["hashes"][0]["alg"], "SHA-256");
+
+    let dependencies = bom["dependencies"].as_array().unwrap();
+    assert_eq!(dependencies.len(), components.len());
+    let ipython_dependencies = dependencies
+        .iter()
+        .find(|dependency| dependency["ref"] == ipython_purl)
+        .unwrap()["dependsOn"]
+        .as_array()
+        .unwrap();
+    assert!(ipython_dependencies.contains(&serde_json::json!(
+        "pkg:conda/python@3.13.0?build=h206b6c5_100_cp313&channel=conda-forge&subdir=osx-arm64&type=conda"
+    )));
+}
+
+#[rstest]
+fn test_sbom_cyclonedx_lockfile_to_file(mut out: Vec<u8>) {
+    let sbom_dir = tempfile::tempdir().unwrap();
+    let output = sbom_dir.path().join("sbom.json");
+    let cli = CondaDenyCliConfig::Sbom {
+        lockfile: Some(vec!["tests/test_inconsistent_licenses/pixi.lock".into()]),
+        prefix: None,
+        platform: Some(vec![Platform::Linux64]),
+        environment: None,
+        ignore_pypi: None,
+        format: SbomFormat::CyclonedxJson,
+        output: Some(output.clone()),
+        bundle_directory: None,
+    };
+    let CondaDenyConfig::Sbom(sbom_config) = get_config_options(None, cli).unwrap() else {
+        panic!()
+    };
+    sbom(sbom_config, &mut out).unwrap();
+    assert!(String::from_utf8(out).unwrap().contains("Wrote the SBOM"));
+
+    let bom: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&output).unwrap()).unwrap();
+    let components = bom["components"].as_array().unwrap();
+    assert_eq!(components.len(), 1);
+    assert_eq!(
+        components[0]["hashes"][1],
+        serde_json::json!({ "alg": "MD5", "content": "900661eac2fb25bce3e1d618ca34cb96" })
+    );
+    assert_eq!(
+        components[0]["externalReferences"][0]["url"],
+        "https://conda.anaconda.org/conda-forge/linux-64/k9s-0.40.5-he91c749_1.conda"
+    );
+}
+
+#[rstest]
+fn test_sbom_spdx_json(mut out: Vec<u8>) {
+    let sbom_dir = tempfile::tempdir().unwrap();
+    let bundle_directory = sbom_dir.path().join("bundle");
+    let license_directory = bundle_directory.join("xz-5.2.6-h166bdaf_0");
+    std::fs::create_dir_all(&license_directory).unwrap();
+    std::fs::write(license_directory.join("COPYING"), "XZ Utils Licensing").unwrap();
+
+    let cli = CondaDenyCliConfig::Sbom {
+        lockfile: Some(vec!["tests/test_default_use_case/pixi.lock".into()]),
+        prefix: None,
+        platform: Some(vec![Platform::Linux64]),
+        environment: Some(vec!["default".into()]),
+        ignore_pypi: None,
+        format: SbomFormat::SpdxJson,
+        output: None,
+        bundle_directory: Some(bundle_directory),
+    };
+    let CondaDenyConfig::Sbom(sbom_config) = get_config_options(None, cli).unwrap() else {
+        panic!()
+    };
+    sbom(sbom_config, &mut out).unwrap();
+
+    let document: serde_json::Value = serde_json::from_slice(&out).unwrap();
+    assert_eq!(document["spdxVersion"], "SPDX-2.3");
+    assert_eq!(document["SPDXID"], "SPDXRef-DOCUMENT");
+    let packages = document["packages"].as_array().unwrap();
+    let package = |name: &str| {
+        packages
+            .iter()
+            .find(|package| package["name"] == name)
+            .unwrap()
+    };
+
+    // Declared as in the metadata, concluded as normalized SPDX expression
+    let archspec = package("archspec");
+    assert_eq!(archspec["licenseDeclared"], "MIT OR Apache-2.0");
+    assert_eq!(archspec["licenseConcluded"], "Apache-2.0 OR MIT");
+    assert_eq!(
+        archspec["externalRefs"][0]["referenceLocator"],
+        "pkg:conda/archspec@0.2.3?build=pyhd8ed1ab_0&channel=conda-forge&subdir=noarch&type=conda"
+    );
+
+    // Non-SPDX licenses refer to the bundled license texts
+    let xz = package("xz");
+    assert_eq!(
+        xz["licenseDeclared"],
+        "LicenseRef-xz-5.2.6-h166bdaf-0-linux-64"
+    );
+    assert_eq!(xz["licenseConcluded"], "NOASSERTION");
+    let extracted_licenses = document["hasExtractedLicensingInfos"].as_array().unwrap();
+    let xz_license = extracted_licenses
+        .iter()
+        .find(|license| license["licenseId"] == xz["licenseDeclared"])
+        .unwrap();
+    assert_eq!(xz_license["name"], "LGPL-2.1 and GPL-2.0");
+    assert_eq!(
+        xz_license["extractedText"],
+        "COPYING:\n\nXZ Utils Licensing"
+    );
+
+    let relationships = document["relationships"].as_array().unwrap();
+    assert!(relationships.contains(&serde_json::json!({
+        "spdxElementId": xz["SPDXID"],
+        "relationshipType": "DEPENDS_ON",
+        "relatedSpdxElement": package("libgcc-ng")["SPDXID"],
+    })));
+    assert!(relationships
+        .iter()
+        .any(|relationship| relationship["relationshipType"] == "DESCRIBES"));
+}
+
+#[rstest]
+fn test_sbom_spdx_tag_value(mut out: Vec<u8>) {
+    let cli = CondaDenyCliConfig::Sbom {
+        lockfile: Some(vec!["tests/test_inconsistent_licenses/pixi.lock".into()]),
+        prefix: None,
+        platform: Some(vec![Platform::Win64]),
+        environment: None,
+        ignore_pypi: None,
+        format: SbomFormat::SpdxTv,
+        output: None,
+        bundle_directory: None,
+    };
+    let CondaDenyConfig::Sbom(sbom_config) = get_config_options(None, cli).unwrap() else {
+        panic!()
+    };
+    sbom(sbom_config, &mut out).unwrap();
+
+    let document = String::from_utf8(out).unwrap();
+    assert!(document.starts_with("SPDXVersion: SPDX-2.3\nDataLicense: CC0-1.0\n"));
+    for line in [
+        "PackageName: k9s",
+        "SPDXID: SPDXRef-Package-k9s-0.40.5-h36c15f3-1-win-64",
+        "PackageChecksum: MD5: bdf2f361058dd5b238b3468b0f824f30",
+        "PackageLicenseDeclared: MIT",
+        "Relationship: SPDXRef-DOCUMENT DESCRIBES SPDXRef-Package-k9s-0.40.5-h36c15f3-1-win-64",
+    ] {
+        assert!(
+            document.lines().any(|l| l == line),
+            "{line} not in {document}"
+        );
+    }
+}
+
+#[rstest]
+fn test_check_template(mut out: Vec<u8>) {
+    let mut template = NamedTempFile::new().unwrap();
+    write!(
+        template,
+        "{{{{ metadata.command }}}}: {{{{ summary.safe }}}} safe, {{{{ summary.unsafe }}}} unsafe\n\
+         {{% for package in unsafe %}}{{{{ package.name }}}} ({{{{ package.reason }}}})\n{{% endfor %}}\
+         {{% if summary.passed %}}passed{{% else %}}failed{{% endif %}}\n"
+    )
+    .unwrap();
+
+    let cli = CondaDenyCliConfig::Check {
+        lockfile: Some(vec!["tests/test_default_use_case/pixi.lock".into()]),
+        prefix: None,
+        platform: Some(vec![Platform::Linux64]),
+        environment: Some(vec!["default".into()]),
+        osi: Some(true),
+        policy: None,
+        fail_on: None,
+        pulled_in_by: false,
+        runtime_closure: None,
+        baseline: None,
+        write_baseline: None,
+        license_history: None,
+        ignore_pypi: None,
+        // --template implies --output template
+        output: None,
+        template: Some(template.path().to_path_buf()),
+        filter: None,
+        sort_by: None,
+        aggregate: None,
+        group_by: None,
+    };
+    let CondaDenyConfig::Check(check_config) = get_config_options(None, cli).unwrap() else {
+        panic!()
+    };
+    assert!(matches!(check_config.output_format, OutputFormat::Template));
+
+    let result = check(check_config, &mut out);
+    assert!(result.is_err());
+    insta::assert_snapshot!(String::from_utf8(out).unwrap());
+}
+
+#[rstest]
+fn test_list_template(mut out: Vec<u8>) {
+    let mut template = NamedTempFile::new().unwrap();
+    write!(
+        template,
+        "{{{{ summary.total }}}} packages\n\
+         {{% for group in licenses %}}{{{{ group.license }}}}: {{{{ group.packages | join(sep=\", \") }}}}\n{{% endfor %}}"
+    )
+    .unwrap();
+
+    let cli = CondaDenyCliConfig::List {
+        lockfile: None,
+        prefix: Some(vec!["tests/test_conda_prefixes/test-env".into()]),
+        platform: None,
+        environment: None,
+        ignore_pypi: None,
+        output: Some(OutputFormat::Template),
+        template: Some(template.path().to_path_buf()),
+        filter: None,
+        sort_by: None,
+        columns: None,
+        aggregate: None,
+        group_by: None,
+    };
+    let CondaDenyConfig::List(list_config) = get_config_options(None, cli).unwrap() else {
+        panic!()
+    };
+
+    list(list_config, &mut out).unwrap();
+    insta::assert_snapshot!(String::from_utf8(out).unwrap());
+}
+
+#[rstest]
+fn test_template_requires_template_output() {
+    let cli = CondaDenyCliConfig::List {
+        lockfile: None,
+        prefix: Some(vec!["tests/test_conda_prefixes/test-env".into()]),
+        platform: None,
+        environment: None,
+        ignore_pypi: None,
+        output: Some(OutputFormat::Json),
+        template: Some("licenses.tera".into()),
+        filter: None,
+        sort_by: None,
+        columns: None,
+        aggregate: None,
+        group_by: None,
+    };
+    let error = get_config_options(None, cli).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "--template can only be used with --output template"
+    );
+
+    let cli = CondaDenyCliConfig::List {
+        lockfile: None,
+        prefix: Some(vec!["tests/test_conda_prefixes/test-env".into()]),
+        platform: None,
+        environment: None,
+        ignore_pypi: None,
+        output: Some(OutputFormat::Template),
+        template: None,
+        filter: None,
+        sort_by: None,
+        columns: None,
+        aggregate: None,
+        group_by: None,
+    };
+    let CondaDenyConfig::List(list_config) = get_config_options(None, cli).unwrap() else {
+        panic!()
+    };
+    assert!(list(list_config, Vec::new()).is_err());
+}
+
+#[rstest]
+#[case::list_sarif(
+    CondaDenyCliConfig::List {
+        lockfile: None,
+        prefix: Some(vec!["tests/test_conda_prefixes/test-env".into()]),
+        platform: None,
+        environment: None,
+        ignore_pypi: None,
+        output: Some(OutputFormat::Sarif),
+        template: None,
+        filter: None,
+        sort_by: None,
+        columns: None,
+        aggregate: None,
+        group_by: None,
+    },
+    "--output sarif is not supported by list"
+)]
+#[case::why_markdown(
+    CondaDenyCliConfig::Why {
+        package: "openssl".to_string(),
+        lockfile: Some(vec!["tests/test_default_use_case/pixi.lock".into()]),
+        prefix: None,
+        platform: None,
+        environment: None,
+        output: Some(OutputFormat::Markdown),
+    },
+    "--output markdown is not supported by why"
+)]
+#[case::licenses_junit(
+    CondaDenyCliConfig::Licenses {
+        lockfile: None,
+        prefix: Some(vec!["tests/test_conda_prefixes/test-env".into()]),
+        platform: None,
+        environment: None,
+        osi: Some(true),
+        policy: None,
+        by_id: false,
+        ignore_pypi: None,
+        output: Some(OutputFormat::Junit),
+        template: None,
+    },
+    "--output junit is not supported by licenses"
+)]
+fn test_unsupported_output_format(#[case] cli: CondaDenyCliConfig, #[case] expected: &str) {
+    let error = get_config_options(None, cli).unwrap_err();
+    assert_eq!(error.to_string(), expected);
+}
+
+#[rstest]
+#[case::expression(false)]
+#[case::by_id(true)]
+fn test_licenses(#[case] by_id: bool, mut out: Vec<u8>) {
+    let cli = CondaDenyCliConfig::Licenses {
+        lockfile: None,
+        prefix: Some(vec!["tests/test_conda_prefixes/test-env".into()]),
+        platform: None,
+        environment: None,
+        osi: Some(true),
+        policy: None,
+        by_id,
+        ignore_pypi: None,
+        output: Some(OutputFormat::Json),
+        template: None,
+    };
+    let CondaDenyConfig::Licenses(licenses_config) = get_config_options(None, cli).unwrap() else {
+        panic!()
+    };
+
+    licenses(licenses_config, &mut out).unwrap();
+    let output: serde_json::Value = serde_json::from_slice(&out).unwrap();
+    let summary = |license: &str| {
+        output["licenses"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|summary| summary["license"] == license)
+            .cloned()
+    };
+
+    let mit = summary("MIT").unwrap();
+    assert_eq!(mit["verdict"], "safe");
+    assert_eq!(mit["count"], mit["packages"].as_array().unwrap().len());
+    assert!(mit["packages"].as_array().unwrap().contains(
+        &serde_json::json!({ "name": "six", "versions": ["1.16.0"], "verdict": "safe" })
+    ));
+
+    // ncurses is licensed under "X11 AND BSD-3-Clause"
+    let ncurses_in = |license: &str| {
+        summary(license).is_some_and(|summary| {
+            summary["packages"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .any(|package| package["name"] == "ncurses")
+        })
+    };
+    assert_eq!(ncurses_in("BSD-3-Clause AND X11"), !by_id);
+    assert_eq!(ncurses_in("X11"), by_id);
+    assert_eq!(ncurses_in("BSD-3-Clause"), by_id);
+}
+
+#[rstest]
+fn test_list_filter_columns(mut out: Vec<u8>) {
+    let cli = CondaDenyCliConfig::List {
+        lockfile: None,
+        prefix: Some(vec!["tests/test_conda_prefixes/test-env".into()]),
+        platform: None,
+        environment: None,
+        ignore_pypi: None,
+        output: Some(OutputFormat::Csv),
+        template: None,
+        filter: Some(vec![
+            "license~GPL or state=invalid".parse().unwrap(),
+            "not name=readline".parse().unwrap(),
+        ]),
+        sort_by: Some(vec![Field::License, Field::Name]),
+        columns: Some(vec![
+            Field::Name,
+            Field::Version,
+            Field::License,
+            Field::State,
+        ]),
+        aggregate: None,
+        group_by: None,
+    };
+    let CondaDenyConfig::List(list_config) = get_config_options(None, cli).unwrap() else {
+        panic!()
+    };
+
+    list(list_config, &mut out).unwrap();
+    insta::assert_snapshot!(String::from_utf8(out).unwrap());
+}
+
+#[rstest]
+fn test_list_filter_verdict() {
+    let cli = CondaDenyCliConfig::List {
+        lockfile: None,
+        prefix: Some(vec!["tests/test_conda_prefixes/test-env".into()]),
+        platform: None,
+        environment: None,
+        ignore_pypi: None,
+        output: None,
+        template: None,
+        filter: Some(vec!["verdict=unsafe".parse().unwrap()]),
+        sort_by: None,
+        columns: None,
+        aggregate: None,
+        group_by: None,
+    };
+    let error = get_config_options(None, cli).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "The verdict field is only available with check"
+    );
+}
+
+#[rstest]
+fn test_check_filter(mut out: Vec<u8>) {
+    let filter: Filter = "verdict=unsafe and name=lib*".parse().unwrap();
+    let cli = CondaDenyCliConfig::Check {
+        lockfile: None,
+        prefix: Some(vec!["tests/test_conda_prefixes/test-env".into()]),
+        platform: None,
+        environment: None,
+        osi: Some(true),
+        policy: None,
+        fail_on: None,
+        pulled_in_by: false,
+        runtime_closure: None,
+        baseline: None,
+        write_baseline: None,
+        license_history: None,
+        ignore_pypi: None,
+        output: Some(OutputFormat::Json),
+        template: None,
+        filter: Some(vec![filter]),
+        sort_by: Some(vec![Field::Name]),
+        aggregate: None,
+        group_by: None,
+    };
+    let CondaDenyConfig::Check(check_config) = get_config_options(None, cli).unwrap() else {
+        panic!()
+    };
+
+    assert!(check(check_config, &mut out).is_err());
+    let output: serde_json::Value = serde_json::from_slice(&out).unwrap();
+    let names: Vec<&str> = packages_with_result(&output, "unsafe")
+        .into_iter()
+        .map(|info| info["package_name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, vec!["libcxx", "libgfortran", "libgfortran5"]);
+    assert!(packages_with_result(&output, "safe").is_empty());
+}
+
+#[rstest]
+fn test_check_filter_keeps_exit_status(mut out: Vec<u8>) {
+    let cli = CondaDenyCliConfig::Check {
+        lockfile: None,
+        prefix: Some(vec!["tests/test_conda_prefixes/test-env".into()]),
+        platform: None,
+        environment: None,
+        osi: Some(true),
+        policy: None,
+        fail_on: None,
+        pulled_in_by: false,
+        runtime_closure: None,
+        baseline: None,
+        write_baseline: None,
+        license_history: None,
+        ignore_pypi: None,
+        output: Some(OutputFormat::Json),
+        template: None,
+        filter: Some(vec!["verdict=safe".parse().unwrap()]),
+        sort_by: None,
+        aggregate: None,
+        group_by: None,
+    };
+    let CondaDenyConfig::Check(check_config) = get_config_options(None, cli).unwrap() else {
+        panic!()
+    };
+
+    // The unsafe packages are filtered out of the output, but still fail the check
+    assert!(check(check_config, &mut out).is_err());
+    let output: serde_json::Value = serde_json::from_slice(&out).unwrap();
+    assert!(packages_with_result(&output, "unsafe").is_empty());
+    assert!(!packages_with_result(&output, "safe").is_empty());
+}
+
+#[rstest]
+#[case(None, 2)]
+#[case(Some(true), 1)]
+fn test_list_aggregate(mut out: Vec<u8>, #[case] aggregate: Option<bool>, #[case] entries: usize) {
+    let cli = CondaDenyCliConfig::List {
+        lockfile: Some(vec!["tests/default_pixi.lock".into()]),
+        prefix: None,
+        platform: None,
+        environment: None,
+        ignore_pypi: None,
+        output: Some(OutputFormat::Json),
+        template: None,
+        filter: Some(vec!["name=binutils".parse().unwrap()]),
+        sort_by: None,
+        columns: None,
+        aggregate,
+        group_by: None,
+    };
+    let CondaDenyConfig::List(list_config) = get_config_options(None, cli).unwrap() else {
+        panic!()
+    };
+
+    list(list_config, &mut out).unwrap();
+    let output: serde_json::Value = serde_json::from_slice(&out).unwrap();
+    let license_infos = output["packages"].as_array().unwrap();
+    assert_eq!(license_infos.len(), entries);
+    if aggregate == Some(true) {
+        assert_eq!(license_infos[0]["platform"], serde_json::Value::Null);
+        assert_eq!(
+            license_infos[0]["aggregation"]["platforms"],
+            serde_json::json!(["linux-64", "linux-aarch64"])
+        );
+    }
+}
+
+#[rstest]
+fn test_aggregate_with_sarif() {
+    let cli = CondaDenyCliConfig::Check {
+        lockfile: Some(vec!["tests/default_pixi.lock".into()]),
+        prefix: None,
+        platform: None,
+        environment: None,
+        osi: Some(true),
+        policy: None,
+        fail_on: None,
+        pulled_in_by: false,
+        runtime_closure: None,
+        baseline: None,
+        write_baseline: None,
+        license_history: None,
+        ignore_pypi: None,
+        output: Some(OutputFormat::Sarif),
+        template: None,
+        filter: None,
+        sort_by: None,
+        aggregate: Some(true),
+        group_by: None,
+    };
+    let error = get_config_options(None, cli).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "--aggregate cannot be used with SARIF or JUnit output, which report each platform"
+    );
+}
+
+#[rstest]
+fn test_list_group_by(_colored_control: (), mut out: Vec<u8>) {
+    let cli = CondaDenyCliConfig::List {
+        lockfile: Some(vec![
+            "tests/default_pixi.lock".into(),
+            "tests/test_default_use_case/pixi.lock".into(),
+        ]),
+        prefix: None,
+        platform: Some(vec![Platform::Linux64]),
+        environment: None,
+        ignore_pypi: None,
+        output: None,
+        template: None,
+        filter: Some(vec!["name=bzip2".parse().unwrap()]),
+        sort_by: None,
+        columns: None,
+        aggregate: None,
+        group_by: Some(vec![GroupBy::Lockfile, GroupBy::Environment]),
+    };
+    let CondaDenyConfig::List(list_config) = get_config_options(None, cli).unwrap() else {
+        panic!()
+    };
+
+    list(list_config, &mut out).unwrap();
+    insta::assert_snapshot!(String::from_utf8(out).unwrap());
+}
+
+#[rstest]
+fn test_list_lockfile_column(mut out: Vec<u8>) {
+    let cli = CondaDenyCliConfig::List {
+        lockfile: Some(vec![
+            "tests/default_pixi.lock".into(),
+            "tests/test_default_use_case/pixi.lock".into(),
+        ]),
+        prefix: None,
+        platform: Some(vec![Platform::Linux64]),
+        environment: None,
+        ignore_pypi: None,
+        output: Some(OutputFormat::Json),
+        template: None,
+        filter: Some(vec!["name=bzip2 and lockfile=*use_case*".parse().unwrap()]),
+        sort_by: None,
+        columns: Some(vec![Field::Name, Field::Lockfile]),
+        aggregate: None,
+        group_by: None,
+    };
+    let CondaDenyConfig::List(list_config) = get_config_options(None, cli).unwrap() else {
+        panic!()
+    };
+
+    list(list_config, &mut out).unwrap();
+    let output: serde_json::Value = serde_json::from_slice(&out).unwrap();
+    assert_eq!(
+        output["rows"],
+        serde_json::json!([{
+            "name": "bzip2",
+            "lockfile": [
+                "tests/default_pixi.lock",
+                "tests/test_default_use_case/pixi.lock"
+            ],
+        }])
+    );
+}
+
+#[rstest]
+fn test_json_output_schema(mut out: Vec<u8>) {
+    let cli = CondaDenyCliConfig::List {
+        lockfile: Some(vec!["tests/default_pixi.lock".into()]),
+        prefix: None,
+        platform: Some(vec![Platform::Linux64]),
+        environment: None,
+        ignore_pypi: None,
+        output: Some(OutputFormat::Json),
+        template: None,
+        filter: Some(vec!["name=archspec".parse().unwrap()]),
+        sort_by: None,
+        columns: None,
+        aggregate: None,
+        group_by: None,
+    };
+    let CondaDenyConfig::List(list_config) = get_config_options(None, cli).unwrap() else {
+        panic!()
+    };
+
+    list(list_config, &mut out).unwrap();
+    let output: serde_json::Value = serde_json::from_slice(&out).unwrap();
+
+    assert_eq!(output["schema_version"], 1);
+    assert_eq!(output["metadata"]["tool"], "conda-deny");
+    assert_eq!(output["metadata"]["command"], "list");
+    assert_eq!(
+        output["metadata"]["lockfiles"],
+        serde_json::json!(["tests/default_pixi.lock"])
+    );
+    assert_eq!(
+        output["metadata"]["platforms"],
+        serde_json::json!(["linux-64"])
+    );
+    assert_eq!(
+        output["packages"][0]["license"],
+        serde_json::json!({
+            "raw": "MIT OR Apache-2.0",
+            "state": "valid",
+            "expression": {
+                "type": "or",
+                "operands": [
+                    { "type": "license", "license": "MIT" },
+                    { "type": "license", "license": "Apache-2.0" },
+                ],
+            },
+        })
+    );
+}
+
+/// Asserts that a JSON document matches `schema/output.schema.json`.
+fn assert_matches_schema(output: &serde_json::Value) {
+    let schema: serde_json::Value =
+        serde_json::from_str(include_str!("../schema/output.schema.json")).unwrap();
+    let validator = jsonschema::validator_for(&schema).unwrap();
+    let errors: Vec<String> = validator
+        .iter_errors(output)
+        .map(|error| format!("{}: {error}", error.instance_path()))
+        .collect();
+    assert!(errors.is_empty(), "{errors:#?}");
+}
+
+#[rstest]
+#[case::check(CondaDenyCliConfig::Check {
+    lockfile: Some(vec!["tests/test_default_use_case/pixi.lock".into()]),
+    prefix: None,
+    platform: None,
+    environment: None,
+    osi: Some(true),
+    policy: None,
+    fail_on: None,
+    pulled_in_by: true,
+    runtime_closure: None,
+    baseline: None,
+    write_baseline: None,
+    license_history: None,
+    ignore_pypi: None,
+    output: Some(OutputFormat::Json),
+    template: None,
+    filter: None,
+    sort_by: None,
+    aggregate: None,
+    group_by: None,
+})]
+#[case::list(CondaDenyCliConfig::List {
+    lockfile: None,
+    prefix: Some(vec!["tests/test_conda_prefixes/test-env".into()]),
+    platform: None,
+    environment: None,
+    ignore_pypi: None,
+    output: Some(OutputFormat::Json),
+    template: None,
+    filter: None,
+    sort_by: None,
+    columns: None,
+    aggregate: None,
+    group_by: None,
+})]
+#[case::list_aggregate(CondaDenyCliConfig::List {
+    lockfile: Some(vec!["tests/default_pixi.lock".into()]),
+    prefix: None,
+    platform: None,
+    environment: None,
+    ignore_pypi: None,
+    output: Some(OutputFormat::Json),
+    template: None,
+    filter: None,
+    sort_by: None,
+    columns: None,
+    aggregate: Some(true),
+    group_by: None,
+})]
+#[case::list_columns(CondaDenyCliConfig::List {
+    lockfile: Some(vec!["tests/default_pixi.lock".into()]),
+    prefix: None,
+    platform: None,
+    environment: None,
+    ignore_pypi: None,
+    output: Some(OutputFormat::Json),
+    template: None,
+    filter: None,
+    sort_by: None,
+    columns: Some(vec![Field::Name, Field::License, Field::Environment]),
+    aggregate: None,
+    group_by: None,
+})]
+#[case::licenses(CondaDenyCliConfig::Licenses {
+    lockfile: None,
+    prefix: Some(vec!["tests/test_conda_prefixes/test-env".into()]),
+    platform: None,
+    environment: None,
+    osi: Some(true),
+    policy: None,
+    by_id: false,
+    ignore_pypi: None,
+    output: Some(OutputFormat::Json),
+    template: None,
+})]
+#[case::diff(diff_cli(
+    "tests/test_default_use_case/pixi.lock",
+    "tests/default_pixi.lock",
+    OutputFormat::Json,
+))]
+#[case::explain(CondaDenyCliConfig::Explain {
+    package: "python".to_string(),
+    lockfile: Some(vec!["tests/default_pixi.lock".into()]),
+    prefix: None,
+    platform: None,
+    environment: None,
+    osi: Some(true),
+    policy: None,
+    ignore_pypi: None,
+    output: Some(OutputFormat::Json),
+})]
+#[case::why(CondaDenyCliConfig::Why {
+    package: "openssl".to_string(),
+    lockfile: Some(vec!["tests/test_default_use_case/pixi.lock".into()]),
+    prefix: None,
+    platform: None,
+    environment: None,
+    output: Some(OutputFormat::Json),
+})]
+fn test_json_output_matches_schema(#[case] cli: CondaDenyCliConfig, mut out: Vec<u8>) {
+    // check and diff fail on unsafe licenses, but write the output anyway
+    let _ = match get_config_options(None, cli).unwrap() {
+        CondaDenyConfig::Check(config) => check(config, &mut out),
+        CondaDenyConfig::List(config) => list(config, &mut out),
+        CondaDenyConfig::Licenses(config) => licenses(config, &mut out),
+        CondaDenyConfig::Diff(config) => diff(config, &mut out),
+        CondaDenyConfig::Explain(config) => explain(config, &mut out),
+        CondaDenyConfig::Why(config) => why(config, &mut out),
+        _ => panic!("The command has no JSON output"),
+    };
+    let output: serde_json::Value = serde_json::from_slice(&out).unwrap();
+
+    assert_matches_schema(&output);
+}